@@ -7,17 +7,13 @@ fn main() -> io::Result<()> {
     // 调试输出：开始执行 build.rs
     println!("cargo:warning=Build script started!");
 
-    // 1. 获取 OUT_DIR（形如 target/release/build/xxx/out）
+    // 1. 获取 OUT_DIR（形如 <target-dir>/[<triple>/]<profile>/build/xxx/out）
     let out_dir = env::var("OUT_DIR").unwrap();
     println!("cargo:warning=OUT_DIR: {}", out_dir);
 
-    let mut dest_dir = PathBuf::from(&out_dir);
-
-    // 2. 往上退三级，把路径修正到真正的可执行文件输出目录 (target/release)
-    dest_dir.pop(); // out
-    dest_dir.pop(); // build/xxx
-    dest_dir.pop(); // build
-    // 现在 dest_dir 理论上就是 target/release
+    // 2. 计算真正的可执行文件输出目录（如 target/debug、target/release、
+    //    target/x86_64-pc-windows-msvc/release 或自定义 --target-dir 下的对应目录）
+    let dest_dir = profile_dir(Path::new(&out_dir));
     println!("cargo:warning=Destination directory: {:?}", dest_dir);
 
     // 3. 准备要复制的源目录和目标目录
@@ -28,6 +24,16 @@ fn main() -> io::Result<()> {
     println!("cargo:warning=Source directory: {:?}", models_src);
     println!("cargo:warning=Destination directory: {:?}", models_dst);
 
+    // 如果 models 目录里的内容或跳过复制的开关有变动，就让 Cargo 重新执行构建脚本
+    println!("cargo:rerun-if-changed=models");
+    println!("cargo:rerun-if-env-changed={}", SKIP_MODEL_COPY_ENV);
+
+    // 按绝对路径加载模型或使用自动下载时不需要复制，设置该环境变量即可跳过（模型文件很大，复制很慢）
+    if skip_model_copy() {
+        println!("cargo:warning={} is set, skipping models directory copy", SKIP_MODEL_COPY_ENV);
+        return Ok(());
+    }
+
     // 检查源目录是否存在
     if !models_src.exists() {
         println!("cargo:warning=models directory does not exist!");
@@ -42,15 +48,42 @@ fn main() -> io::Result<()> {
     }
     println!("cargo:warning=Models directory copied successfully!");
 
-    // 5. 如果 models 目录里的内容有变动，就让 Cargo 重新执行构建脚本
-    println!("cargo:rerun-if-changed=models");
-
     // 调试输出：构建脚本完成
     println!("cargo:warning=Build script finished!");
 
     Ok(())
 }
 
+/// 设为 `1`/`true` 时不复制 models 目录
+const SKIP_MODEL_COPY_ENV: &str = "LAT_SKIP_MODEL_COPY";
+
+/// 是否设置了 `LAT_SKIP_MODEL_COPY`；空值、`0` 和 `false` 视为未设置
+fn skip_model_copy() -> bool {
+    env::var(SKIP_MODEL_COPY_ENV)
+        .is_ok_and(|value| !matches!(value.trim().to_lowercase().as_str(), "" | "0" | "false"))
+}
+
+/// 根据 OUT_DIR 推算可执行文件所在的 profile 目录。
+///
+/// OUT_DIR 总是位于 `<profile 目录>/build/<包名-哈希>/out`，无论是 debug/release、
+/// 自定义 profile、`--target-dir`/`CARGO_TARGET_DIR` 还是交叉编译（多一级 `<triple>`），
+/// 因此向上查找名为 `build` 的目录，其父目录就是 profile 目录。
+/// 找不到时退回到 `CARGO_TARGET_DIR`（默认 `target`）下的 `PROFILE` 目录。
+fn profile_dir(out_dir: &Path) -> PathBuf {
+    if let Some(build_dir) = out_dir
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == "build"))
+    {
+        if let Some(parent) = build_dir.parent() {
+            return parent.to_path_buf();
+        }
+    }
+
+    let target_dir = env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string());
+    let profile = env::var("PROFILE").unwrap_or_else(|_| "debug".to_string());
+    PathBuf::from(target_dir).join(profile)
+}
+
 /// 递归复制整个目录
 fn copy_dir_all(src: &Path, dst: &Path) -> io::Result<()> {
     if src.is_dir() {
@@ -0,0 +1,160 @@
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam_channel::{bounded, Receiver, TryRecvError};
+use log::{error, info, warn};
+
+use crate::download_model::fetch_file;
+use crate::model_select::{WhisperModel, WHISPER_MODELS};
+use crate::transcribe::{Whisper, WhisperConfig};
+
+/// 某个模型下载或加载失败后，多久之内不再尝试切换到它
+const RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// 自动切换 Whisper 模型的条件
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveModelConfig {
+    /// 队列中等待转录的音频超过该时长（秒）视为跟不上
+    pub max_queue_secs: f64,
+    /// 持续跟不上超过该时长后换用小一号的模型
+    pub downgrade_after: Duration,
+    /// 负载下降后是否换回较大的模型（不超过启动时的模型）
+    pub upgrade: bool,
+    /// 队列为空且实时率低于该值时视为负载较低
+    pub upgrade_rtf: f64,
+    /// 负载持续较低超过该时长后换用大一号的模型
+    pub upgrade_after: Duration,
+}
+
+impl Default for AdaptiveModelConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_secs: 5.0,
+            downgrade_after: Duration::from_secs(10),
+            upgrade: false,
+            upgrade_rtf: 0.3,
+            upgrade_after: Duration::from_secs(60),
+        }
+    }
+}
+
+/// 根据队列延迟和实时率在 `WHISPER_MODELS` 中自动降级或升级模型，
+/// 让 CPU 负载突增时字幕仍能跟上。新模型在后台线程中加载（必要时先下载），
+/// 加载完成后由 `observe` 交给调用方替换，加载期间原模型照常转录。
+pub struct AdaptiveModel {
+    config: AdaptiveModelConfig,
+    /// 新模型沿用的转录参数
+    whisper_config: WhisperConfig,
+    /// 当前模型在 `WHISPER_MODELS` 中的下标
+    current: usize,
+    /// 启动时的模型，升级时不超过它
+    ceiling: usize,
+    overloaded_since: Option<Instant>,
+    idle_since: Option<Instant>,
+    /// 正在后台加载的模型下标及其结果
+    loading: Option<(usize, Receiver<anyhow::Result<Whisper>>)>,
+    /// 最近一次加载失败的模型下标及失败时间，`RETRY_AFTER` 之内不再尝试
+    failed: Option<(usize, Instant)>,
+}
+
+impl AdaptiveModel {
+    /// `model` 为启动时使用的模型，不在 `WHISPER_MODELS` 中时返回 `None`
+    pub fn new(model: &WhisperModel, whisper_config: WhisperConfig, config: AdaptiveModelConfig) -> Option<Self> {
+        let current = WHISPER_MODELS.iter().position(|m| m.file == model.file)?;
+        Some(Self {
+            config,
+            whisper_config,
+            current,
+            ceiling: current,
+            overloaded_since: None,
+            idle_since: None,
+            loading: None,
+            failed: None,
+        })
+    }
+
+    /// 根据最新的队列延迟（秒）和实时率决定是否切换模型。
+    /// 返回加载完成、应当换上的模型及其名称
+    pub fn observe(&mut self, queue_secs: f64, real_time_factor: f64) -> Option<(Whisper, &'static str)> {
+        if let Some((index, receiver)) = &self.loading {
+            let index = *index;
+            let result = match receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => Err(anyhow::anyhow!("loader thread exited")),
+            };
+            self.loading = None;
+            self.overloaded_since = None;
+            self.idle_since = None;
+            return match result {
+                Ok(whisper) => {
+                    self.current = index;
+                    Some((whisper, WHISPER_MODELS[index].name))
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to load Whisper model '{}', not retrying for {:?}: {:#}",
+                        WHISPER_MODELS[index].name, RETRY_AFTER, e
+                    );
+                    self.failed = Some((index, Instant::now()));
+                    None
+                }
+            };
+        }
+
+        let now = Instant::now();
+        if queue_secs > self.config.max_queue_secs {
+            self.idle_since = None;
+            let since = *self.overloaded_since.get_or_insert(now);
+            if self.current > 0 && now - since >= self.config.downgrade_after {
+                warn!(
+                    "Transcription queue has stayed above {:.1}s for {:?}, switching to a smaller model",
+                    self.config.max_queue_secs, self.config.downgrade_after
+                );
+                self.start_loading(self.current - 1);
+            }
+            return None;
+        }
+        self.overloaded_since = None;
+
+        let idle = queue_secs == 0.0 && real_time_factor < self.config.upgrade_rtf;
+        if !(self.config.upgrade && idle && self.current < self.ceiling) {
+            self.idle_since = None;
+            return None;
+        }
+        let since = *self.idle_since.get_or_insert(now);
+        if now - since >= self.config.upgrade_after {
+            info!(
+                "Real-time factor has stayed below {:.2} for {:?}, switching to a larger model",
+                self.config.upgrade_rtf, self.config.upgrade_after
+            );
+            self.start_loading(self.current + 1);
+        }
+        None
+    }
+
+    /// 在后台线程中加载 `WHISPER_MODELS[index]`，模型文件不存在时先下载。
+    /// 该模型在 `RETRY_AFTER` 之内加载失败过时不做任何事
+    fn start_loading(&mut self, index: usize) {
+        if self
+            .failed
+            .is_some_and(|(failed, at)| failed == index && at.elapsed() < RETRY_AFTER)
+        {
+            return;
+        }
+        let model = &WHISPER_MODELS[index];
+        info!("Loading Whisper model '{}' in the background", model.name);
+        let whisper_config = self.whisper_config.clone();
+        let (sender, receiver) = bounded(1);
+        thread::spawn(move || {
+            let path = model.path();
+            let result = if Path::new(&path).exists() {
+                Ok(())
+            } else {
+                fetch_file(&model.download_url(), Path::new(&path))
+            };
+            let _ = sender.send(result.and_then(|_| Whisper::load(&path, &whisper_config)));
+        });
+        self.loading = Some((index, receiver));
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use log::info;
+
+use crate::capture::load_wav;
+use crate::transcribe::Whisper;
+
+/// 每次送入 Whisper 的窗口长度（秒），与 Whisper 的 30 秒输入窗口一致
+const WINDOW_SECS: usize = 30;
+
+/// 一次基准测试的结果
+#[derive(Debug)]
+pub struct BenchmarkReport {
+    pub reference_words: usize,
+    pub hypothesis_words: usize,
+    pub edit_distance: usize,
+    pub audio: Duration,
+    pub latencies: Vec<Duration>,
+}
+
+impl BenchmarkReport {
+    /// 词错误率：(替换 + 删除 + 插入) / 参考词数
+    pub fn wer(&self) -> f64 {
+        if self.reference_words == 0 {
+            return 0.0;
+        }
+        self.edit_distance as f64 / self.reference_words as f64
+    }
+
+    /// 总处理耗时 / 音频时长
+    pub fn real_time_factor(&self) -> f64 {
+        let total: Duration = self.latencies.iter().sum();
+        total.as_secs_f64() / self.audio.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// 打印结果摘要
+    pub fn print(&self) {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let percentile = |p: f64| -> Duration {
+            if sorted.is_empty() {
+                return Duration::ZERO;
+            }
+            sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+        };
+        println!("Audio duration:   {:.1}s", self.audio.as_secs_f64());
+        println!("Reference words:  {}", self.reference_words);
+        println!("Hypothesis words: {}", self.hypothesis_words);
+        println!("Edit distance:    {}", self.edit_distance);
+        println!("WER:              {:.2}%", self.wer() * 100.0);
+        println!("Real-time factor: {:.3}", self.real_time_factor());
+        println!(
+            "Window latency:   p50 {:?}, p95 {:?}, max {:?} ({} windows of {}s)",
+            percentile(0.5),
+            percentile(0.95),
+            percentile(1.0),
+            self.latencies.len(),
+            WINDOW_SECS
+        );
+    }
+}
+
+/// 转录 `wav_path`，并与 `reference_path` 中的参考文本计算 WER
+pub fn run(whisper: &mut Whisper, wav_path: &str, reference_path: &str) -> anyhow::Result<BenchmarkReport> {
+    let reference = std::fs::read_to_string(reference_path)
+        .with_context(|| format!("failed to read reference transcript {}", reference_path))?;
+    let samples = load_wav(wav_path)?;
+    info!("Benchmarking {} ({} samples)", wav_path, samples.len());
+
+    let mut hypothesis = String::new();
+    let mut latencies = Vec::new();
+    for window in samples.chunks(16_000 * WINDOW_SECS) {
+        let started = Instant::now();
+        if let Some(text) = whisper.transcribe_samples(window.to_vec()) {
+            hypothesis.push_str(&text);
+            hypothesis.push(' ');
+        }
+        latencies.push(started.elapsed());
+    }
+
+    let reference_words = normalize_words(&reference);
+    let hypothesis_words = normalize_words(&hypothesis);
+    Ok(BenchmarkReport {
+        reference_words: reference_words.len(),
+        hypothesis_words: hypothesis_words.len(),
+        edit_distance: word_edit_distance(&reference_words, &hypothesis_words),
+        audio: Duration::from_secs_f64(samples.len() as f64 / 16_000.0),
+        latencies,
+    })
+}
+
+/// 统一大小写并去掉标点（保留单词内的撇号），再按空白切分成单词
+fn normalize_words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '\'' { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .map(|w| w.trim_matches('\'').to_string())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// 单词级别的 Levenshtein 距离
+fn word_edit_distance(reference: &[String], hypothesis: &[String]) -> usize {
+    let mut prev: Vec<usize> = (0..=hypothesis.len()).collect();
+    for (i, r) in reference.iter().enumerate() {
+        let mut curr = vec![i + 1; hypothesis.len() + 1];
+        for (j, h) in hypothesis.iter().enumerate() {
+            let substitution = prev[j] + usize::from(r != h);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[hypothesis.len()]
+}
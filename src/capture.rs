@@ -1,90 +1,345 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{SampleFormat, Stream, StreamConfig};
+use cpal::{BufferSize, Device, SampleFormat, Stream, StreamConfig, SupportedBufferSize, SupportedStreamConfig};
 use samplerate::{convert, ConverterType};
 use crossbeam_channel::Sender;
-use std::sync::{Arc, Mutex};
-use log::{info, error};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use log::{info, debug, warn, error};
+use anyhow::Context;
 
-/// 一个简单的 `AudioCapture` 结构，持有一个可选的 `Stream`
+use crate::ring::{ring_buffer, Consumer};
+
+/// 采集相关的可调参数
+#[derive(Debug, Clone)]
+pub struct CaptureConfig {
+    /// 累计多长的音频（秒）后发送一个块
+    pub chunk_secs: f64,
+    /// 若设置，发送的块长度会向下取整为该帧长（毫秒）的整数倍，
+    /// 余下不足一帧的数据留到下一个块，使下游的填充与重叠计算可预期。
+    ///
+    /// 16k 采样率下 Whisper 的 mel 帧移为 160 个采样点（10ms），
+    /// 发送门槛里的 320 即两个帧移（20ms）：`send_counter_threshold = 16000 / 320 * 0.6`，
+    /// 相当于至少经过 30 次采集回调才发送。帧长取 10ms 的整数倍（如 20ms、30ms）即可与之对齐。
+    pub frame_ms: Option<u32>,
+    /// 输出音频的采样率。Whisper 要求 16000，转录流水线只接受这个值；
+    /// 直接使用 `AudioCapture` 的其他用途（如其他识别引擎）可改用 48000 等更高采样率。
+    /// 块长、帧长等门槛都按该采样率换算。需要在转录的同时拿到高采样率音频时用 `tap`
+    pub target_sample_rate: u32,
+    /// 在 `target_sample_rate` 的输出之外，另以 `AudioTap::sample_rate` 输出一路单声道音频
+    /// （如 16k 转录的同时录制 48k 原始音频）。`None` 时不输出
+    pub tap: Option<AudioTap>,
+    /// 实时重采样使用的算法。`SincBestQuality` 音质最好但非常耗 CPU，
+    /// 在树莓派等弱设备上可改用 `SincFastest` 或 `Linear`。
+    pub resample_quality: ConverterType,
+    /// 向驱动请求的采集缓冲区大小（帧）。较小的缓冲区延迟更低，较大的缓冲区在弱设备上不易丢数据；
+    /// 超出设备支持范围时取最近的可用值。`None` 时由驱动决定
+    pub buffer_frames: Option<u32>,
+    /// 开始（及恢复）采集后丢弃的音频时长（毫秒）。驱动启动时的噪声或爆音常被转录成乱码，
+    /// 丢掉开头这一小段可避免每次会话开始时的错误字幕。0 表示不丢弃
+    pub warmup_ms: u32,
+    /// 设置后把每个发送的块按峰值归一化，使不同音量的音源电平一致。`None` 时不处理
+    pub peak_normalize: Option<PeakNormalizeConfig>,
+    /// 把采集回调线程提升为实时优先级、处理线程提升为高优先级，减少系统负载高时的丢帧（xrun）。
+    /// 需要 `realtime` feature；操作系统拒绝时记录警告并以普通优先级继续
+    pub realtime_priority: bool,
+    /// 缓冲中最早的未发送音频等待超过该时长时，不论是否达到发送门槛都立即发送。
+    /// 语音停在门槛以下、或设备暂时不再送来数据（如回环采集时没有声音播放）时，
+    /// 最后几个词不会一直等到下一个块。实际使用的值不小于两个块长（`chunk_secs`），
+    /// 以免打乱正常的分块。`None` 时只按门槛发送
+    pub max_flush_interval: Option<Duration>,
+    /// 同时采集麦克风与回环设备混音时，以回环信号为参考抑制麦克风中串入的扬声器声音，
+    /// 取值为抑制强度（0~1）。只对混音器生效；`None` 时不处理
+    pub echo_suppression: Option<f32>,
+    /// 多声道混为单声道的方式
+    pub mix: Mix,
+}
+
+/// 与转录音频并行输出的另一路音频。
+///
+/// 混为单声道后单独重采样到 `sample_rate`（与设备采样率相同时不重采样），
+/// 不做预热丢弃、峰值归一化与分块，收到的块长度取决于设备回调
+#[derive(Debug, Clone)]
+pub struct AudioTap {
+    pub sample_rate: u32,
+    pub sender: Sender<Vec<f32>>,
+}
+
+/// 多声道混为单声道的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mix {
+    /// 各声道取平均
+    #[default]
+    Average,
+    /// 声道间相位相反（部分广播信号）时平均后几乎是静音，
+    /// 检测到明显的相位抵消时改为取能量最大的一个声道，其余情况仍取平均
+    LoudnessPreserving,
+}
+
+/// 只开启回声抑制、未指定强度时使用的抑制强度
+pub const DEFAULT_ECHO_STRENGTH: f32 = 0.8;
+
+/// 按块的峰值归一化参数。与随时间跟踪 RMS 的自动增益不同，每个块单独计算增益
+#[derive(Debug, Clone, Copy)]
+pub struct PeakNormalizeConfig {
+    /// 归一化后的峰值（dBFS）
+    pub target_dbfs: f32,
+    /// 最大增益（dB），避免把静音或底噪放大
+    pub max_gain_db: f32,
+}
+
+impl Default for PeakNormalizeConfig {
+    fn default() -> Self {
+        Self {
+            target_dbfs: -3.0,
+            max_gain_db: 20.0,
+        }
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            chunk_secs: 1.1,
+            frame_ms: None,
+            target_sample_rate: 16_000,
+            tap: None,
+            resample_quality: ConverterType::SincMediumQuality,
+            buffer_frames: None,
+            warmup_ms: 150,
+            peak_normalize: None,
+            realtime_priority: false,
+            max_flush_interval: Some(Duration::from_secs(3)),
+            echo_suppression: None,
+            mix: Mix::Average,
+        }
+    }
+}
+
+/// 一个简单的 `AudioCapture` 结构，持有一个可选的 `Stream`。
+///
+/// 采集回调运行在实时音频线程上，只把原始数据写入无锁环形缓冲区，不加锁也不分配内存；
+/// 混音、重采样、攒块和发送都在单独的处理线程中完成。
 pub struct AudioCapture {
     stream: Option<Stream>,
+    device_name: String,
+    /// 处理线程，停止时等待它发送完尾段音频
+    worker: Option<JoinHandle<()>>,
+    control: Arc<WorkerControl>,
+    /// cpal 报告的采集延迟（微秒），`u64::MAX` 表示尚未得知
+    latency_us: Arc<AtomicU64>,
+}
+
+/// 采集回调、处理线程与 `AudioCapture` 之间共享的计数与标志
+#[derive(Default)]
+struct WorkerControl {
+    /// 自上次取走以来的回调次数，用于发送门槛
+    callbacks: AtomicUsize,
+    /// 环形缓冲区已满、整段被丢弃的回调次数
+    overruns: AtomicUsize,
+    /// 要求处理线程丢弃已缓冲的音频（恢复采集时）
+    clear: AtomicBool,
+    /// 要求处理线程处理完剩余数据、发送尾段后退出
+    stop: AtomicBool,
+    /// 采集回调线程提升优先级的结果，见 `PRIORITY_*`；由处理线程记录日志，回调中不写日志
+    priority: AtomicU8,
 }
 
+/// 未请求提升优先级，或结果已记录
+const PRIORITY_NONE: u8 = 0;
+/// 等待采集回调尝试提升优先级
+const PRIORITY_PENDING: u8 = 1;
+const PRIORITY_RAISED: u8 = 2;
+const PRIORITY_DENIED: u8 = 3;
+
+/// 采集回调线程使用的实时优先级（0~99，映射到操作系统的优先级范围）
+#[cfg(feature = "realtime")]
+const REALTIME_PRIORITY: u8 = 70;
+
+/// 处理线程把原始数据变为发送块所需的参数
+struct ChunkParams {
+    input_channels: usize,
+    input_sample_rate: u32,
+    target_sample_rate: u32,
+    resample_quality: ConverterType,
+    send_frames_threshold: usize,
+    send_counter_threshold: usize,
+    frame_samples: Option<usize>,
+    /// 开始采集后丢弃的采样点数（目标采样率下）
+    warmup_samples: usize,
+    peak_normalize: Option<PeakNormalizeConfig>,
+    max_flush_interval: Option<Duration>,
+    mix: Mix,
+    tap: Option<AudioTap>,
+}
+
+/// `samplerate` 支持的最大重采样比例（libsamplerate 的 SRC_MAX_RATIO）
+const MAX_RESAMPLE_RATIO: f64 = 256.0;
+
+/// 判断尾段音频是否含有语音的 RMS 门限（约 -46 dBFS），低于它视为静音不再发送
+const FLUSH_RMS_THRESHOLD: f32 = 0.005;
+
+/// 环形缓冲区可容纳的原始音频时长（秒），处理线程短暂卡顿时不至于丢数据
+const RING_SECS: usize = 2;
+
+/// 处理线程在缓冲区为空时的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
 impl AudioCapture {
+    #[allow(dead_code)]
     /// 创建并启动音频采集，将处理后的音频（16k 单声道）通过 `audio_sender` 发送出去。
-    pub fn new_stream_with_sender(audio_sender: Sender<Vec<f32>>) -> Self {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .expect("Failed to get default output device");
-        info!("Using device: {}", device.name().unwrap_or_else(|_| "Unknown".into()));
+    pub fn new_stream_with_sender(audio_sender: Sender<Vec<f32>>) -> anyhow::Result<Self> {
+        Self::new_stream_with_config(audio_sender, CaptureConfig::default())
+    }
 
+    /// 使用自定义参数，从默认输出设备采集。
+    /// 没有输出设备（如无声卡的服务器或 CI 环境）时返回错误，而不是 panic
+    pub fn new_stream_with_config(audio_sender: Sender<Vec<f32>>, config: CaptureConfig) -> anyhow::Result<Self> {
+        let host = cpal::default_host();
+        let device = host.default_output_device().ok_or_else(|| {
+            anyhow::anyhow!("no audio output device found; use --device or --stdin-format to choose another input")
+        })?;
         let supported_config = device
             .default_output_config()
-            .expect("Failed to get default output config");
-        info!("Default output config: {:?}", supported_config);
+            .context("failed to get the default output config of the audio device")?;
+        Self::start(device, supported_config, audio_sender, config)
+    }
+
+    /// 按名称查找设备并开始采集。
+    ///
+    /// 优先匹配输入设备（麦克风），其次匹配输出设备（回环采集系统声音）。
+    pub fn new_stream_for_device(
+        name: &str,
+        audio_sender: Sender<Vec<f32>>,
+        config: CaptureConfig,
+    ) -> anyhow::Result<Self> {
+        let host = cpal::default_host();
+        if let Some(device) = host
+            .input_devices()?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        {
+            let supported_config = device.default_input_config()?;
+            return Self::start(device, supported_config, audio_sender, config);
+        }
+        if let Some(device) = host
+            .output_devices()?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        {
+            let supported_config = device.default_output_config()?;
+            return Self::start(device, supported_config, audio_sender, config);
+        }
+        anyhow::bail!("audio device not found: {}", name)
+    }
+
+    /// 名称对应的设备是否为输出设备，即采集的是回环信号。
+    /// 与 `new_stream_for_device` 一致，同名的输入设备优先
+    pub fn is_loopback_device(name: &str) -> bool {
+        let host = cpal::default_host();
+        let matches = |device: &Device| device.name().map(|n| n == name).unwrap_or(false);
+        let is_input = host.input_devices().is_ok_and(|mut devices| devices.any(|d| matches(&d)));
+        let is_output = host.output_devices().is_ok_and(|mut devices| devices.any(|d| matches(&d)));
+        !is_input && is_output
+    }
+
+    /// 在指定设备上建立输入流，并把处理后的音频发送到 `audio_sender`
+    fn start(
+        device: Device,
+        supported_config: SupportedStreamConfig,
+        audio_sender: Sender<Vec<f32>>,
+        config: CaptureConfig,
+    ) -> anyhow::Result<Self> {
+        let device_name = device.name().unwrap_or_else(|_| "Unknown".into());
+        info!("Using device: {}", device_name);
+        info!("Default stream config: {:?}", supported_config);
 
         // 把 `SupportedStreamConfig` 转成通用的 `StreamConfig`
         let sample_format = supported_config.sample_format();
-        let stream_config: StreamConfig = supported_config.into();
+        let supported_buffer_size = *supported_config.buffer_size();
+        let mut stream_config: StreamConfig = supported_config.into();
+        if let Some(frames) = config.buffer_frames {
+            let frames = fit_buffer_size(frames, &supported_buffer_size);
+            stream_config.buffer_size = BufferSize::Fixed(frames);
+        }
+        info!("Capture buffer size: {:?}", stream_config.buffer_size);
 
         let err_fn = |err| error!("Stream error: {}", err);
 
-        let data_accumulator = Arc::new(Mutex::new(DataAccumulator {
-            buffer: Vec::new(),
-            counter: 0,
-        }));
-
         let input_channels = stream_config.channels as usize;
         let input_sample_rate = stream_config.sample_rate.0;
-        let target_sample_rate = 16_000;
+        let target_sample_rate = config.target_sample_rate;
+
+        // 部分虚拟设备会报告 0 声道或 0Hz，后续的混音和重采样无法处理这种配置
+        if input_channels == 0 {
+            anyhow::bail!("audio device '{}' reports 0 channels: {:?}", device_name, stream_config);
+        }
+        if input_sample_rate == 0 {
+            anyhow::bail!("audio device '{}' reports a 0 Hz sample rate: {:?}", device_name, stream_config);
+        }
+        validate_target_rate(input_sample_rate, target_sample_rate)
+            .with_context(|| format!("cannot capture from '{}'", device_name))?;
+        if let Some(tap) = &config.tap {
+            validate_target_rate(input_sample_rate, tap.sample_rate)
+                .with_context(|| format!("cannot tap audio from '{}'", device_name))?;
+        }
 
-        // 阈值大约为 1.1 秒、且累计调用次数也到达一定量再发送
-        let send_frames_threshold = (target_sample_rate as f64 * 1.1) as usize;
-        let send_counter_threshold = (16_000.0 / 320.0 * 0.6) as usize;
+        // 20ms 对应的采样点数（16k 下为 320），门槛即 `target / (target / 50) * 0.6`
+        let samples_per_20ms = (target_sample_rate / 50).max(1);
+        let params = ChunkParams {
+            input_channels,
+            input_sample_rate,
+            target_sample_rate,
+            resample_quality: config.resample_quality,
+            // 阈值大约为 `chunk_secs`（默认 1.1 秒）、且累计调用次数也到达一定量再发送
+            send_frames_threshold: (target_sample_rate as f64 * config.chunk_secs) as usize,
+            send_counter_threshold: (target_sample_rate as f64 / samples_per_20ms as f64 * 0.6) as usize,
+            // 帧长（采样点），发送的块长度为其整数倍
+            frame_samples: config
+                .frame_ms
+                .map(|ms| (target_sample_rate as usize * ms as usize / 1000).max(1)),
+            warmup_samples: target_sample_rate as usize * config.warmup_ms as usize / 1000,
+            peak_normalize: config.peak_normalize,
+            mix: config.mix,
+            tap: config.tap,
+            max_flush_interval: config
+                .max_flush_interval
+                .map(|interval| interval.max(Duration::from_secs_f64(config.chunk_secs * 2.0))),
+        };
+
+        let (mut producer, consumer) = ring_buffer(input_sample_rate as usize * input_channels * RING_SECS);
+        let control = Arc::new(WorkerControl::default());
+        let latency_us = Arc::new(AtomicU64::new(u64::MAX));
+        let realtime_priority = config.realtime_priority && cfg!(feature = "realtime");
+        if config.realtime_priority && !realtime_priority {
+            error!("--realtime-priority requires building with the `realtime` feature");
+        }
+        if realtime_priority {
+            control.priority.store(PRIORITY_PENDING, Ordering::Relaxed);
+        }
 
         let stream = match sample_format {
             SampleFormat::F32 => {
                 device.build_input_stream(
                     &stream_config,
                     {
-                        let data_accumulator = Arc::clone(&data_accumulator);
-                        move |data: &[f32], _| {
-                            // 如果是双声道则混合，否则直接拷贝
-                            let mono_samples = if input_channels == 1 {
-                                data.to_vec()
-                            } else if input_channels == 2 {
-                                stereo_to_mono(data)
-                            } else {
-                                panic!("Unsupported number of channels: {}", input_channels);
-                            };
-
-                            // 重采样到 16k
-                            let processed = if input_sample_rate != target_sample_rate {
-                                audio_resample(&mono_samples, input_sample_rate, target_sample_rate)
+                        let control = Arc::clone(&control);
+                        let latency_us = Arc::clone(&latency_us);
+                        // 实时线程：只做原子操作和无锁写入
+                        move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                            // cpal 不暴露回调线程，只能在第一次回调时提升它自己的优先级
+                            if control.priority.load(Ordering::Relaxed) == PRIORITY_PENDING {
+                                let status = if raise_to_realtime() { PRIORITY_RAISED } else { PRIORITY_DENIED };
+                                control.priority.store(status, Ordering::Relaxed);
+                            }
+                            // 回调时刻与采集时刻之差即设备/驱动引入的延迟
+                            let timestamp = info.timestamp();
+                            if let Some(latency) = timestamp.callback.duration_since(&timestamp.capture) {
+                                latency_us.store(latency.as_micros() as u64, Ordering::Relaxed);
+                            }
+                            if producer.push_slice(data) {
+                                control.callbacks.fetch_add(1, Ordering::Relaxed);
                             } else {
-                                mono_samples
-                            };
-
-                            // 累计数据到共享 buffer 里
-                            let mut acc = data_accumulator.lock().unwrap();
-                            acc.buffer.extend(processed);
-                            acc.counter += 1;
-
-                            // 检查是否达到发送门槛
-                            if acc.counter > send_counter_threshold
-                                && acc.buffer.len() >= send_frames_threshold
-                            {
-                                // 把数据拷贝一份，再清空 buffer
-                                let to_send = acc.buffer.clone();
-                                acc.buffer.clear();
-                                acc.counter = 0;
-                                drop(acc);
-
-                                // 将数据发送到主线程或其他处理端
-                                if let Err(e) = audio_sender.send(to_send) {
-                                    eprintln!("Failed to send processed audio data: {}", e);
-                                }
+                                control.overruns.fetch_add(1, Ordering::Relaxed);
                             }
                         }
                     },
@@ -93,54 +348,528 @@ impl AudioCapture {
                 )
             }
             other => {
-                panic!("Unsupported sample format: {:?}", other);
+                anyhow::bail!("unsupported sample format on '{}': {:?}", device_name, other);
             }
         }
-        .expect("Failed to build input stream");
+        .with_context(|| format!("failed to build input stream on '{}'", device_name))?;
 
         // 开始播放（录音实际上就是“启动回调”）
-        stream.play().expect("Failed to start stream");
+        stream
+            .play()
+            .with_context(|| format!("failed to start stream on '{}'", device_name))?;
 
-        Self {
+        let worker = {
+            let control = Arc::clone(&control);
+            thread::spawn(move || {
+                if realtime_priority {
+                    raise_to_high();
+                }
+                process_loop(consumer, &control, &audio_sender, &params)
+            })
+        };
+
+        Ok(Self {
             stream: Some(stream),
+            device_name,
+            worker: Some(worker),
+            control,
+            latency_us,
+        })
+    }
+
+    /// 当前采集设备的名称
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    /// cpal 报告的采集延迟（从声音被采集到回调收到数据），后端不提供时间戳时为 `None`
+    pub fn latency(&self) -> Option<Duration> {
+        match self.latency_us.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            us => Some(Duration::from_micros(us)),
         }
     }
 
-    #[allow(dead_code)]
-    /// 停止录音
-    pub fn stop(&mut self) {
+    /// 暂停采集，流保持打开，可通过 `resume` 恢复
+    pub fn pause(&self) {
+        if let Some(s) = &self.stream {
+            if let Err(e) = s.pause() {
+                error!("Failed to pause stream: {}", e);
+            }
+        }
+    }
+
+    /// 恢复采集。暂停前累计但尚未发送的音频会被丢弃，避免恢复后处理过期数据
+    pub fn resume(&self) {
+        self.control.clear.store(true, Ordering::SeqCst);
+        if let Some(s) = &self.stream {
+            if let Err(e) = s.play() {
+                error!("Failed to resume stream: {}", e);
+            }
+        }
+    }
+
+    /// 关闭输入流并通知处理线程处理完环形缓冲区中的剩余数据、发送尾段音频，不等待其结束。
+    /// 同时停止多路采集时先对每一路调用它，再逐一 `stop`，各路的尾段在同一时刻截止
+    pub fn request_stop(&mut self) {
         if let Some(s) = self.stream.take() {
             if let Err(e) = s.pause() {
                 error!("Failed to pause stream: {}", e);
             }
         }
+        self.control.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// 停止录音。缓冲区中尚未达到发送门槛的尾段音频若含有语音，会被一并发送出去，
+    /// 以免丢失会话最后几个词。
+    pub fn stop(&mut self) {
+        self.request_stop();
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                error!("Capture worker thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for AudioCapture {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// 处理线程：从环形缓冲区取出原始数据，混为单声道并重采样，攒够门槛后发送
+fn process_loop(mut consumer: Consumer, control: &WorkerControl, audio_sender: &Sender<Vec<f32>>, params: &ChunkParams) {
+    let mut buffer: Vec<f32> = Vec::new();
+    let mut counter = 0;
+    // 预热阶段尚需丢弃的采样点数
+    let mut warmup = params.warmup_samples;
+    // buffer 中最早的未发送音频到达的时间，buffer 为空时为 `None`
+    let mut buffered_since: Option<Instant> = None;
+    let mut tap = params.tap.as_ref();
+    loop {
+        // 先读停止标志再取数据，保证退出前取到了停止之前写入的全部音频
+        let stopping = control.stop.load(Ordering::SeqCst);
+        if control.clear.swap(false, Ordering::SeqCst) {
+            consumer.clear();
+            buffer.clear();
+            buffered_since = None;
+            counter = 0;
+            control.callbacks.store(0, Ordering::Relaxed);
+            // 恢复采集时流重新启动，同样可能有爆音
+            warmup = params.warmup_samples;
+        }
+        // 记录采集回调提升优先级的结果，只记录一次
+        let priority = control.priority.load(Ordering::Relaxed);
+        if priority == PRIORITY_RAISED {
+            info!("Capture callback thread is running at real-time priority");
+        } else if priority == PRIORITY_DENIED {
+            warn!(
+                "The OS denied real-time priority for the capture thread, continuing at normal priority \
+                 (on Linux, grant an rtprio limit or CAP_SYS_NICE)"
+            );
+        }
+        if priority >= PRIORITY_RAISED {
+            control.priority.store(PRIORITY_NONE, Ordering::Relaxed);
+        }
+        let overruns = control.overruns.swap(0, Ordering::Relaxed);
+        if overruns > 0 {
+            warn!("Capture ring buffer full, dropped {} callbacks of audio", overruns);
+        }
+
+        let raw = consumer.pop_all(params.input_channels);
+        if raw.is_empty() {
+            if stopping {
+                break;
+            }
+            // 设备暂时没有送来数据，已缓冲的音频等待过久时先发送出去
+            if flush_due(buffered_since, params) {
+                debug!("Flushing {} buffered samples after {:?} without a chunk", buffer.len(), params.max_flush_interval);
+                send_chunk(std::mem::take(&mut buffer), audio_sender, params);
+                buffered_since = None;
+                counter = 0;
+            }
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        counter += control.callbacks.swap(0, Ordering::Relaxed);
+
+        // 混为单声道并重采样到目标采样率
+        let mono = downmix_with(raw, params.input_channels, params.mix);
+        if let Some(output) = tap {
+            let tapped = if params.input_sample_rate != output.sample_rate {
+                audio_resample(&mono, params.input_sample_rate, output.sample_rate, params.resample_quality)
+            } else {
+                mono.clone()
+            };
+            // 接收端不再需要时停止输出，不影响转录
+            if output.sender.send(tapped).is_err() {
+                debug!("Audio tap receiver dropped, stopping the tap");
+                tap = None;
+            }
+        }
+        let mut processed = if params.input_sample_rate != params.target_sample_rate {
+            audio_resample(&mono, params.input_sample_rate, params.target_sample_rate, params.resample_quality)
+        } else {
+            mono
+        };
+        // 预热阶段的音频直接丢弃
+        if warmup > 0 {
+            let discard = warmup.min(processed.len());
+            processed.drain(..discard);
+            warmup -= discard;
+            if warmup == 0 {
+                debug!("Capture warm-up finished");
+            }
+        }
+        buffer.extend(processed);
+        if buffered_since.is_none() && !buffer.is_empty() {
+            buffered_since = Some(Instant::now());
+        }
+
+        // 检查是否达到发送门槛；等待过久时不论门槛整段发送
+        if flush_due(buffered_since, params) {
+            debug!("Flushing {} buffered samples below the send threshold", buffer.len());
+            send_chunk(std::mem::take(&mut buffer), audio_sender, params);
+            buffered_since = None;
+            counter = 0;
+        } else if counter > params.send_counter_threshold && buffer.len() >= params.send_frames_threshold {
+            // 取出要发送的数据；按帧对齐时，不足一帧的尾巴留在 buffer 里
+            let send_len = match params.frame_samples {
+                Some(frame) => buffer.len() / frame * frame,
+                None => buffer.len(),
+            };
+            send_chunk(buffer.drain(..send_len).collect(), audio_sender, params);
+            buffered_since = (!buffer.is_empty()).then(Instant::now);
+            counter = 0;
+        }
+    }
+
+    // 停止时，尚未达到发送门槛的尾段音频若含有语音则一并发送，以免丢失会话最后几个词
+    if buffer.is_empty() {
+        return;
+    }
+    if rms(&buffer) < FLUSH_RMS_THRESHOLD {
+        debug!("Discarding {} trailing silent samples on stop", buffer.len());
+        return;
+    }
+    info!("Flushing {} trailing samples on stop", buffer.len());
+    if let Some(config) = &params.peak_normalize {
+        peak_normalize(&mut buffer, config);
+    }
+    if let Err(e) = audio_sender.send(buffer) {
+        error!("Failed to flush trailing audio: {}", e);
+    }
+}
+
+/// 缓冲中最早的音频是否已等待超过 `max_flush_interval`
+fn flush_due(buffered_since: Option<Instant>, params: &ChunkParams) -> bool {
+    match (buffered_since, params.max_flush_interval) {
+        (Some(since), Some(interval)) => since.elapsed() >= interval,
+        _ => false,
+    }
+}
+
+/// 按需做峰值归一化后发送一个块
+fn send_chunk(mut chunk: Vec<f32>, audio_sender: &Sender<Vec<f32>>, params: &ChunkParams) {
+    if let Some(config) = &params.peak_normalize {
+        peak_normalize(&mut chunk, config);
+    }
+    if let Err(e) = audio_sender.send(chunk) {
+        error!("Failed to send processed audio data: {}", e);
+    }
+}
+
+/// 把当前线程（采集回调线程）提升为实时优先级：Linux 上使用 SCHED_FIFO 调度，
+/// 其他平台使用该平台的最高优先级。返回是否成功
+#[cfg(feature = "realtime")]
+fn raise_to_realtime() -> bool {
+    use thread_priority::{ThreadPriority, ThreadPriorityValue};
+    let Ok(value) = ThreadPriorityValue::try_from(REALTIME_PRIORITY) else {
+        return false;
+    };
+    #[cfg(target_os = "linux")]
+    let result = {
+        use thread_priority::unix::{
+            set_thread_priority_and_policy, thread_native_id, RealtimeThreadSchedulePolicy, ThreadSchedulePolicy,
+        };
+        set_thread_priority_and_policy(
+            thread_native_id(),
+            ThreadPriority::Crossplatform(value),
+            ThreadSchedulePolicy::Realtime(RealtimeThreadSchedulePolicy::Fifo),
+        )
+    };
+    #[cfg(not(target_os = "linux"))]
+    let result = thread_priority::set_current_thread_priority(ThreadPriority::Crossplatform(value));
+    result.is_ok()
+}
+
+#[cfg(not(feature = "realtime"))]
+fn raise_to_realtime() -> bool {
+    false
+}
+
+/// 把当前线程（处理线程）提升为普通调度下的最高优先级，失败时记录警告
+#[cfg(feature = "realtime")]
+fn raise_to_high() {
+    if let Err(e) = thread_priority::set_current_thread_priority(thread_priority::ThreadPriority::Max) {
+        warn!("Could not raise the capture processing thread priority: {:?}", e);
+    }
+}
+
+#[cfg(not(feature = "realtime"))]
+fn raise_to_high() {}
+
+/// 把请求的缓冲区大小限制在设备支持的范围内，超出时记录警告
+fn fit_buffer_size(frames: u32, supported: &SupportedBufferSize) -> u32 {
+    match *supported {
+        SupportedBufferSize::Range { min, max } => {
+            let fitted = frames.clamp(min, max);
+            if fitted != frames {
+                warn!(
+                    "Requested buffer size {} frames is outside the supported range {}..={}, using {}",
+                    frames, min, max, fitted
+                );
+            }
+            fitted
+        }
+        SupportedBufferSize::Unknown => {
+            warn!("Device does not report a supported buffer size range, requesting {} frames as is", frames);
+            frames
+        }
     }
 }
 
-/// 一个简单的结构，用来在回调中累计数据
-struct DataAccumulator {
-    buffer: Vec<f32>,
-    counter: usize,
+/// 检查 `samplerate` 能否在两种采样率之间转换
+fn validate_target_rate(input_rate: u32, target_rate: u32) -> anyhow::Result<()> {
+    if target_rate == 0 {
+        anyhow::bail!("target sample rate must be greater than 0");
+    }
+    let ratio = target_rate as f64 / input_rate as f64;
+    if !(1.0 / MAX_RESAMPLE_RATIO..=MAX_RESAMPLE_RATIO).contains(&ratio) {
+        anyhow::bail!(
+            "cannot resample {} Hz to {} Hz: ratio exceeds 1:{}",
+            input_rate,
+            target_rate,
+            MAX_RESAMPLE_RATIO
+        );
+    }
+    Ok(())
 }
 
-/// 将立体声数据（f32）混合为单声道
-fn stereo_to_mono(stereo_data: &[f32]) -> Vec<f32> {
-    let mut mono = Vec::with_capacity(stereo_data.len() / 2);
-    for chunk in stereo_data.chunks(2) {
-        let m = (chunk[0] + chunk[1]) * 0.5;
-        mono.push(m);
+/// 计算音频的均方根幅度
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// 读取 WAV 文件，并转换为 16k 单声道 f32 数据
+pub fn load_wav(path: &str) -> anyhow::Result<Vec<f32>> {
+    let reader = hound::WavReader::open(path)
+        .map_err(|e| anyhow::anyhow!("failed to open WAV file {}: {}", path, e))?;
+    decode_wav(reader)
+}
+
+/// 与 `load_wav` 相同，但从内存中的 WAV 数据（如 HTTP 上传的文件）读取
+#[allow(dead_code)]
+pub fn load_wav_bytes(bytes: &[u8]) -> anyhow::Result<Vec<f32>> {
+    let reader = hound::WavReader::new(std::io::Cursor::new(bytes))
+        .map_err(|e| anyhow::anyhow!("invalid WAV data: {}", e))?;
+    decode_wav(reader)
+}
+
+fn decode_wav<R: std::io::Read>(mut reader: hound::WavReader<R>) -> anyhow::Result<Vec<f32>> {
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    let mono = downmix(samples, spec.channels as usize);
+
+    Ok(if spec.sample_rate != 16_000 {
+        audio_resample(&mono, spec.sample_rate, 16_000, ConverterType::SincBestQuality)
+    } else {
+        mono
+    })
+}
+
+/// 交错排列的多声道数据取平均混为单声道
+pub fn downmix(samples: Vec<f32>, channels: usize) -> Vec<f32> {
+    downmix_with(samples, channels, Mix::Average)
+}
+
+/// 平均后的能量低于各声道平均能量的该比例时视为相位抵消。
+/// 两路不相关的信号平均后约为 0.5，完全同相为 1，完全反相为 0
+const PHASE_CANCEL_RATIO: f32 = 0.2;
+/// 各声道平均能量低于该值（约 -60 dBFS）时是静音，不判断相位
+const PHASE_CHECK_MIN_POWER: f32 = 1e-6;
+
+/// 按 `mix` 把交错排列的多声道数据混为单声道。相位检测按整段数据进行
+pub fn downmix_with(samples: Vec<f32>, channels: usize, mix: Mix) -> Vec<f32> {
+    if channels <= 1 {
+        return samples;
+    }
+    let average: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    if mix == Mix::Average || average.is_empty() {
+        return average;
+    }
+
+    let mut channel_power = vec![0.0f32; channels];
+    for frame in samples.chunks_exact(channels) {
+        for (power, sample) in channel_power.iter_mut().zip(frame) {
+            *power += sample * sample;
+        }
+    }
+    let mean_power = channel_power.iter().sum::<f32>() / channels as f32;
+    let mixed_power = average.iter().map(|s| s * s).sum::<f32>();
+    let frames = average.len() as f32;
+    if mean_power / frames < PHASE_CHECK_MIN_POWER || mixed_power >= PHASE_CANCEL_RATIO * mean_power {
+        return average;
+    }
+    let loudest = channel_power
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map_or(0, |(index, _)| index);
+    samples.chunks(channels).map(|frame| frame[loudest.min(frame.len() - 1)]).collect()
+}
+
+/// 把音频整体缩放，使峰值达到 `target_dbfs`；增益不超过 `max_gain_db`，全静音时不处理。
+/// 增益可正可负，过响的音源同样会被压低到目标峰值
+pub fn peak_normalize(samples: &mut [f32], config: &PeakNormalizeConfig) {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak <= 0.0 {
+        return;
+    }
+    let target = 10f32.powf(config.target_dbfs / 20.0);
+    let gain = (target / peak).min(10f32.powf(config.max_gain_db / 20.0));
+    for sample in samples.iter_mut() {
+        *sample *= gain;
     }
-    mono
 }
 
 /// 用 `samplerate` crate 进行重采样
-fn audio_resample(data: &[f32], sr_in: u32, sr_out: u32) -> Vec<f32> {
+pub fn audio_resample(data: &[f32], sr_in: u32, sr_out: u32, quality: ConverterType) -> Vec<f32> {
     convert(
         sr_in,
         sr_out,
         1, // 单声道
-        ConverterType::SincBestQuality,
+        quality,
         data,
     )
     .expect("failed to resample")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    /// 生成 `channels` 个声道交错排列的正弦波，各声道相同，时长 `secs` 秒
+    fn sine(frequency: f32, rate: u32, channels: usize, secs: f32) -> Vec<f32> {
+        let frames = (rate as f32 * secs) as usize;
+        (0..frames)
+            .flat_map(|i| std::iter::repeat_n(0.5 * (2.0 * PI * frequency * i as f32 / rate as f32).sin(), channels))
+            .collect()
+    }
+
+    /// 用 Goertzel 算法求 `samples` 在 `frequency` 处的能量
+    fn goertzel(samples: &[f32], frequency: f32, rate: u32) -> f32 {
+        let coeff = 2.0 * (2.0 * PI * frequency / rate as f32).cos();
+        let (mut s1, mut s2) = (0.0f32, 0.0f32);
+        for &sample in samples {
+            let s0 = sample + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s0;
+        }
+        s1 * s1 + s2 * s2 - coeff * s1 * s2
+    }
+
+    #[test]
+    fn downmix_identical_channels_keeps_the_signal() {
+        let stereo = sine(440.0, 48_000, 2, 0.1);
+        let mono = downmix(stereo.clone(), 2);
+        assert_eq!(mono.len(), stereo.len() / 2);
+        for (mixed, frame) in mono.iter().zip(stereo.chunks(2)) {
+            assert!((mixed - frame[0]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn loudness_preserving_downmix_survives_phase_cancellation() {
+        // 右声道与左声道反相：平均后为静音，应改取其中一个声道
+        let mut stereo = sine(440.0, 48_000, 2, 0.1);
+        for frame in stereo.chunks_mut(2) {
+            frame[1] = -frame[0];
+        }
+        let average = downmix_with(stereo.clone(), 2, Mix::Average);
+        assert!(average.iter().all(|s| s.abs() < 1e-6));
+        let preserved = downmix_with(stereo, 2, Mix::LoudnessPreserving);
+        assert!(goertzel(&preserved, 440.0, 48_000) > 1.0);
+    }
+
+    #[test]
+    fn resampling_48k_to_16k_preserves_length_and_tone() {
+        let mono = downmix(sine(440.0, 48_000, 2, 1.0), 2);
+        assert_eq!(mono.len(), 48_000);
+        let resampled = audio_resample(&mono, 48_000, 16_000, ConverterType::SincBestQuality);
+        assert!(
+            resampled.len().abs_diff(16_000) <= 160,
+            "expected about 16000 samples, got {}",
+            resampled.len()
+        );
+        let tone = goertzel(&resampled, 440.0, 16_000);
+        for other in [220.0, 330.0, 550.0, 880.0, 1_000.0] {
+            let power = goertzel(&resampled, other, 16_000);
+            assert!(tone > 100.0 * power, "{} Hz ({}) rivals the 440 Hz tone ({})", other, power, tone);
+        }
+    }
+
+    #[test]
+    fn tap_receives_the_native_rate_alongside_the_16k_feed() {
+        let (audio_sender, audio_receiver) = crossbeam_channel::unbounded();
+        let (tap_sender, tap_receiver) = crossbeam_channel::unbounded();
+        let params = ChunkParams {
+            input_channels: 2,
+            input_sample_rate: 48_000,
+            target_sample_rate: 16_000,
+            resample_quality: ConverterType::SincFastest,
+            send_frames_threshold: usize::MAX,
+            send_counter_threshold: usize::MAX,
+            frame_samples: None,
+            warmup_samples: 0,
+            peak_normalize: None,
+            max_flush_interval: None,
+            mix: Mix::Average,
+            tap: Some(AudioTap {
+                sample_rate: 48_000,
+                sender: tap_sender,
+            }),
+        };
+        let stereo = sine(440.0, 48_000, 2, 0.5);
+        let (mut producer, consumer) = ring_buffer(stereo.len());
+        assert!(producer.push_slice(&stereo));
+        let control = WorkerControl::default();
+        control.stop.store(true, Ordering::SeqCst);
+        process_loop(consumer, &control, &audio_sender, &params);
+
+        // 附加输出与设备同为 48k，不重采样；转录的一路停止时作为尾段发出，约 8000 个采样点
+        let tapped: Vec<f32> = tap_receiver.try_iter().flatten().collect();
+        assert_eq!(tapped, downmix(stereo, 2));
+        let feed: Vec<f32> = audio_receiver.try_iter().flatten().collect();
+        assert!(feed.len().abs_diff(8_000) <= 80, "expected about 8000 samples, got {}", feed.len());
+    }
+}
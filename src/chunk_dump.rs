@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::Context;
+use log::{info, warn, error};
+
+/// 未指定时最多写出的文件数（默认块长下约 9 分钟的音频）
+pub const DEFAULT_MAX_FILES: usize = 500;
+
+/// 调试用：把每个送入转录的音频块原样写成一个 WAV 文件（16k 单声道 f32），
+/// 文件名为该块在本次会话音频时间轴上的起始时间，如 `chunk_000012.340.wav`。
+/// 某条字幕识别有误时，可直接打开对应的音频片段。
+///
+/// 每个块一个文件，长时间运行会产生大量文件，因此写满 `max_files` 个后不再写入。
+pub struct ChunkDumper {
+    dir: PathBuf,
+    max_files: usize,
+    written: usize,
+    /// 已经过的会话音频时长，即下一个块的起始时间
+    position: Duration,
+}
+
+impl ChunkDumper {
+    /// 在 `dir` 中写入音频块，目录不存在时自动创建
+    pub fn new(dir: &str, max_files: usize) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("failed to create chunk dump directory {}", dir))?;
+        info!("Dumping up to {} transcription chunks to {}", max_files, dir);
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            max_files,
+            written: 0,
+            position: Duration::ZERO,
+        })
+    }
+
+    /// 写出一个音频块。达到文件数上限后只推进时间轴
+    pub fn dump(&mut self, samples: &[f32]) {
+        let start = self.position;
+        self.position += Duration::from_secs_f64(samples.len() as f64 / 16_000.0);
+        if self.written >= self.max_files {
+            return;
+        }
+        let path = self.dir.join(format!("chunk_{:010.3}.wav", start.as_secs_f64()));
+        if let Err(e) = write_wav(&path, samples) {
+            error!("Failed to write chunk {}: {:#}", path.display(), e);
+            return;
+        }
+        self.written += 1;
+        if self.written == self.max_files {
+            warn!("Reached the chunk dump limit of {} files, no more chunks will be written", self.max_files);
+        }
+    }
+}
+
+fn write_wav(path: &std::path::Path, samples: &[f32]) -> anyhow::Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16_000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
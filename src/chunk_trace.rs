@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use log::{log_enabled, trace, Level};
+use serde::Serialize;
+
+use crate::sink::CaptionEvent;
+use crate::transcribe::Transcription;
+
+/// 逐块决策记录使用的日志 target，由 `--trace-chunks` 指定的文件接收
+pub const TRACE_TARGET: &str = "chunk_trace";
+
+/// 下一个音频块的编号，转录线程与 `process_samples` 共用
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// 一个音频块从转录到输出的完整记录，以一行 JSON 写入 trace 级别的日志，
+/// 用于排查某句话为什么得到了某条字幕
+#[derive(Debug, Serialize)]
+pub struct ChunkTrace {
+    id: u64,
+    timestamp: DateTime<Local>,
+    /// 送入 Whisper 的采样点数（16k 单声道）
+    samples: usize,
+    rms: f32,
+    /// 是否判为语音；空白、"[BLANK_AUDIO]" 等计为静音，用于分段
+    speech: bool,
+    text: String,
+    confidence: f32,
+    language: Option<String>,
+    /// 翻译决策，见 `Pipeline::translate_decision`；没有生成字幕时为 `None`
+    translate: Option<&'static str>,
+    outputs: Vec<TracedOutput>,
+    transcribe_ms: f64,
+    /// 转录结果在队列中等待主循环处理的时长
+    queue_ms: f64,
+    /// 后处理、翻译直到生成字幕事件的时长
+    translate_ms: f64,
+    #[serde(skip)]
+    transcribed_at: Instant,
+}
+
+/// 该块最终生成的一个字幕事件
+#[derive(Debug, Serialize)]
+struct TracedOutput {
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    translation: Option<String>,
+}
+
+impl ChunkTrace {
+    /// 记录转录阶段的结果。未启用该 target 的 trace 日志时返回 `None`，不做任何额外工作
+    pub fn new(samples: usize, rms: f32, speech: bool, transcription: &Transcription, elapsed: Duration) -> Option<Self> {
+        if !log_enabled!(target: TRACE_TARGET, Level::Trace) {
+            return None;
+        }
+        Some(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            timestamp: Local::now(),
+            samples,
+            rms,
+            speech,
+            text: transcription.text.trim().to_string(),
+            confidence: transcription.confidence(),
+            language: transcription.language.clone(),
+            translate: None,
+            outputs: Vec::new(),
+            transcribe_ms: elapsed.as_secs_f64() * 1000.0,
+            queue_ms: 0.0,
+            translate_ms: 0.0,
+            transcribed_at: Instant::now(),
+        })
+    }
+
+    /// 补上翻译阶段的决策、生成的事件和耗时（`elapsed`），写出这一条记录
+    pub fn finish(mut self, decision: Option<&'static str>, events: &[CaptionEvent], elapsed: Duration) {
+        self.translate = decision;
+        self.outputs = events
+            .iter()
+            .map(|event| match event {
+                CaptionEvent::Final(caption) => TracedOutput {
+                    kind: "final",
+                    source: Some(caption.source.clone()),
+                    translation: caption.translation.clone(),
+                },
+                CaptionEvent::Partial(caption) => TracedOutput {
+                    kind: "partial",
+                    source: Some(caption.source.clone()),
+                    translation: None,
+                },
+                CaptionEvent::Revision(caption) => TracedOutput {
+                    kind: "revision",
+                    source: Some(caption.source.clone()),
+                    translation: caption.translation.clone(),
+                },
+                CaptionEvent::ParagraphBreak => TracedOutput {
+                    kind: "paragraph_break",
+                    source: None,
+                    translation: None,
+                },
+            })
+            .collect();
+        self.queue_ms = self.transcribed_at.elapsed().saturating_sub(elapsed).as_secs_f64() * 1000.0;
+        self.translate_ms = elapsed.as_secs_f64() * 1000.0;
+        match serde_json::to_string(&self) {
+            Ok(line) => trace!(target: TRACE_TARGET, "{}", line),
+            Err(e) => trace!(target: TRACE_TARGET, "failed to serialize chunk trace: {}", e),
+        }
+    }
+}
@@ -0,0 +1,735 @@
+use anyhow::{bail, Context};
+use samplerate::ConverterType;
+
+use crate::adaptive::AdaptiveModelConfig;
+use crate::capture::{Mix, PeakNormalizeConfig, DEFAULT_ECHO_STRENGTH};
+use crate::sink::DisplayContent;
+use crate::stdin_source::StdinFormat;
+use crate::throttle::ThrottleConfig;
+
+/// `--serve` 默认同时处理的请求数，即共用模型的转录状态个数
+const DEFAULT_SERVE_WORKERS: usize = 2;
+/// `--fifo` 默认的行格式：原文和译文以制表符分隔
+const DEFAULT_FIFO_FORMAT: &str = "{source}\t{translation}";
+
+/// 程序的运行模式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// 默认模式：实时采集、转录并翻译
+    Run,
+    /// 转录 WAV 文件，并与参考文本对比计算词错误率（WER）
+    BenchmarkFile { wav: String, reference: String },
+    /// 列出 whisper.cpp 支持的转录语言，并说明所加载的模型是否只支持英文
+    ListLanguages,
+    /// 在 `addr` 上提供 HTTP 转录服务，`workers` 为同时处理的请求数
+    Serve { addr: String, workers: usize },
+}
+
+/// 解析后的命令行参数。
+///
+/// 部分选项也可以通过环境变量给出（便于在容器中部署），优先级从高到低为：
+/// 命令行参数 > 环境变量 > 配置文件（`--whisper-config`）> 内置默认值。
+/// 环境变量在 `from_env` 中填入未由命令行给出的字段，配置文件和默认值由使用方在字段为 `None` 时采用：
+///
+/// | 参数 | 环境变量 |
+/// | --- | --- |
+/// | `--whisper-model` | `LAT_WHISPER_MODEL` |
+/// | `--translate-model` | `LAT_TRANSLATE_MODEL` |
+/// | `--device` | `LAT_DEVICE` |
+/// | `--mix-devices` | `LAT_MIX_DEVICES`（命令行给出 `--device` 时不读取） |
+/// | `--language` | `LAT_LANGUAGE` |
+/// | `--whisper-config` | `LAT_WHISPER_CONFIG` |
+/// | `--transcript` | `LAT_TRANSCRIPT` |
+#[derive(Debug, Clone)]
+pub struct Cli {
+    pub command: Command,
+    /// `--resample-quality linear|fastest|medium|best`
+    pub resample_quality: Option<ConverterType>,
+    /// `--downmix average|loudness`：多声道混为单声道的方式
+    pub mix: Option<Mix>,
+    /// `--buffer-frames <n>`：向驱动请求的采集缓冲区大小（帧）
+    pub buffer_frames: Option<u32>,
+    /// `--warmup-ms <ms>`：开始采集后丢弃的音频时长
+    pub warmup_ms: Option<u32>,
+    /// `--max-flush-interval <secs>`：未发送的音频最多等待多久就发送，0 表示只按门槛发送
+    pub max_flush_interval: Option<f64>,
+    /// `--peak-normalize`：把每个音频块按峰值归一化。`--peak-target <dBFS>` 设置目标峰值，
+    /// `--max-gain <dB>` 设置最大增益；两者均隐含 `--peak-normalize`
+    pub peak_normalize: Option<PeakNormalizeConfig>,
+    /// `--echo-suppress`：混音时抑制麦克风中串入的扬声器声音。`--echo-strength <0-1>` 设置抑制强度，
+    /// 隐含 `--echo-suppress`
+    pub echo_suppression: Option<f32>,
+    /// `--fused-worker`：在同一线程中完成转录、翻译和输出，降低延迟
+    pub fused_worker: bool,
+    /// `--realtime-priority`：提升采集线程的调度优先级（需要 `realtime` feature）
+    pub realtime_priority: bool,
+    /// `--transcript <path>`：把字幕追加到纯文本文稿
+    pub transcript: Option<String>,
+    /// `--json`：以 JSON Lines 输出到标准输出（替代终端显示）
+    pub json: bool,
+    /// `--websocket <addr>`：通过 WebSocket 广播字幕
+    pub websocket: Option<String>,
+    /// `--osc <addr>`：通过 OSC 发送字幕
+    pub osc: Option<String>,
+    /// `--fifo <path>`：把最终字幕逐行写入命名管道（仅 Unix）
+    pub fifo: Option<String>,
+    /// `--fifo-format <format>`：写入命名管道的行格式，可用 `{time}`、`{source}`、`{translation}`、`{speaker}`
+    pub fifo_format: String,
+    /// `--final-only <sink>`：该输出端（如 json、websocket、display）只接收最终字幕，不接收临时字幕，可重复
+    pub final_only: Vec<String>,
+    /// `--whisper-config <path.json>`：从 JSON 文件读取 Whisper 转录参数
+    pub whisper_config: Option<String>,
+    /// `--whisper-model <path>`：Whisper 模型文件，优先于 `--auto-model`
+    pub whisper_model: Option<String>,
+    /// `--translate-model <dir>`：翻译模型目录，内含 model.safetensors 与两个分词器文件
+    pub translate_model: Option<String>,
+    /// `--zh-en-model <dir>`：中译英模型目录。给出后按每块检测到的语言分派：英文译为中文，中文译为英文
+    pub zh_en_model: Option<String>,
+    /// `--glossary <path.json>`：术语表，翻译时保护指定术语并替换译文中的词
+    pub glossary: Option<String>,
+    /// `--protect-code`：翻译时保留代码、URL 一类的片段不翻译
+    pub protect_code: bool,
+    /// `--localize <locale>[,option...]`：按目标区域统一译文中日期、时间和数字的写法，如 "zh-CN"、"zh-TW,thousands"
+    pub localize: Option<String>,
+    /// `--protect-pattern <regex>`：自定义识别受保护片段的正则，可重复，替换默认规则并隐含 `--protect-code`
+    pub protect_patterns: Vec<String>,
+    /// `--tokenizer-repo <owner/name[@revision]>`：从 Hugging Face 仓库下载英译中的分词器，代替模型目录中的文件
+    pub tokenizer_repo: Option<String>,
+    /// `--zh-en-tokenizer-repo <owner/name[@revision]>`：同上，用于 `--zh-en-model` 的中译英分词器
+    pub zh_en_tokenizer_repo: Option<String>,
+    /// `--top-p <p>`：翻译改用核采样，译文更自然但不那么直译；默认贪心解码
+    pub top_p: Option<f64>,
+    /// `--min-translate-chars <n>`：字符数少于该值的文本不送入翻译模型，原样输出
+    pub min_translate_chars: Option<usize>,
+    /// `--resume`：续写 `--transcript` 文稿中最后一个会话，而不是开始新会话
+    pub resume: bool,
+    /// `--device <name>`：按名称选择采集设备，默认采集默认输出设备
+    pub device: Option<String>,
+    /// `--mix-devices <设备名=增益;...>`：同时采集多个设备并混成一路，优先于 `--device`
+    pub mix_devices: Option<String>,
+    /// `--language <code>`：转录语言，覆盖配置文件中的 `language`
+    pub language: Option<String>,
+    /// `--auto-model`：按内存预算自动选择 Whisper 模型（默认使用 base）
+    pub auto_model: bool,
+    /// `--memory-budget <MB>`：自动选择模型时的内存预算，隐含 `--auto-model`
+    pub memory_budget: Option<u64>,
+    /// `--adaptive-model`：转录跟不上时自动换用更小的模型。`--max-queue-latency <secs>`
+    /// 设置判定跟不上的队列延迟，`--adaptive-upgrade` 允许负载下降后换回；两者均隐含 `--adaptive-model`
+    pub adaptive_model: Option<AdaptiveModelConfig>,
+    /// `--edge-fade-ms <ms>`：转录前对音频块首尾做淡入淡出
+    pub edge_fade_ms: Option<u32>,
+    /// `--chunk-timeout <secs>`：单个音频块的转录时限，超时的块被跳过
+    pub chunk_timeout: Option<f32>,
+    /// `--word-confidence`：输出逐词的时间和概率（JSON 输出中的 `words`）
+    pub word_confidence: bool,
+    /// `--max-segment-len <chars>`：Whisper 单个片段的最大字符数
+    pub max_segment_len: Option<i32>,
+    /// `--split-on-word`：切分片段时只在词边界处切开
+    pub split_on_word: bool,
+    /// `--transcribe-only`：本次运行不加载翻译模型，只输出转录原文。
+    /// 未编译 `translate` feature 时本就只转录，该选项不起作用
+    pub transcribe_only: bool,
+    /// `--no-normalize`：翻译前不做英文数字/货币规范化
+    pub no_normalize: bool,
+    /// `--min-translate-confidence <0-1>`：转录置信度低于该值的字幕只显示原文，不翻译
+    pub min_translate_confidence: Option<f32>,
+    /// `--translate-every <n>`：每 n 条字幕只翻译一条，其余只显示原文。`--translate-interval <secs>`
+    /// 设置两次翻译的最短间隔，`--throttle-rtf <rtf>` 只在转录实时率高于该值时限流；三者均启用限流
+    pub translation_throttle: Option<ThrottleConfig>,
+    /// `--unclear-below <0-1>`：置信度低于该值的片段替换为占位符
+    pub unclear_below: Option<f32>,
+    /// `--unclear-placeholder <text>`：低置信度片段的占位文本，默认 "[unclear]"
+    pub unclear_placeholder: Option<String>,
+    /// `--keep-unclear-guess`：把被替换的原文附在 JSON 字幕的 `unclear` 字段中
+    pub keep_unclear_guess: bool,
+    /// `--sentence-hold <secs>`：翻译前先缓冲，凑成完整句子再翻译，最多等待该秒数
+    pub sentence_hold: Option<f64>,
+    /// `--paragraph-silence <secs>`：静音超过该秒数时在文稿中分段
+    pub paragraph_silence: Option<f64>,
+    /// `--sliding-window <secs>`：改用该长度的滑动窗口流式转录
+    pub sliding_window: Option<f64>,
+    /// `--hop <secs>`：滑动窗口每次前进的长度
+    pub hop: Option<f64>,
+    /// `--stability <k>`：滑动窗口中一个词连续 k 次转录不变才定稿
+    pub stability: Option<usize>,
+    /// `--stable-timeout <secs>`：滑动窗口中一个词不变超过该秒数即定稿
+    pub stable_timeout: Option<f64>,
+    /// `--revise-captions`：滑动窗口改正已输出字幕的原文时，重新翻译并输出修订
+    pub revise_captions: bool,
+    /// `--dump-chunks <dir>`：调试用，把每个送入转录的音频块写成 WAV 文件
+    pub dump_chunks: Option<String>,
+    /// `--dump-chunks-max <n>`：最多写出的音频块文件数
+    pub dump_chunks_max: Option<usize>,
+    /// `--trace-chunks <path>`：调试用，把每个音频块从转录到输出的决策记录写成 JSON Lines
+    pub trace_chunks: Option<String>,
+    /// `--recovery-threshold <n>`：连续 n 次异常转录后重建 Whisper 状态，0 表示关闭
+    pub recovery_threshold: Option<usize>,
+    /// `--pinyin`：为译文标注拼音（需要 `pinyin` feature）
+    pub pinyin: bool,
+    /// `--confidence-colors`：终端中按置信度渐变为原文着色
+    pub confidence_colors: bool,
+    /// `--show <both|source|target>`：终端中只显示原文或只显示译文。`source` 隐含 `--transcribe-only`
+    pub display_content: DisplayContent,
+    /// `--clear-after <secs>`：终端中超过该时长没有新字幕时清屏
+    pub clear_after: Option<f64>,
+    /// `--tui`：改用全屏终端界面显示字幕（需要 `tui` feature）
+    pub tui: bool,
+    /// `--mask-words <file>`：屏蔽词表，每行一个词，原文和译文中命中的词替换为 `***`
+    pub mask_words: Option<String>,
+    /// `--mask-replacement <text>`：屏蔽词的替换文本
+    pub mask_replacement: Option<String>,
+    /// `--marker "[MUSIC]=🎵"`（可重复）：终端中特殊标记的替换文本，值为空表示隐藏
+    pub markers: Vec<(String, Option<String>)>,
+    /// `--stdin-format <rate>:<channels>:<f32|i16>`：改从标准输入读取原始 PCM
+    pub stdin_format: Option<StdinFormat>,
+}
+
+impl Cli {
+    /// 从进程参数中解析，未给出的选项再从对应的环境变量读取
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut cli = Self::parse(std::env::args().skip(1))?;
+        cli.apply_env_fallbacks();
+        Ok(cli)
+    }
+
+    /// 用环境变量补全命令行中未给出的选项，命令行参数始终优先
+    fn apply_env_fallbacks(&mut self) {
+        // 命令行明确指定了单个设备时，不让环境变量里的混音配置把它盖掉
+        if self.mix_devices.is_none() && self.device.is_none() {
+            self.mix_devices = std::env::var("LAT_MIX_DEVICES").ok().filter(|value| !value.trim().is_empty());
+        }
+        let fallbacks = [
+            (&mut self.whisper_model, "LAT_WHISPER_MODEL"),
+            (&mut self.translate_model, "LAT_TRANSLATE_MODEL"),
+            (&mut self.device, "LAT_DEVICE"),
+            (&mut self.language, "LAT_LANGUAGE"),
+            (&mut self.whisper_config, "LAT_WHISPER_CONFIG"),
+            (&mut self.transcript, "LAT_TRANSCRIPT"),
+        ];
+        for (field, name) in fallbacks {
+            if field.is_none() {
+                *field = std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+            }
+        }
+    }
+
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> anyhow::Result<Self> {
+        let mut command = Command::Run;
+        let mut serve_workers = None;
+        let mut resample_quality = None;
+        let mut mix = None;
+        let mut buffer_frames = None;
+        let mut warmup_ms = None;
+        let mut max_flush_interval = None;
+        let mut peak_normalize = None;
+        let mut echo_suppression = None;
+        let mut fused_worker = false;
+        let mut realtime_priority = false;
+        let mut transcript = None;
+        let mut json = false;
+        let mut websocket = None;
+        let mut osc = None;
+        let mut fifo = None;
+        let mut fifo_format = DEFAULT_FIFO_FORMAT.to_string();
+        let mut final_only = Vec::new();
+        let mut whisper_config = None;
+        let mut whisper_model = None;
+        let mut translate_model = None;
+        let mut zh_en_model = None;
+        let mut resume = false;
+        let mut glossary = None;
+        let mut protect_code = false;
+        let mut localize = None;
+        let mut protect_patterns = Vec::new();
+        let mut top_p = None;
+        let mut min_translate_chars = None;
+        let mut tokenizer_repo = None;
+        let mut zh_en_tokenizer_repo = None;
+        let mut device = None;
+        let mut mix_devices = None;
+        let mut language = None;
+        let mut auto_model = false;
+        let mut memory_budget = None;
+        let mut edge_fade_ms = None;
+        let mut chunk_timeout = None;
+        let mut word_confidence = false;
+        let mut max_segment_len = None;
+        let mut split_on_word = false;
+        let mut clear_after = None;
+        let mut adaptive_model: Option<AdaptiveModelConfig> = None;
+        let mut transcribe_only = false;
+        let mut no_normalize = false;
+        let mut min_translate_confidence = None;
+        let mut translation_throttle: Option<ThrottleConfig> = None;
+        let mut throttle_every = None;
+        let mut unclear_below = None;
+        let mut unclear_placeholder = None;
+        let mut keep_unclear_guess = false;
+        let mut sentence_hold = None;
+        let mut paragraph_silence = None;
+        let mut sliding_window = None;
+        let mut revise_captions = false;
+        let mut hop = None;
+        let mut stability = None;
+        let mut stable_timeout = None;
+        let mut recovery_threshold = None;
+        let mut dump_chunks = None;
+        let mut dump_chunks_max = None;
+        let mut trace_chunks = None;
+        let mut pinyin = false;
+        let mut confidence_colors = false;
+        let mut tui = false;
+        let mut display_content = DisplayContent::default();
+        let mut mask_words = None;
+        let mut mask_replacement = None;
+        let mut markers = Vec::new();
+        let mut stdin_format = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--benchmark-file" => {
+                    let wav = args.next().context("--benchmark-file requires <wav> <reference.txt>")?;
+                    let reference = args.next().context("--benchmark-file requires <wav> <reference.txt>")?;
+                    command = Command::BenchmarkFile { wav, reference };
+                }
+                "--list-languages" => command = Command::ListLanguages,
+                "--serve" => {
+                    let addr = args.next().context("--serve requires an address")?;
+                    command = Command::Serve {
+                        addr,
+                        workers: DEFAULT_SERVE_WORKERS,
+                    };
+                }
+                "--serve-workers" => {
+                    let value = args.next().context("--serve-workers requires a value")?;
+                    let workers: usize = value
+                        .parse()
+                        .with_context(|| format!("invalid --serve-workers '{}'", value))?;
+                    if workers == 0 {
+                        bail!("--serve-workers must be at least 1");
+                    }
+                    serve_workers = Some(workers);
+                }
+                "--resample-quality" => {
+                    let value = args.next().context("--resample-quality requires a value")?;
+                    resample_quality = Some(parse_resample_quality(&value)?);
+                }
+                "--downmix" => {
+                    let value = args.next().context("--downmix requires average|loudness")?;
+                    mix = Some(match value.as_str() {
+                        "average" => Mix::Average,
+                        "loudness" => Mix::LoudnessPreserving,
+                        other => bail!("invalid --downmix '{}', expected average|loudness", other),
+                    });
+                }
+                "--buffer-frames" => {
+                    let value = args.next().context("--buffer-frames requires a value")?;
+                    let frames: u32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --buffer-frames '{}'", value))?;
+                    if frames == 0 {
+                        bail!("--buffer-frames must be at least 1");
+                    }
+                    buffer_frames = Some(frames);
+                }
+                "--max-flush-interval" => {
+                    let value = args.next().context("--max-flush-interval requires a value in seconds")?;
+                    let secs: f64 = value
+                        .parse()
+                        .with_context(|| format!("invalid --max-flush-interval '{}'", value))?;
+                    if !(secs >= 0.0 && secs.is_finite()) {
+                        bail!("--max-flush-interval must be a non-negative number of seconds");
+                    }
+                    max_flush_interval = Some(secs);
+                }
+                "--warmup-ms" => {
+                    let value = args.next().context("--warmup-ms requires a value")?;
+                    warmup_ms = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid --warmup-ms '{}'", value))?,
+                    );
+                }
+                "--realtime-priority" => realtime_priority = true,
+                "--fused-worker" => fused_worker = true,
+                "--peak-normalize" => {
+                    peak_normalize.get_or_insert_with(PeakNormalizeConfig::default);
+                }
+                "--peak-target" => {
+                    let value = args.next().context("--peak-target requires a value in dBFS")?;
+                    let target: f32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --peak-target '{}'", value))?;
+                    if !(target <= 0.0 && target.is_finite()) {
+                        bail!("--peak-target must be at most 0 dBFS");
+                    }
+                    peak_normalize.get_or_insert_with(PeakNormalizeConfig::default).target_dbfs = target;
+                }
+                "--max-gain" => {
+                    let value = args.next().context("--max-gain requires a value in dB")?;
+                    let gain: f32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --max-gain '{}'", value))?;
+                    if !(gain >= 0.0 && gain.is_finite()) {
+                        bail!("--max-gain must be a non-negative number of dB");
+                    }
+                    peak_normalize.get_or_insert_with(PeakNormalizeConfig::default).max_gain_db = gain;
+                }
+                "--echo-suppress" => {
+                    echo_suppression.get_or_insert(DEFAULT_ECHO_STRENGTH);
+                }
+                "--echo-strength" => {
+                    let value = args.next().context("--echo-strength requires a value")?;
+                    let strength: f32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --echo-strength '{}'", value))?;
+                    if !(0.0..=1.0).contains(&strength) {
+                        bail!("--echo-strength must be between 0 and 1");
+                    }
+                    echo_suppression = Some(strength);
+                }
+                "--transcript" => {
+                    transcript = Some(args.next().context("--transcript requires a path")?);
+                }
+                "--json" => json = true,
+                "--websocket" => {
+                    websocket = Some(args.next().context("--websocket requires an address")?);
+                }
+                "--osc" => {
+                    osc = Some(args.next().context("--osc requires an address")?);
+                }
+                "--fifo" => {
+                    fifo = Some(args.next().context("--fifo requires a path")?);
+                }
+                "--fifo-format" => {
+                    fifo_format = args.next().context("--fifo-format requires a format")?;
+                }
+                "--final-only" => {
+                    final_only.push(args.next().context("--final-only requires a sink name")?);
+                }
+                "--whisper-config" => {
+                    whisper_config = Some(args.next().context("--whisper-config requires a path")?);
+                }
+                "--whisper-model" => {
+                    whisper_model = Some(args.next().context("--whisper-model requires a path")?);
+                }
+                "--translate-model" => {
+                    translate_model = Some(args.next().context("--translate-model requires a directory")?);
+                }
+                "--zh-en-model" => {
+                    zh_en_model = Some(args.next().context("--zh-en-model requires a directory")?);
+                }
+                "--device" => device = Some(args.next().context("--device requires a device name")?),
+                "--mix-devices" => mix_devices = Some(args.next().context("--mix-devices requires a device list")?),
+                "--language" => language = Some(args.next().context("--language requires a language code")?),
+                "--auto-model" => auto_model = true,
+                "--memory-budget" => {
+                    let value = args.next().context("--memory-budget requires a value in MB")?;
+                    memory_budget = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid --memory-budget '{}'", value))?,
+                    );
+                    auto_model = true;
+                }
+                "--edge-fade-ms" => {
+                    let value = args.next().context("--edge-fade-ms requires a value")?;
+                    edge_fade_ms = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid --edge-fade-ms '{}'", value))?,
+                    );
+                }
+                "--adaptive-model" => {
+                    adaptive_model.get_or_insert_with(AdaptiveModelConfig::default);
+                }
+                "--max-queue-latency" => {
+                    let secs = parse_seconds("--max-queue-latency", args.next())?;
+                    adaptive_model.get_or_insert_with(AdaptiveModelConfig::default).max_queue_secs = secs;
+                }
+                "--adaptive-upgrade" => {
+                    adaptive_model.get_or_insert_with(AdaptiveModelConfig::default).upgrade = true;
+                }
+                "--chunk-timeout" => {
+                    chunk_timeout = Some(parse_seconds("--chunk-timeout", args.next())? as f32);
+                }
+                "--word-confidence" => word_confidence = true,
+                "--max-segment-len" => {
+                    let value = args.next().context("--max-segment-len requires a number of characters")?;
+                    let max_len: i32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --max-segment-len '{}'", value))?;
+                    if max_len <= 0 {
+                        bail!("--max-segment-len must be a positive number of characters");
+                    }
+                    max_segment_len = Some(max_len);
+                }
+                "--split-on-word" => split_on_word = true,
+                "--clear-after" => clear_after = Some(parse_seconds("--clear-after", args.next())?),
+                "--transcribe-only" => transcribe_only = true,
+                "--no-normalize" => no_normalize = true,
+                "--min-translate-confidence" => {
+                    let value = args.next().context("--min-translate-confidence requires a value")?;
+                    let threshold: f32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --min-translate-confidence '{}'", value))?;
+                    if !(0.0..=1.0).contains(&threshold) {
+                        bail!("--min-translate-confidence must be between 0 and 1");
+                    }
+                    min_translate_confidence = Some(threshold);
+                }
+                "--translate-every" => {
+                    let value = args.next().context("--translate-every requires a count")?;
+                    let every: u32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --translate-every '{}'", value))?;
+                    if every == 0 {
+                        bail!("--translate-every must be at least 1");
+                    }
+                    throttle_every = Some(every);
+                    translation_throttle.get_or_insert_with(ThrottleConfig::default);
+                }
+                "--translate-interval" => {
+                    let secs = parse_seconds("--translate-interval", args.next())?;
+                    translation_throttle.get_or_insert_with(ThrottleConfig::default).min_interval =
+                        std::time::Duration::from_secs_f64(secs);
+                    // 只给间隔时按时间限流，不再额外按条数跳过
+                    throttle_every.get_or_insert(1);
+                }
+                "--throttle-rtf" => {
+                    let value = args.next().context("--throttle-rtf requires a value")?;
+                    let rtf: f64 = value
+                        .parse()
+                        .with_context(|| format!("invalid --throttle-rtf '{}'", value))?;
+                    if !(rtf > 0.0 && rtf.is_finite()) {
+                        bail!("--throttle-rtf must be a positive number");
+                    }
+                    translation_throttle.get_or_insert_with(ThrottleConfig::default).auto_rtf = Some(rtf);
+                }
+                "--unclear-below" => {
+                    let value = args.next().context("--unclear-below requires a value")?;
+                    let threshold: f32 = value
+                        .parse()
+                        .with_context(|| format!("invalid --unclear-below '{}'", value))?;
+                    if !(0.0..=1.0).contains(&threshold) {
+                        bail!("--unclear-below must be between 0 and 1");
+                    }
+                    unclear_below = Some(threshold);
+                }
+                "--unclear-placeholder" => {
+                    unclear_placeholder = Some(args.next().context("--unclear-placeholder requires a value")?);
+                }
+                "--keep-unclear-guess" => keep_unclear_guess = true,
+                "--sentence-hold" => {
+                    sentence_hold = Some(parse_seconds("--sentence-hold", args.next())?);
+                }
+                "--paragraph-silence" => {
+                    paragraph_silence = Some(parse_seconds("--paragraph-silence", args.next())?);
+                }
+                "--sliding-window" => {
+                    sliding_window = Some(parse_seconds("--sliding-window", args.next())?);
+                }
+                "--hop" => hop = Some(parse_seconds("--hop", args.next())?),
+                "--stability" => {
+                    let value = args.next().context("--stability requires a value")?;
+                    let k: usize = value
+                        .parse()
+                        .with_context(|| format!("invalid --stability '{}'", value))?;
+                    if k == 0 {
+                        bail!("--stability must be at least 1");
+                    }
+                    stability = Some(k);
+                }
+                "--stable-timeout" => {
+                    stable_timeout = Some(parse_seconds("--stable-timeout", args.next())?);
+                }
+                "--revise-captions" => revise_captions = true,
+                "--recovery-threshold" => {
+                    let value = args.next().context("--recovery-threshold requires a value")?;
+                    recovery_threshold = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid --recovery-threshold '{}'", value))?,
+                    );
+                }
+                "--pinyin" => pinyin = true,
+                "--dump-chunks" => {
+                    dump_chunks = Some(args.next().context("--dump-chunks requires a directory")?);
+                }
+                "--trace-chunks" => {
+                    trace_chunks = Some(args.next().context("--trace-chunks requires a path")?);
+                }
+                "--dump-chunks-max" => {
+                    let value = args.next().context("--dump-chunks-max requires a value")?;
+                    dump_chunks_max = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid --dump-chunks-max '{}'", value))?,
+                    );
+                }
+                "--confidence-colors" => confidence_colors = true,
+                "--tui" => tui = true,
+                "--show" => {
+                    let value = args.next().context("--show requires both|source|target")?;
+                    display_content = parse_display_content(&value)?;
+                }
+                "--resume" => resume = true,
+                "--glossary" => glossary = Some(args.next().context("--glossary requires a file")?),
+                "--protect-code" => protect_code = true,
+                "--localize" => localize = Some(args.next().context("--localize requires a locale")?),
+                "--protect-pattern" => {
+                    protect_patterns.push(args.next().context("--protect-pattern requires a regular expression")?);
+                    protect_code = true;
+                }
+                "--tokenizer-repo" => {
+                    tokenizer_repo = Some(args.next().context("--tokenizer-repo requires a repo id")?);
+                }
+                "--zh-en-tokenizer-repo" => {
+                    zh_en_tokenizer_repo = Some(args.next().context("--zh-en-tokenizer-repo requires a repo id")?);
+                }
+                "--top-p" => {
+                    let value = args.next().context("--top-p requires a value")?;
+                    let p: f64 = value
+                        .parse()
+                        .with_context(|| format!("invalid --top-p '{}'", value))?;
+                    if !(p > 0.0 && p <= 1.0) {
+                        bail!("--top-p must be in (0, 1]");
+                    }
+                    top_p = Some(p);
+                }
+                "--min-translate-chars" => {
+                    let value = args.next().context("--min-translate-chars requires a count")?;
+                    min_translate_chars = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid --min-translate-chars '{}'", value))?,
+                    );
+                }
+                "--mask-words" => mask_words = Some(args.next().context("--mask-words requires a file")?),
+                "--mask-replacement" => {
+                    mask_replacement = Some(args.next().context("--mask-replacement requires a value")?);
+                }
+                "--marker" => {
+                    let value = args.next().context("--marker requires MARKER=REPLACEMENT")?;
+                    let (marker, replacement) = value
+                        .split_once('=')
+                        .with_context(|| format!("invalid --marker '{}', expected MARKER=REPLACEMENT", value))?;
+                    let replacement = (!replacement.is_empty()).then(|| replacement.to_string());
+                    markers.push((marker.to_string(), replacement));
+                }
+                "--stdin-format" => {
+                    let value = args.next().context("--stdin-format requires <rate>:<channels>:<f32|i16>")?;
+                    stdin_format = Some(StdinFormat::parse(&value)?);
+                }
+                other => bail!("unknown argument: {}", other),
+            }
+        }
+        if let (Command::Serve { workers, .. }, Some(n)) = (&mut command, serve_workers) {
+            *workers = n;
+        }
+        if let (Some(throttle), Some(every)) = (&mut translation_throttle, throttle_every) {
+            throttle.every = every;
+        }
+        Ok(Self {
+            command,
+            resample_quality,
+            mix,
+            buffer_frames,
+            warmup_ms,
+            max_flush_interval,
+            peak_normalize,
+            echo_suppression,
+            fused_worker,
+            realtime_priority,
+            transcript,
+            json,
+            websocket,
+            osc,
+            fifo,
+            fifo_format,
+            final_only,
+            whisper_config,
+            whisper_model,
+            translate_model,
+            zh_en_model,
+            resume,
+            glossary,
+            protect_code,
+            localize,
+            protect_patterns,
+            top_p,
+            min_translate_chars,
+            tokenizer_repo,
+            zh_en_tokenizer_repo,
+            device,
+            mix_devices,
+            language,
+            auto_model,
+            memory_budget,
+            edge_fade_ms,
+            chunk_timeout,
+            word_confidence,
+            max_segment_len,
+            split_on_word,
+            clear_after,
+            adaptive_model,
+            // 只显示原文时不需要翻译
+            transcribe_only: transcribe_only || display_content == DisplayContent::SourceOnly,
+            no_normalize,
+            min_translate_confidence,
+            translation_throttle,
+            unclear_below,
+            unclear_placeholder,
+            keep_unclear_guess,
+            sentence_hold,
+            paragraph_silence,
+            sliding_window,
+            hop,
+            stability,
+            stable_timeout,
+            revise_captions,
+            recovery_threshold,
+            dump_chunks,
+            dump_chunks_max,
+            trace_chunks,
+            pinyin,
+            confidence_colors,
+            display_content,
+            tui,
+            mask_words,
+            mask_replacement,
+            markers,
+            stdin_format,
+        })
+    }
+}
+
+/// 解析一个表示秒数的正数参数
+fn parse_seconds(flag: &str, value: Option<String>) -> anyhow::Result<f64> {
+    let value = value.with_context(|| format!("{} requires a value in seconds", flag))?;
+    let secs: f64 = value
+        .parse()
+        .with_context(|| format!("invalid {} '{}'", flag, value))?;
+    if !(secs > 0.0 && secs.is_finite()) {
+        bail!("{} must be a positive number of seconds", flag);
+    }
+    Ok(secs)
+}
+
+fn parse_display_content(value: &str) -> anyhow::Result<DisplayContent> {
+    Ok(match value {
+        "both" => DisplayContent::Both,
+        "source" => DisplayContent::SourceOnly,
+        "target" => DisplayContent::TargetOnly,
+        other => bail!("invalid --show '{}', expected both|source|target", other),
+    })
+}
+
+fn parse_resample_quality(value: &str) -> anyhow::Result<ConverterType> {
+    Ok(match value {
+        "linear" => ConverterType::Linear,
+        "fastest" => ConverterType::SincFastest,
+        "medium" => ConverterType::SincMediumQuality,
+        "best" => ConverterType::SincBestQuality,
+        other => bail!("invalid --resample-quality '{}', expected linear|fastest|medium|best", other),
+    })
+}
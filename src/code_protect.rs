@@ -0,0 +1,122 @@
+use anyhow::Context;
+use log::debug;
+use regex::Regex;
+
+use crate::translation::{DecodeStats, Translate};
+
+/// 默认识别的代码、URL 类片段：网址、域名、邮箱、路径、反引号中的代码、
+/// `foo.bar()`/`std::fs` 形式的调用与路径、snake_case 与 camelCase 标识符
+pub const DEFAULT_CODE_PATTERNS: &[&str] = &[
+    r"(?i)\b(?:https?://|www\.)\S+",
+    r"(?i)\b[a-z0-9-]+(?:\.[a-z0-9-]+)*\.(?:com|org|net|io|dev|rs|ai|app|co|cn|edu|gov)\b(?:/\S*)?",
+    r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+",
+    r"(?:~|\.{1,2})?/[\w.-]+/[\w./-]*",
+    r"`[^`]+`",
+    r"\b[A-Za-z_]\w+(?:(?:\.|::)[A-Za-z_]\w+)+(?:\(\))?",
+    r"\b[A-Za-z_]\w*\(\)",
+    r"\b[A-Za-z0-9]+_\w+\b",
+    r"\b[a-z]+[A-Z]\w*\b",
+];
+
+/// 片段末尾的这些标点通常属于句子而不属于代码，不计入受保护的片段
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', '"', '\''];
+
+/// 在任意翻译器外套一层代码保护：翻译前把代码、URL 一类片段换成占位符，翻译后还原，
+/// 只翻译周围的文字，避免网址和代码被译成中文而无法使用。
+///
+/// 模型丢掉或改写了占位符时，改为直接翻译原文。
+pub struct CodeProtectTranslator {
+    inner: Box<dyn Translate>,
+    patterns: Vec<Regex>,
+}
+
+impl CodeProtectTranslator {
+    /// `patterns` 为识别受保护片段的正则表达式，为空时使用 `DEFAULT_CODE_PATTERNS`
+    pub fn new(inner: Box<dyn Translate>, patterns: &[String]) -> anyhow::Result<Self> {
+        let patterns = if patterns.is_empty() {
+            DEFAULT_CODE_PATTERNS.iter().map(|p| Regex::new(p)).collect::<Result<Vec<_>, _>>()?
+        } else {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p).with_context(|| format!("invalid code pattern '{}'", p)))
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        Ok(Self { inner, patterns })
+    }
+
+    /// 找出所有受保护的片段，重叠的片段合并，按位置排列
+    fn spans(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut spans: Vec<(usize, usize)> = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| pattern.find_iter(text))
+            .map(|m| (m.start(), m.start() + m.as_str().trim_end_matches(TRAILING_PUNCTUATION).len()))
+            .filter(|(start, end)| end > start)
+            .collect();
+        spans.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    /// 把受保护的片段换成占位符，返回替换后的文本和（占位符, 原片段）列表
+    fn protect(&self, text: &str) -> (String, Vec<(String, String)>) {
+        let mut protected = String::with_capacity(text.len());
+        let mut placeholders = Vec::new();
+        let mut last = 0;
+        for (start, end) in self.spans(text) {
+            // 与术语表的 "ZX{n}Q" 区分开，两者叠加使用时互不干扰
+            let placeholder = format!("ZC{}Q", placeholders.len());
+            protected.push_str(&text[last..start]);
+            protected.push_str(&placeholder);
+            placeholders.push((placeholder, text[start..end].to_string()));
+            last = end;
+        }
+        protected.push_str(&text[last..]);
+        (protected, placeholders)
+    }
+}
+
+impl Translate for CodeProtectTranslator {
+    fn translate(&mut self, text: &str) -> anyhow::Result<String> {
+        self.translate_from(None, text)
+    }
+
+    fn translate_from(&mut self, language: Option<&str>, text: &str) -> anyhow::Result<String> {
+        let (protected, placeholders) = self.protect(text);
+        if placeholders.is_empty() {
+            return self.inner.translate_from(language, text);
+        }
+        debug!("Protecting code-like spans from translation: {:?}", placeholders);
+        let translated = self.inner.translate_from(language, &protected)?;
+        if placeholders.iter().all(|(placeholder, _)| translated.contains(placeholder.as_str())) {
+            Ok(placeholders
+                .iter()
+                .fold(translated, |text, (placeholder, span)| text.replace(placeholder.as_str(), span)))
+        } else {
+            debug!("Translator dropped code placeholders, translating without them: {:?}", translated);
+            self.inner.translate_from(language, text)
+        }
+    }
+
+    fn last_stats(&self) -> Option<DecodeStats> {
+        self.inner.last_stats()
+    }
+
+    fn load_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        self.inner.load_cache(path)
+    }
+
+    fn save_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        self.inner.save_cache(path)
+    }
+
+    fn is_cache_dirty(&self) -> bool {
+        self.inner.is_cache_dirty()
+    }
+}
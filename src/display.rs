@@ -1,12 +1,57 @@
+use std::collections::HashMap;
 use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use crossterm::{
+    cursor::{MoveTo, MoveUp},
     style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
     ExecutableCommand,
 };
+use unicode_width::UnicodeWidthChar;
+
+use crate::sink::{Caption, CaptionEvent, CaptionSink, DisplayContent, SegmentConfidence};
+
+/// 默认置信度低于该值的字幕以暗色显示，提示可能识别有误
+const LOW_CONFIDENCE: f32 = 0.5;
+
+/// 定时清屏线程检查是否到期的间隔
+const CLEAR_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 定时清屏的共享状态。显示端输出期间持有锁，清屏不会与输出交错
+struct ClearTimer {
+    /// 到期后清屏；`None` 表示屏幕已清空或还没有字幕
+    deadline: Option<Instant>,
+    /// 清屏后置位，提示显示端临时字幕已被一并擦掉
+    cleared: bool,
+}
 
 pub struct ScrollingDisplay {
     current_english: String,
     current_chinese: String,
+    /// 译文的拼音，没有时为空
+    current_pinyin: String,
+    current_confidence: Option<f32>,
+    current_speaker: Option<u32>,
+    /// 当前字幕原文各片段的置信度，与原文对得上时逐段着色
+    current_segments: Vec<SegmentConfidence>,
+    /// 是否按置信度渐变为原文着色（替代固定的黄色）
+    confidence_coloring: bool,
+    /// 置信度低于该值的字幕以暗色显示
+    low_confidence: f32,
+    /// 显示原文、译文或两者
+    content: DisplayContent,
+    /// 临时字幕占用的终端行数（长文本会折行），下一次输出前需要先擦掉，0 表示没有临时字幕
+    partial_rows: u16,
+    /// 特殊标记（键为大写形式）到替换文本的映射，`None` 表示不显示
+    markers: HashMap<String, Option<String>>,
+    /// 设置后，超过该时长没有新文本就清屏
+    clear_timer: Option<(Duration, Arc<Mutex<ClearTimer>>)>,
+    /// 最近一次 `display_text` 输出占用的终端行数
+    last_rows: u16,
+    /// 屏幕上最后一条字幕的序号及其占用的行数，收到它的修订时原地改写
+    last_caption: Option<(u64, u16)>,
 }
 
 impl ScrollingDisplay {
@@ -14,43 +59,260 @@ impl ScrollingDisplay {
         Self {
             current_english: String::new(),
             current_chinese: String::new(),
+            current_pinyin: String::new(),
+            current_confidence: None,
+            current_speaker: None,
+            current_segments: Vec::new(),
+            confidence_coloring: false,
+            low_confidence: LOW_CONFIDENCE,
+            content: DisplayContent::Both,
+            partial_rows: 0,
+            markers: HashMap::new(),
+            clear_timer: None,
+            last_rows: 0,
+            last_caption: None,
         }
     }
 
+    /// 设置特殊标记（如 "[MUSIC]"）的显示方式：映射到替换文本，或映射到 `None` 隐藏。
+    /// 匹配不区分大小写；未列出的标记一律隐藏。
+    pub fn with_markers(mut self, markers: HashMap<String, Option<String>>) -> Self {
+        self.markers = markers
+            .into_iter()
+            .map(|(marker, replacement)| (marker.to_uppercase(), replacement))
+            .collect();
+        self
+    }
+
+    /// 按置信度渐变为原文着色：可信的片段为亮黄色，越不可信越暗、越偏红。
+    /// 有片段信息时逐段着色，否则整行按字幕的置信度着色
+    pub fn with_confidence_coloring(mut self, enabled: bool) -> Self {
+        self.confidence_coloring = enabled;
+        self
+    }
+
+    /// 设置以暗色显示的置信度门限（默认 0.5），可与流水线的翻译置信度门限保持一致
+    pub fn with_low_confidence(mut self, threshold: f32) -> Self {
+        self.low_confidence = threshold;
+        self
+    }
+
+    /// 只显示原文或只显示译文，默认两者都显示
+    pub fn with_content(mut self, content: DisplayContent) -> Self {
+        self.content = content;
+        self
+    }
+
+    /// 超过 `after` 没有新文本（最终或临时字幕）时清屏，避免说话人停下后旧字幕一直留在画面上，
+    /// 适合作为视频叠加层（如 OBS）使用。每次有新文本都会重新计时
+    pub fn with_clear_after(mut self, after: Duration) -> Self {
+        let timer = Arc::new(Mutex::new(ClearTimer {
+            deadline: None,
+            cleared: false,
+        }));
+        // 线程只持有弱引用，显示端被丢弃后随之退出
+        let weak = Arc::downgrade(&timer);
+        thread::spawn(move || loop {
+            thread::sleep(CLEAR_POLL_INTERVAL);
+            let Some(timer) = weak.upgrade() else {
+                break;
+            };
+            let mut timer = timer.lock().unwrap();
+            if timer.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                timer.deadline = None;
+                timer.cleared = true;
+                clear_screen();
+            }
+        });
+        self.clear_timer = Some((after, timer));
+        self
+    }
+
     // 检查是否是特殊标记（如[音乐]等）
     fn is_special_mark(text: &str) -> bool {
         text.starts_with('[') && text.ends_with(']')
     }
 
-    pub fn add_text(&mut self, english: &str, chinese: &str) {
-        // 如果是特殊标记，不显示
-        if Self::is_special_mark(english) || Self::is_special_mark(chinese) {
-            return;
-        }
+    /// 更新并显示一条字幕。`confidence` 为转录置信度，低于暗色门限时以暗色显示；
+    /// 给出 `speaker` 时原文前加 "Speaker N: "；`segments` 为原文各片段的置信度，供逐段着色；
+    /// 给出 `pinyin` 时在译文下方再显示一行拼音。
+    pub fn add_text(
+        &mut self,
+        english: &str,
+        chinese: &str,
+        pinyin: Option<&str>,
+        confidence: Option<f32>,
+        speaker: Option<u32>,
+        segments: &[SegmentConfidence],
+    ) {
+        self.last_rows = 0;
+        // 特殊标记按映射替换，未配置的不显示
+        let (english, chinese) = if Self::is_special_mark(english) {
+            match self.markers.get(&english.to_uppercase()) {
+                Some(Some(replacement)) => (replacement.clone(), String::new()),
+                _ => return,
+            }
+        } else if Self::is_special_mark(chinese) {
+            (english.to_string(), String::new())
+        } else {
+            (english.to_string(), chinese.to_string())
+        };
 
         // 直接更新当前文本
-        self.current_english = english.to_string();
-        self.current_chinese = chinese.to_string();
+        self.current_english = english;
+        self.current_pinyin = match pinyin {
+            Some(pinyin) if !chinese.is_empty() => pinyin.to_string(),
+            _ => String::new(),
+        };
+        self.current_chinese = chinese;
+        self.current_confidence = confidence;
+        self.current_speaker = speaker;
+        self.current_segments = segments.to_vec();
 
         // 显示文本
+        self.clear_partial();
         self.display_text();
     }
 
-    fn display_text(&self) {
-        // 显示英文（黄色）
+    /// 显示一条最终字幕，并记下它的序号和占用的行数以备修订
+    fn show_caption(&mut self, caption: &Caption) {
+        self.add_text(
+            &caption.source,
+            caption.translation.as_deref().unwrap_or(""),
+            caption.pinyin.as_deref(),
+            caption.confidence,
+            caption.speaker,
+            &caption.segments,
+        );
+        self.last_caption = caption.index.filter(|_| self.last_rows > 0).map(|index| (index, self.last_rows));
+    }
+
+    /// 在当前行显示临时字幕（灰色），后续输出会覆盖它
+    pub fn show_partial(&mut self, text: &str) {
+        self.clear_partial();
+        if text.is_empty() {
+            return;
+        }
         io::stdout()
-            .execute(SetForegroundColor(Color::Yellow))
+            .execute(SetForegroundColor(Color::DarkGrey))
             .unwrap()
-            .execute(Print(&self.current_english))
+            .execute(Print(text))
             .unwrap()
             .execute(ResetColor)
+            .unwrap();
+        io::stdout().flush().unwrap();
+        let columns = terminal::size().map(|(columns, _)| columns).unwrap_or(80);
+        self.partial_rows = rows_needed(text, columns);
+    }
+
+    /// 擦掉临时字幕：回到它的第一行行首，清除到屏幕末尾
+    fn clear_partial(&mut self) {
+        if self.partial_rows == 0 {
+            return;
+        }
+        if self.partial_rows > 1 {
+            io::stdout().execute(MoveUp(self.partial_rows - 1)).unwrap();
+        }
+        io::stdout()
+            .execute(Print("\r"))
             .unwrap()
-            .execute(Print("\n"))
+            .execute(Clear(ClearType::FromCursorDown))
+            .unwrap();
+        self.partial_rows = 0;
+    }
+
+    /// 改写屏幕上的最后一条字幕：擦掉它（以及其后的临时字幕）后按修订重新显示。
+    /// 修订的不是最后一条时返回 `false`，更早的字幕已被后续输出隔开，不再改动
+    fn revise_last(&mut self, index: u64) -> bool {
+        let Some((_, rows)) = self.last_caption.filter(|(last, _)| *last == index) else {
+            return false;
+        };
+        self.clear_partial();
+        if rows > 0 {
+            io::stdout().execute(MoveUp(rows)).unwrap();
+        }
+        io::stdout()
+            .execute(Print("\r"))
+            .unwrap()
+            .execute(Clear(ClearType::FromCursorDown))
+            .unwrap();
+        true
+    }
+
+    /// 显示一条分隔线，表示长时间静音后的分段
+    pub fn add_separator(&mut self) {
+        self.last_caption = None;
+        self.clear_partial();
+        io::stdout()
+            .execute(SetForegroundColor(Color::DarkGrey))
+            .unwrap()
+            .execute(Print("────────────────────────\n"))
+            .unwrap()
+            .execute(ResetColor)
             .unwrap();
+        io::stdout().flush().unwrap();
+    }
+
+    fn display_text(&mut self) {
+        let columns = terminal::size().map(|(columns, _)| columns).unwrap_or(80);
+        let label = self.current_speaker.map(|speaker| format!("Speaker {}: ", speaker)).unwrap_or_default();
+        let shows_source = self.content.shows_source(!self.current_chinese.is_empty());
+        let shows_target = !self.current_chinese.is_empty() && self.content.shows_target();
+        // 记下占用的行数，供修订时原地改写；说话人前缀与第一行一起计算
+        self.last_rows = 0;
+        if shows_source {
+            self.last_rows += rows_needed(&format!("{}{}", label, self.current_english), columns);
+        }
+        if shows_target {
+            let prefix = if shows_source { "" } else { label.as_str() };
+            self.last_rows += rows_needed(&format!("{}{}", prefix, self.current_chinese), columns);
+            if !self.current_pinyin.is_empty() {
+                self.last_rows += rows_needed(&self.current_pinyin, columns);
+            }
+        }
+
+        // 低置信度的字幕改用暗色
+        let dim = self.current_confidence.is_some_and(|c| c < self.low_confidence);
+        let (english_color, chinese_color) = if dim {
+            (Color::DarkYellow, Color::DarkGreen)
+        } else {
+            (Color::Yellow, Color::Green)
+        };
+
+        // 说话人前缀加在显示的第一行前
+        if let Some(speaker) = self.current_speaker {
+            io::stdout()
+                .execute(SetForegroundColor(english_color))
+                .unwrap()
+                .execute(Print(format!("Speaker {}: ", speaker)))
+                .unwrap();
+        }
+
+        // 显示英文（黄色，或按置信度渐变）
+        if self.content.shows_source(!self.current_chinese.is_empty()) {
+            if self.confidence_coloring {
+                self.print_confidence_colored();
+            } else {
+                io::stdout()
+                    .execute(SetForegroundColor(english_color))
+                    .unwrap()
+                    .execute(Print(&self.current_english))
+                    .unwrap();
+            }
+            io::stdout()
+                .execute(ResetColor)
+                .unwrap()
+                .execute(Print("\n"))
+                .unwrap();
+        }
 
-        // 显示中文（绿色）
+        // 显示中文（绿色），只做转录时没有译文
+        if self.current_chinese.is_empty() || !self.content.shows_target() {
+            io::stdout().flush().unwrap();
+            return;
+        }
         io::stdout()
-            .execute(SetForegroundColor(Color::Green))
+            .execute(SetForegroundColor(chinese_color))
             .unwrap()
             .execute(Print(&self.current_chinese))
             .unwrap()
@@ -59,6 +321,143 @@ impl ScrollingDisplay {
             .execute(Print("\n"))
             .unwrap();
 
+        // 显示拼音（灰色）
+        if !self.current_pinyin.is_empty() {
+            io::stdout()
+                .execute(SetForegroundColor(Color::Grey))
+                .unwrap()
+                .execute(Print(&self.current_pinyin))
+                .unwrap()
+                .execute(ResetColor)
+                .unwrap()
+                .execute(Print("\n"))
+                .unwrap();
+        }
+
         io::stdout().flush().unwrap();
     }
-} 
\ No newline at end of file
+
+    /// 按置信度着色输出原文。片段文本与原文一致（未被后处理改写）时逐段着色，否则整行着色
+    fn print_confidence_colored(&self) {
+        let words = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let joined = self
+            .current_segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.current_segments.is_empty() || words(&joined) != words(&self.current_english) {
+            let color = confidence_color(self.current_confidence.unwrap_or(1.0));
+            io::stdout()
+                .execute(SetForegroundColor(color))
+                .unwrap()
+                .execute(Print(&self.current_english))
+                .unwrap();
+            return;
+        }
+        for (i, segment) in self.current_segments.iter().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            io::stdout()
+                .execute(SetForegroundColor(confidence_color(segment.confidence)))
+                .unwrap()
+                .execute(Print(format!("{}{}", separator, segment.text)))
+                .unwrap();
+        }
+    }
+} 
+
+/// 清空整个终端并把光标移到左上角
+fn clear_screen() {
+    let _ = io::stdout()
+        .execute(Clear(ClearType::All))
+        .and_then(|stdout| stdout.execute(MoveTo(0, 0)));
+    let _ = io::stdout().flush();
+}
+
+/// 单行文本在 `columns` 列宽的终端中占用的行数。中文等全角字符占两列，
+/// 行尾只剩一列时终端会把全角字符整个折到下一行，所以要逐字计算，不能直接用总宽度除以列数
+fn rows_needed(text: &str, columns: u16) -> u16 {
+    let columns = columns.max(1) as usize;
+    let mut rows = 1usize;
+    let mut row_width = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if row_width + w > columns && row_width > 0 {
+            rows += 1;
+            row_width = 0;
+        }
+        row_width += w;
+    }
+    rows.min(u16::MAX as usize) as u16
+}
+
+/// 置信度到颜色的渐变：0.3 以下为暗红，0.3~0.9 之间逐渐过渡，0.9 以上为亮黄
+fn confidence_color(confidence: f32) -> Color {
+    let t = ((confidence - 0.3) / 0.6).clamp(0.0, 1.0);
+    Color::Rgb {
+        r: (180.0 + 75.0 * t) as u8,
+        g: (60.0 + 195.0 * t) as u8,
+        b: (60.0 * (1.0 - t)) as u8,
+    }
+}
+
+impl CaptionSink for ScrollingDisplay {
+    fn emit(&mut self, event: &CaptionEvent) {
+        // 设置了定时清屏时，输出期间持有锁；屏幕已被清空则临时字幕也已不在
+        let clear_timer = self.clear_timer.as_ref().map(|(after, timer)| (*after, Arc::clone(timer)));
+        let mut clear_guard = clear_timer.as_ref().map(|(after, timer)| (*after, timer.lock().unwrap()));
+        if let Some((after, timer)) = clear_guard.as_mut() {
+            if std::mem::take(&mut timer.cleared) {
+                self.partial_rows = 0;
+                self.last_caption = None;
+            }
+            // 有新文本时重新计时；清空临时字幕不算新文本
+            if !matches!(event, CaptionEvent::Partial(caption) if caption.source.is_empty()) {
+                timer.deadline = Some(Instant::now() + *after);
+            }
+        }
+        match event {
+            CaptionEvent::Final(caption) => self.show_caption(caption),
+            // 只改写屏幕上的最后一条字幕
+            CaptionEvent::Revision(caption) => {
+                if caption.index.is_some_and(|index| self.revise_last(index)) {
+                    self.show_caption(caption);
+                }
+            }
+            // 临时字幕只有原文，只显示译文时不显示
+            CaptionEvent::Partial(_) if self.content == DisplayContent::TargetOnly => {}
+            CaptionEvent::Partial(caption) => self.show_partial(&caption.source),
+            CaptionEvent::ParagraphBreak => self.add_separator(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_needed_wraps_wide_chars_whole() {
+        // 81 列放得下 40 个汉字，第 41 个整个折到下一行：81 个汉字要 3 行，而不是 162 / 81 = 2 行
+        let text = "中".repeat(81);
+        assert_eq!(rows_needed(&text, 81), 3);
+        assert_eq!(rows_needed(&"a".repeat(81), 81), 1);
+        assert_eq!(rows_needed(&"a".repeat(82), 81), 2);
+    }
+
+    #[test]
+    fn rows_needed_shrinks_with_the_line() {
+        // 临时字幕从一长串中文变回短句时，需要清掉的行数按上一次的文本计算
+        assert_eq!(rows_needed(&"你好世界".repeat(30), 80), 3);
+        assert_eq!(rows_needed("你好", 80), 1);
+        assert_eq!(rows_needed("", 80), 1);
+    }
+
+    #[test]
+    fn rows_needed_handles_mixed_text_at_odd_widths() {
+        // 中英混排、列宽为奇数时逐字计算，不会在多字节字符中间切分
+        let text = "Hello 世界，这是一段中英混排的字幕";
+        assert_eq!(rows_needed(text, 7), 6);
+        assert_eq!(rows_needed(text, 1), 20);
+    }
+}
@@ -1,35 +1,106 @@
 use reqwest::blocking::Client;
+use reqwest::StatusCode;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
+use anyhow::Context;
 use log::info;
 
+/// 访问受限（gated）或私有的 Hugging Face 模型时使用的访问令牌
+const HF_TOKEN_ENV: &str = "HF_TOKEN";
+
 /// 下载文件，并保存到指定路径。如果下载失败则 panic。
 pub fn download_file(url: &str, output_path: &str) {
+    if let Err(e) = fetch_file(url, Path::new(output_path)) {
+        panic!("{:#}", e);
+    }
+}
+
+/// 下载文件并保存到指定路径，失败时返回错误。先写入临时文件再改名，中断的下载不会留下不完整的文件
+pub fn fetch_file(url: &str, output_path: &Path) -> anyhow::Result<()> {
     info!("Downloading from {} ...", url);
 
     // 使用 Client 构建器设置超时时间（例如 600 秒）
     let client = Client::builder()
         .timeout(Duration::from_secs(600))
         .build()
-        .expect("Failed to build client");
+        .context("failed to build HTTP client")?;
 
-    let response = client.get(url).send().expect("Failed to send request");
+    let mut request = client.get(url);
+    // 令牌只发给 Hugging Face。reqwest 默认跟随重定向：同一主机内的跳转保留 Authorization，
+    // 跳到其他主机（如带签名 URL 的 CDN）时会去掉它，CDN 也不需要令牌
+    let token = hf_token().filter(|_| is_hugging_face(url));
+    if let Some(token) = &token {
+        request = request.bearer_auth(token);
+    }
+    let response = request
+        .send()
+        .with_context(|| format!("failed to request {}", url))?;
 
     // 检查响应状态码
-    if !response.status().is_success() {
-        panic!("Failed to download file: HTTP {}", response.status());
+    let status = response.status();
+    if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) && is_hugging_face(url) {
+        let hint = match token {
+            Some(_) => "check that the token in HF_TOKEN has access to this repository",
+            None => "the model may be gated or private; set HF_TOKEN to an access token",
+        };
+        anyhow::bail!("failed to download {}: HTTP {} ({})", url, status, hint);
+    }
+    if !status.is_success() {
+        anyhow::bail!("failed to download {}: HTTP {}", url, status);
     }
 
     // 如果输出目录不存在，则创建
-    if let Some(parent) = Path::new(output_path).parent() {
-        std::fs::create_dir_all(parent).expect("Failed to create directories");
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
     }
 
-    let mut file = File::create(output_path).expect("Failed to create file");
     // 直接将响应体复制到文件中
-    let content = response.bytes().expect("Failed to read response bytes");
-    file.write_all(&content).expect("Failed to write to file");
-    info!("Download completed and saved to {}", output_path);
+    let content = response.bytes().context("failed to read response body")?;
+    let partial = output_path.with_extension("part");
+    let mut file = File::create(&partial)
+        .with_context(|| format!("failed to create {}", partial.display()))?;
+    file.write_all(&content)
+        .with_context(|| format!("failed to write {}", partial.display()))?;
+    std::fs::rename(&partial, output_path)
+        .with_context(|| format!("failed to move download to {}", output_path.display()))?;
+    info!("Download completed and saved to {}", output_path.display());
+    Ok(())
+}
+
+/// 环境变量 `HF_TOKEN` 中的访问令牌，未设置或为空时为 `None`
+fn hf_token() -> Option<String> {
+    std::env::var(HF_TOKEN_ENV)
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// URL 是否指向 Hugging Face
+fn is_hugging_face(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .is_some_and(|host| host == "huggingface.co" || host.ends_with(".huggingface.co"))
+}
+
+/// 从 Hugging Face 仓库取得一个文件，缓存在 `cache_dir/<owner>--<name>/<revision>/` 下，
+/// 已缓存时不再下载。`repo` 写作 `owner/name`，或 `owner/name@revision` 指定分支、标签或提交（默认 main）
+#[allow(dead_code)]
+pub fn hub_file(repo: &str, file: &str, cache_dir: &Path) -> anyhow::Result<PathBuf> {
+    let (repo_id, revision) = repo.split_once('@').unwrap_or((repo, "main"));
+    if repo_id.split('/').count() != 2 || repo_id.split('/').any(str::is_empty) {
+        anyhow::bail!("invalid Hugging Face repo id '{}', expected owner/name", repo);
+    }
+    let path = cache_dir
+        .join(repo_id.replace('/', "--"))
+        .join(revision)
+        .join(file);
+    if !path.exists() {
+        let url = format!("https://huggingface.co/{}/resolve/{}/{}", repo_id, revision, file);
+        fetch_file(&url, &path).with_context(|| format!("failed to fetch {} from {}", file, repo))?;
+    }
+    Ok(path)
 }
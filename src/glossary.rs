@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::fs;
+use anyhow::Context;
+use log::debug;
+use serde::Deserialize;
+
+use crate::mask::mask_whole_word;
+use crate::translation::{DecodeStats, Translate};
+
+/// 术语表，从 JSON 文件读取：
+///
+/// ```json
+/// {
+///   "protect": ["Kubernetes", "WebAssembly"],
+///   "replace": { "库伯内特斯": "Kubernetes", "锈": "Rust" }
+/// }
+/// ```
+///
+/// `protect` 中的术语不交给模型翻译，原样保留在译文中；`replace` 在翻译之后把模型译出的词替换为指定译法。
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Glossary {
+    pub protect: Vec<String>,
+    pub replace: HashMap<String, String>,
+}
+
+impl Glossary {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("failed to read glossary {}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("failed to parse glossary {}", path))
+    }
+}
+
+/// 在任意翻译器外套一层术语表：翻译前把受保护的术语换成占位符，翻译后还原，再按 `replace` 替换译文中的词。
+///
+/// 英文术语不区分大小写、只匹配完整的词，还原时使用术语表中的写法。
+/// 模型丢掉或改写了占位符时，改为直接翻译原文，只应用 `replace`。
+pub struct GlossaryTranslator {
+    inner: Box<dyn Translate>,
+    protect: Vec<String>,
+    /// 按原词长度从长到短排列，避免短词先替换破坏长词
+    replace: Vec<(String, String)>,
+}
+
+impl GlossaryTranslator {
+    pub fn new(inner: Box<dyn Translate>, glossary: Glossary) -> Self {
+        let mut protect: Vec<String> = glossary
+            .protect
+            .into_iter()
+            .map(|term| term.trim().to_string())
+            .filter(|term| !term.is_empty())
+            .collect();
+        protect.sort_by_key(|term| std::cmp::Reverse(term.len()));
+        let mut replace: Vec<(String, String)> = glossary
+            .replace
+            .into_iter()
+            .filter(|(from, _)| !from.is_empty())
+            .collect();
+        replace.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+        Self { inner, protect, replace }
+    }
+
+    /// 把 `text` 中受保护的术语换成占位符，返回替换后的文本和（占位符, 术语）列表
+    fn protect_terms(&self, text: &str) -> (String, Vec<(String, String)>) {
+        let mut text = text.to_string();
+        let mut placeholders = Vec::new();
+        for term in &self.protect {
+            // 大写的无意义字母串，模型通常会原样抄到译文中
+            let placeholder = format!("ZX{}Q", placeholders.len());
+            let replaced = if term.is_ascii() {
+                mask_whole_word(&text, &term.to_ascii_lowercase(), &placeholder)
+            } else {
+                text.replace(term.as_str(), &placeholder)
+            };
+            if replaced != text {
+                text = replaced;
+                placeholders.push((placeholder, term.clone()));
+            }
+        }
+        (text, placeholders)
+    }
+
+    fn apply_replacements(&self, text: String) -> String {
+        self.replace
+            .iter()
+            .fold(text, |text, (from, to)| text.replace(from.as_str(), to))
+    }
+}
+
+impl Translate for GlossaryTranslator {
+    fn translate(&mut self, text: &str) -> anyhow::Result<String> {
+        self.translate_from(None, text)
+    }
+
+    fn translate_from(&mut self, language: Option<&str>, text: &str) -> anyhow::Result<String> {
+        let (protected, placeholders) = self.protect_terms(text);
+        let translation = if placeholders.is_empty() {
+            self.inner.translate_from(language, text)?
+        } else {
+            let translated = self.inner.translate_from(language, &protected)?;
+            if placeholders.iter().all(|(placeholder, _)| translated.contains(placeholder.as_str())) {
+                placeholders
+                    .iter()
+                    .fold(translated, |text, (placeholder, term)| text.replace(placeholder.as_str(), term))
+            } else {
+                debug!("Translator dropped glossary placeholders, translating without them: {:?}", translated);
+                self.inner.translate_from(language, text)?
+            }
+        };
+        Ok(self.apply_replacements(translation))
+    }
+
+    fn last_stats(&self) -> Option<DecodeStats> {
+        self.inner.last_stats()
+    }
+
+    fn load_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        self.inner.load_cache(path)
+    }
+
+    fn save_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        self.inner.save_cache(path)
+    }
+
+    fn is_cache_dirty(&self) -> bool {
+        self.inner.is_cache_dirty()
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use log::{info, error};
+use tiny_http::{Header, Response, Server};
+
+use crate::pipeline::PipelineState;
+
+/// 在独立线程中启动一个极简 HTTP 服务，`GET /healthz` 以 JSON 返回流水线状态。
+/// 启用 `metrics` feature 时，`GET /metrics` 以 Prometheus 文本格式返回运行指标。
+pub fn spawn(addr: &str, state: Arc<PipelineState>) -> anyhow::Result<JoinHandle<()>> {
+    let server = Server::http(addr).map_err(|e| anyhow::anyhow!("failed to bind {}: {}", addr, e))?;
+    info!("Health check listening on http://{}/healthz", addr);
+    #[cfg(feature = "metrics")]
+    info!("Prometheus metrics available at http://{}/metrics", addr);
+
+    let handle = thread::spawn(move || {
+        let json_header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+        for request in server.incoming_requests() {
+            let response = match request.url() {
+                "/healthz" => match serde_json::to_string(&state.snapshot()) {
+                    Ok(body) => Response::from_string(body).with_header(json_header.clone()),
+                    Err(e) => Response::from_string(e.to_string()).with_status_code(500),
+                },
+                #[cfg(feature = "metrics")]
+                "/metrics" => Response::from_string(crate::metrics::render(&state)).with_header(
+                    Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4"[..]).unwrap(),
+                ),
+                _ => Response::from_string("not found").with_status_code(404),
+            };
+            if let Err(e) = request.respond(response) {
+                error!("Failed to respond to health check: {}", e);
+            }
+        }
+    });
+    Ok(handle)
+}
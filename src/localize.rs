@@ -0,0 +1,338 @@
+use anyhow::bail;
+use regex::{Captures, Regex};
+
+use crate::normalize::TextPostProcessor;
+
+/// 大数的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberGrouping {
+    /// 按万、亿计："1,500,000" 写作 "150万"，"1.5 billion" 写作 "15亿"
+    Myriad,
+    /// 每三位加逗号："1500000" 写作 "1,500,000"
+    Thousands,
+}
+
+/// 日期的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateStyle {
+    /// "2024年3月5日"
+    Chinese,
+    /// "2024-03-05"
+    Iso,
+}
+
+/// 译文本地化的格式设置，由 `parse` 从区域名和可选的覆盖项得到
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocaleConfig {
+    pub grouping: NumberGrouping,
+    pub date_style: DateStyle,
+    /// 时间用 24 小时制（"15:30"），否则写作 "下午3:30"
+    pub hour24: bool,
+    /// 使用繁体的 "萬"、"億"
+    pub traditional: bool,
+}
+
+impl LocaleConfig {
+    /// 解析 "zh-CN"、"zh-TW" 等区域名，后面可跟逗号分隔的覆盖项：
+    /// `myriad`/`thousands`（大数写法）、`chinese-date`/`iso-date`（日期写法）、`24h`/`12h`（时间写法），
+    /// 如 "zh-CN,thousands,iso-date"
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let mut items = spec.split(',').map(str::trim);
+        let locale = items.next().unwrap_or_default();
+        let mut config = match locale.to_ascii_lowercase().replace('_', "-").as_str() {
+            "zh" | "zh-cn" | "zh-hans" | "zh-sg" => Self {
+                grouping: NumberGrouping::Myriad,
+                date_style: DateStyle::Chinese,
+                hour24: true,
+                traditional: false,
+            },
+            "zh-tw" | "zh-hk" | "zh-hant" => Self {
+                grouping: NumberGrouping::Myriad,
+                date_style: DateStyle::Chinese,
+                hour24: false,
+                traditional: true,
+            },
+            other => bail!("unsupported locale '{}', expected zh-CN or zh-TW", other),
+        };
+        for item in items.filter(|item| !item.is_empty()) {
+            match item {
+                "myriad" => config.grouping = NumberGrouping::Myriad,
+                "thousands" => config.grouping = NumberGrouping::Thousands,
+                "chinese-date" => config.date_style = DateStyle::Chinese,
+                "iso-date" => config.date_style = DateStyle::Iso,
+                "24h" => config.hour24 = true,
+                "12h" => config.hour24 = false,
+                other => bail!(
+                    "unknown localization option '{}', expected myriad, thousands, chinese-date, iso-date, 24h or 12h",
+                    other
+                ),
+            }
+        }
+        Ok(config)
+    }
+}
+
+const MONTHS: &str = "January|February|March|April|May|June|July|August|September|October|November|December\
+                      |Jan|Feb|Mar|Apr|Jun|Jul|Aug|Sept|Sep|Oct|Nov|Dec";
+
+/// 译文本地化：把译文中写法不一的日期、时间、百分数和大数统一为目标区域的习惯写法，
+/// 如 "March 5, 2024" 与 "3月5日，2024年" 都改为 "2024年3月5日"，"1.5 billion"、"1,500,000,000" 改为 "15亿"。
+///
+/// 只作用于译文，注册为 `Pipeline::with_translation_processor`。规则偏保守：
+/// 按万、亿改写时，只改写能以不超过两位小数精确表示的数，其余保持原样。
+pub struct Localizer {
+    config: LocaleConfig,
+    date_mdy: Regex,
+    date_dmy: Regex,
+    date_numeric: Regex,
+    date_year_last: Regex,
+    date_chinese: Regex,
+    time_english: Regex,
+    time_chinese: Regex,
+    percent: Regex,
+    scaled: Regex,
+    number: Regex,
+}
+
+impl Localizer {
+    pub fn new(config: LocaleConfig) -> Self {
+        let regex = |pattern: &str| Regex::new(pattern).expect("invalid localization pattern");
+        Self {
+            config,
+            date_mdy: regex(&format!(r"({})\.?\s+(\d{{1,2}})(?:st|nd|rd|th)?(?:,?\s*(\d{{4}}))?", MONTHS)),
+            date_dmy: regex(&format!(r"(\d{{1,2}})(?:st|nd|rd|th)?\s+({})\.?(?:,?\s*(\d{{4}}))?", MONTHS)),
+            date_numeric: regex(r"(\d{1,2})/(\d{1,2})/(\d{4})"),
+            date_year_last: regex(r"(\d{1,2})月(\d{1,2})日[，,]\s*(\d{4})年?"),
+            date_chinese: regex(r"(\d{4})年(\d{1,2})月(\d{1,2})日"),
+            time_english: regex(r"(\d{1,2})(?::(\d{2}))?\s*([AaPp])\.?\s?[Mm](?-u:\b)\.?"),
+            time_chinese: regex(r"(凌晨|早上|上午|中午|下午|晚上)\s*(\d{1,2}):(\d{2})"),
+            percent: regex(r"(\d+(?:\.\d+)?)\s*(?:%|(?i:percent|per cent)(?-u:\b))"),
+            scaled: regex(r"(\d+(?:\.\d+)?)\s*(?:((?i:trillion|billion|million|thousand))(?-u:\b)|(千万|百万|十亿))"),
+            number: regex(r"\d+(?:,\d{3})*(?:\.\d+)?"),
+        }
+    }
+
+    /// 月日年统一为目标写法
+    fn format_date(&self, year: Option<&str>, month: u32, day: u32) -> Option<String> {
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        Some(match (self.config.date_style, year) {
+            (DateStyle::Chinese, Some(year)) => format!("{}年{}月{}日", year, month, day),
+            (DateStyle::Iso, Some(year)) => format!("{}-{:02}-{:02}", year, month, day),
+            (_, None) => format!("{}月{}日", month, day),
+        })
+    }
+
+    fn localize_dates(&self, text: &str) -> String {
+        let text = replace_bounded(&self.date_mdy, text, |caps| {
+            let day = caps[2].parse().ok()?;
+            self.format_date(caps.get(3).map(|m| m.as_str()), month_number(&caps[1])?, day)
+        });
+        let text = replace_bounded(&self.date_dmy, &text, |caps| {
+            let day = caps[1].parse().ok()?;
+            self.format_date(caps.get(3).map(|m| m.as_str()), month_number(&caps[2])?, day)
+        });
+        // 原文为英文，数字日期按美式的 月/日/年 理解
+        let text = replace_bounded(&self.date_numeric, &text, |caps| {
+            self.format_date(Some(&caps[3]), caps[1].parse().ok()?, caps[2].parse().ok()?)
+        });
+        let text = replace_bounded(&self.date_year_last, &text, |caps| {
+            self.format_date(Some(&caps[3]), caps[1].parse().ok()?, caps[2].parse().ok()?)
+        });
+        if self.config.date_style == DateStyle::Chinese {
+            return text;
+        }
+        replace_bounded(&self.date_chinese, &text, |caps| {
+            self.format_date(Some(&caps[1]), caps[2].parse().ok()?, caps[3].parse().ok()?)
+        })
+    }
+
+    /// 24 小时制的钟点统一为目标写法
+    fn format_time(&self, hour: u32, minute: u32) -> Option<String> {
+        if hour > 23 || minute > 59 {
+            return None;
+        }
+        if self.config.hour24 {
+            return Some(format!("{}:{:02}", hour, minute));
+        }
+        let period = match hour {
+            0..=5 => "凌晨",
+            6..=11 => "上午",
+            12 => "中午",
+            13..=18 => "下午",
+            _ => "晚上",
+        };
+        let hour = if hour > 12 { hour - 12 } else { hour };
+        Some(format!("{}{}:{:02}", period, hour, minute))
+    }
+
+    fn localize_times(&self, text: &str) -> String {
+        let text = replace_bounded(&self.time_english, text, |caps| {
+            let hour: u32 = caps[1].parse().ok()?;
+            let minute = caps.get(2).map_or(Some(0), |m| m.as_str().parse().ok())?;
+            if !(1..=12).contains(&hour) {
+                return None;
+            }
+            let pm = caps[3].eq_ignore_ascii_case("p");
+            self.format_time(hour % 12 + if pm { 12 } else { 0 }, minute)
+        });
+        if !self.config.hour24 {
+            return text;
+        }
+        replace_bounded(&self.time_chinese, &text, |caps| {
+            let hour: u32 = caps[2].parse().ok()?;
+            let minute = caps[3].parse().ok()?;
+            let hour = match (&caps[1], hour) {
+                ("下午" | "晚上", 1..=11) => hour + 12,
+                ("凌晨" | "早上" | "上午", 12) => 0,
+                _ => hour,
+            };
+            self.format_time(hour, minute)
+        })
+    }
+
+    fn localize_numbers(&self, text: &str) -> String {
+        let text = self.percent.replace_all(text, "$1%");
+        // "1.5 billion"、"150百万" 之类带量级词的数，换算后按目标写法输出
+        let text = replace_bounded(&self.scaled, &text, |caps| {
+            let value: f64 = caps[1].parse().ok()?;
+            let scale = match caps.get(2).or(caps.get(3))?.as_str().to_ascii_lowercase().as_str() {
+                "thousand" => 1e3,
+                "million" | "百万" => 1e6,
+                "千万" => 1e7,
+                "billion" | "十亿" => 1e9,
+                "trillion" => 1e12,
+                _ => return None,
+            };
+            let value = value * scale;
+            Some(match self.config.grouping {
+                // 说话人给出的本就是约数，按万、亿换算后最多保留四位小数
+                NumberGrouping::Myriad => self.format_myriad(value, 4),
+                NumberGrouping::Thousands => format_thousands(&trim_decimal(value, 2)),
+            })
+        });
+        replace_bounded(&self.number, &text, |caps| {
+            let raw = &caps[0];
+            let digits = raw.replace(',', "");
+            let integer_len = digits.split('.').next().map_or(0, str::len);
+            // 四位以内的数（包括年份）不改写
+            if integer_len < 5 {
+                return None;
+            }
+            match self.config.grouping {
+                NumberGrouping::Myriad => {
+                    let value: f64 = digits.parse().ok()?;
+                    let formatted = self.format_myriad(value, 2);
+                    // 不能精确表示时保持原样
+                    let exact = formatted.trim_end_matches(['万', '萬', '亿', '億']).parse::<f64>().ok()?;
+                    let unit = myriad_unit(value);
+                    ((exact * unit - value).abs() < 1e-6 * unit).then_some(formatted)
+                }
+                NumberGrouping::Thousands => Some(format_thousands(&digits)),
+            }
+        })
+    }
+
+    /// 按万、亿写出 `value`，小数最多保留 `decimals` 位
+    fn format_myriad(&self, value: f64, decimals: usize) -> String {
+        let unit = myriad_unit(value);
+        let name = match (unit as u64, self.config.traditional) {
+            (1_000_000_000_000, false) => "万亿",
+            (1_000_000_000_000, true) => "萬億",
+            (100_000_000, false) => "亿",
+            (100_000_000, true) => "億",
+            (10_000, false) => "万",
+            (10_000, true) => "萬",
+            _ => "",
+        };
+        format!("{}{}", trim_decimal(value / unit, decimals), name)
+    }
+}
+
+impl TextPostProcessor for Localizer {
+    fn process(&self, text: &str) -> String {
+        let text = self.localize_dates(text);
+        let text = self.localize_times(&text);
+        self.localize_numbers(&text)
+    }
+}
+
+/// 按万、亿改写 `value` 时使用的单位（1、1e4、1e8 或 1e12）
+fn myriad_unit(value: f64) -> f64 {
+    if value >= 1e12 {
+        1e12
+    } else if value >= 1e8 {
+        1e8
+    } else if value >= 1e4 {
+        1e4
+    } else {
+        1.0
+    }
+}
+
+/// 英文月份名（或缩写）对应的月份
+fn month_number(name: &str) -> Option<u32> {
+    let index = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ]
+    .iter()
+    .position(|prefix| name.to_ascii_lowercase().starts_with(prefix))?;
+    Some(index as u32 + 1)
+}
+
+/// 保留至多 `decimals` 位小数，去掉末尾的 0
+fn trim_decimal(value: f64, decimals: usize) -> String {
+    let formatted = format!("{:.*}", decimals, value);
+    if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        formatted
+    }
+}
+
+/// 给整数部分每三位加逗号，`digits` 不含逗号
+fn format_thousands(digits: &str) -> String {
+    let (integer, fraction) = digits.split_once('.').map_or((digits, None), |(i, f)| (i, Some(f)));
+    let mut grouped = String::with_capacity(integer.len() + integer.len() / 3 + 4);
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push('.');
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+/// 与 `Regex::replace_all` 相同，但只替换前后不紧挨 ASCII 字母、数字或小数点等的匹配，
+/// `replace` 返回 `None` 时保留原文。正则的 `\b` 在数字与汉字之间不成立，不能用来判断数的边界
+fn replace_bounded(regex: &Regex, text: &str, replace: impl Fn(&Captures) -> Option<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in regex.captures_iter(text) {
+        let m = caps.get(0).unwrap();
+        let before = text[..m.start()].chars().next_back();
+        let mut after = text[m.end()..].chars();
+        let attached_before = before.is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ',' | ':' | '/' | '-'));
+        // 后面的句号、逗号只有再接数字时才算连在一起（如 "1.5"），否则是句子的标点
+        let attached_after = match after.next() {
+            Some('.' | ',') => after.next().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_alphanumeric() || matches!(c, ':' | '/' | '-'),
+            None => false,
+        };
+        if attached_before || attached_after {
+            continue;
+        }
+        if let Some(replacement) = replace(&caps) {
+            result.push_str(&text[last..m.start()]);
+            result.push_str(&replacement);
+            last = m.end();
+        }
+    }
+    result.push_str(&text[last..]);
+    result
+}
@@ -1,33 +1,141 @@
 use std::path::Path;
-use std::time::Duration;
-use std::thread;
 use std::io;
-use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
-use log::{info, error, Level, LevelFilter};
+use log::{info, warn, error, LevelFilter};
 use fern::Dispatch;
 use std::fs::File;
 use chrono::Local;
 use std::panic;
+use anyhow::Context;
+
+mod cli;
+use cli::{Cli, Command};
+
+mod benchmark;
 
 mod download_model;
 use download_model::download_file;
+#[cfg(feature = "translate")]
+use download_model::hub_file;
+
+mod model_select;
+
+mod adaptive;
+use adaptive::AdaptiveModel;
+use model_select::{select_whisper_model, DEFAULT_WHISPER_MODEL};
+
+mod ring;
 
 mod capture;
-use capture::AudioCapture;
+use capture::CaptureConfig;
 
 mod transcribe;
-use transcribe::Whisper;
+use transcribe::{is_supported_language, supported_languages, Whisper, WhisperConfig};
+
+mod translation;
+#[cfg(feature = "translate")]
+use translation::{Translate, TranslationRouter};
 
+#[cfg(feature = "translate")]
 mod translate;
-use translate::Translator;
+#[cfg(feature = "translate")]
+use translate::{TranslateConfig, Translator};
 
 mod display;
 use display::ScrollingDisplay;
 
-fn setup_logging(log_to_file: bool) {
-    // 全局设置为 Debug，保证 debug 日志也能通过
-    let base_dispatch = Dispatch::new()
-        .level(LevelFilter::Debug)
+mod sink;
+use sink::{CaptionSink, JsonLinesSink, LogSink, RoutedSink, TranscriptSink};
+
+mod session;
+
+mod mixer;
+use mixer::MixSource;
+
+mod stdin_source;
+
+mod speaker;
+
+#[cfg(feature = "pinyin")]
+mod romanize;
+
+mod normalize;
+use normalize::EnglishNormalizer;
+
+mod sentence;
+mod sentence_buffer;
+
+mod throttle;
+use throttle::TranslationThrottle;
+
+mod mask;
+use mask::WordMasker;
+
+#[cfg(feature = "translate")]
+mod glossary;
+#[cfg(feature = "translate")]
+use glossary::{Glossary, GlossaryTranslator};
+
+#[cfg(feature = "translate")]
+mod localize;
+#[cfg(feature = "translate")]
+use localize::{LocaleConfig, Localizer};
+
+#[cfg(feature = "translate")]
+mod code_protect;
+#[cfg(feature = "translate")]
+use code_protect::CodeProtectTranslator;
+
+mod chunk_dump;
+mod chunk_trace;
+use chunk_trace::TRACE_TARGET;
+use chunk_dump::{ChunkDumper, DEFAULT_MAX_FILES};
+
+mod streaming;
+use streaming::SlidingWindowConfig;
+
+mod pipeline;
+use pipeline::{Pipeline, PipelineCommand, CAPTIONS_TARGET};
+
+#[cfg(feature = "healthz")]
+mod healthz;
+#[cfg(feature = "serve")]
+mod serve;
+#[cfg(feature = "metrics")]
+mod metrics;
+
+#[cfg(feature = "tui")]
+mod tui;
+
+/// 日志输出配置
+struct LogConfig {
+    /// 终端显示的最低日志级别
+    stdout_level: LevelFilter,
+    /// 终端日志改写到标准错误，标准输出只留给字幕（如 `--json`）
+    stderr: bool,
+    /// 诊断日志文件（记录 Debug 及以上级别），`None` 表示不写文件
+    app_log: Option<&'static str>,
+    /// 仅记录最终字幕（时间戳 + 原文 + 译文）的文件，`None` 表示不写
+    captions_log: Option<&'static str>,
+    /// 逐块决策记录（每块一行 JSON）的文件，`None` 表示不记录
+    chunk_trace: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            stdout_level: LevelFilter::Info,
+            stderr: false,
+            app_log: Some("app.log"),
+            captions_log: Some("captions.log"),
+            chunk_trace: None,
+        }
+    }
+}
+
+fn setup_logging(config: &LogConfig) -> anyhow::Result<()> {
+    // 诊断日志：排除字幕专用的 target，带时间戳、target 和级别
+    let mut diagnostics = Dispatch::new()
+        .filter(|metadata| metadata.target() != CAPTIONS_TARGET && metadata.target() != TRACE_TARGET)
         .format(|out, message, record| {
             let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
             out.finish(format_args!(
@@ -38,26 +146,51 @@ fn setup_logging(log_to_file: bool) {
                 message
             ))
         });
-    
-    // 创建终端输出：只显示 Info 及以上的日志
-    let stdout_dispatch = Dispatch::new()
-        .filter(|metadata| metadata.level() >= Level::Info)
-        .chain(io::stdout());
-    
-    // 如果需要输出到文件，则配置文件日志（记录 Debug 及以上级别日志）
-    if log_to_file {
-        let file_dispatch = Dispatch::new()
-            .chain(File::create("app.log").unwrap());
-        
-        // 合并终端和文件输出
-        base_dispatch
-            .chain(stdout_dispatch)
-            .chain(file_dispatch)
-            .apply()
-            .unwrap();
+
+    // 创建终端输出：只显示 `stdout_level` 及以上的日志
+    let stdout_level = config.stdout_level;
+    let terminal = Dispatch::new().filter(move |metadata| metadata.level() <= stdout_level);
+    diagnostics = diagnostics.chain(if config.stderr {
+        terminal.chain(io::stderr())
     } else {
-        base_dispatch.chain(stdout_dispatch).apply().unwrap();
+        terminal.chain(io::stdout())
+    });
+
+    // 如果需要输出到文件，则配置文件日志（记录 Debug 及以上级别日志）
+    if let Some(path) = config.app_log {
+        diagnostics = diagnostics.chain(
+            Dispatch::new()
+                .level(LevelFilter::Debug)
+                .chain(File::create(path).with_context(|| format!("failed to create log file {}", path))?),
+        );
+    }
+
+    // 全局设置为 Debug，保证 debug 日志也能通过；逐块记录为 trace 级别，只在需要时放行
+    let mut root = Dispatch::new().level(LevelFilter::Debug).chain(diagnostics);
+    if let Some(path) = &config.chunk_trace {
+        root = root.level_for(TRACE_TARGET, LevelFilter::Trace).chain(
+            Dispatch::new()
+                .filter(|metadata| metadata.target() == TRACE_TARGET)
+                .format(|out, message, _| out.finish(format_args!("{}", message)))
+                .chain(File::create(path).with_context(|| format!("failed to create chunk trace {}", path))?),
+        );
+    }
+
+    // 字幕日志：只接收字幕 target，不带级别等额外信息，便于会后直接阅读
+    if let Some(path) = config.captions_log {
+        root = root.chain(
+            Dispatch::new()
+                .filter(|metadata| metadata.target() == CAPTIONS_TARGET)
+                .format(|out, message, _| {
+                    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+                    out.finish(format_args!("[{}] {}", timestamp, message))
+                })
+                .chain(File::create(path).with_context(|| format!("failed to create captions log {}", path))?),
+        );
     }
+
+    root.apply().context("failed to install the logger")?;
+    Ok(())
 }
 
 /// 确保模型文件存在，如果不存在则下载
@@ -68,72 +201,600 @@ fn ensure_model_exists(model_path: &str, download_url: &str) {
     } 
 }
 
+/// 根据命令行参数创建字幕输出端及其名称（可在运行中按名称移除）。
+/// `session_details` 写入文稿的会话头，记录所用的模型与配置
+fn build_sinks(cli: &Cli, session_details: &str) -> Vec<(&'static str, Box<dyn CaptionSink>)> {
+    let mut sinks: Vec<(&'static str, Box<dyn CaptionSink>)> = vec![("log", Box::new(LogSink))];
+    // JSON 输出占用标准输出，此时不再使用终端显示；全屏界面在流水线建好后再加入
+    if cli.json {
+        sinks.push(("json", Box::new(JsonLinesSink)));
+    } else if !use_tui(cli) {
+        let mut markers: std::collections::HashMap<_, _> = cli.markers.iter().cloned().collect();
+        // 显示端默认隐藏方括号标记，低置信度占位符（如 "[unclear]"）须照常显示，除非用 `--marker` 另行指定
+        if cli.unclear_below.is_some() {
+            let placeholder = cli.unclear_placeholder.as_deref().unwrap_or(DEFAULT_UNCLEAR_PLACEHOLDER);
+            if !markers.keys().any(|marker: &String| marker.eq_ignore_ascii_case(placeholder)) {
+                markers.insert(placeholder.to_string(), Some(placeholder.to_string()));
+            }
+        }
+        let mut display = ScrollingDisplay::new()
+            .with_markers(markers)
+            .with_content(cli.display_content)
+            .with_confidence_coloring(cli.confidence_colors);
+        // 未翻译的低置信度字幕同样以暗色显示
+        if let Some(threshold) = cli.min_translate_confidence {
+            display = display.with_low_confidence(threshold);
+        }
+        if let Some(secs) = cli.clear_after {
+            display = display.with_clear_after(std::time::Duration::from_secs_f64(secs));
+        }
+        sinks.push(("display", Box::new(display)));
+    }
+    if let Some(path) = &cli.transcript {
+        match TranscriptSink::create(path, cli.resume, session_details) {
+            Ok(sink) => sinks.push(("transcript", Box::new(sink))),
+            Err(e) => error!("Failed to open transcript {}: {}", path, e),
+        }
+    }
+    if let Some(addr) = &cli.websocket {
+        #[cfg(feature = "websocket")]
+        match sink::WebSocketSink::bind(addr) {
+            Ok(sink) => sinks.push(("websocket", Box::new(sink))),
+            Err(e) => error!("Failed to start WebSocket sink on {}: {}", addr, e),
+        }
+        #[cfg(not(feature = "websocket"))]
+        error!("--websocket {} requires building with the `websocket` feature", addr);
+    }
+    if let Some(addr) = &cli.osc {
+        #[cfg(feature = "osc")]
+        match sink::OscSink::connect(addr) {
+            Ok(sink) => sinks.push(("osc", Box::new(sink))),
+            Err(e) => error!("Failed to start OSC sink to {}: {}", addr, e),
+        }
+        #[cfg(not(feature = "osc"))]
+        error!("--osc {} requires building with the `osc` feature", addr);
+    }
+    if let Some(path) = &cli.fifo {
+        #[cfg(unix)]
+        match sink::FifoSink::open(path, &cli.fifo_format) {
+            Ok(sink) => sinks.push(("fifo", Box::new(sink))),
+            Err(e) => error!("Failed to start FIFO sink on {}: {}", path, e),
+        }
+        #[cfg(not(unix))]
+        error!("--fifo {} is only supported on Unix", path);
+    }
+    for name in &cli.final_only {
+        match sinks.iter().position(|(existing, _)| existing == name) {
+            Some(index) => {
+                let (name, sink) = sinks.remove(index);
+                sinks.insert(index, (name, Box::new(RoutedSink::new(sink, false, true))));
+            }
+            None => warn!("--final-only {}: no such caption sink", name),
+        }
+    }
+    sinks
+}
+
+/// `--unclear-below` 未指定 `--unclear-placeholder` 时的占位文本
+const DEFAULT_UNCLEAR_PLACEHOLDER: &str = "[unclear]";
+
+/// 是否使用全屏终端界面：需要 `tui` feature，且不与 JSON 输出同时使用
+fn use_tui(cli: &Cli) -> bool {
+    cli.tui && !cli.json && cfg!(feature = "tui")
+}
+
+/// 解析 "设备名=增益;设备名=增益" 形式的混音配置，省略增益时取 1.0；增益写错时报错而不是悄悄按 1.0 处理
+fn parse_mix_sources(spec: &str) -> anyhow::Result<Vec<MixSource>> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|item| match item.rsplit_once('=') {
+            Some((device, gain)) => Ok(MixSource {
+                device: device.trim().to_string(),
+                gain: gain
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|gain: &f32| gain.is_finite() && *gain >= 0.0)
+                    .with_context(|| format!("invalid gain {:?} for mix device {:?}", gain.trim(), device.trim()))?,
+            }),
+            None => Ok(MixSource {
+                device: item.to_string(),
+                gain: 1.0,
+            }),
+        })
+        .collect()
+}
+
+/// 从标准输入读取控制命令并转发给流水线。目前支持：
+/// `switch <设备名>` 切换采集设备，`sinks`/`remove-sink <名称>` 管理输出端，
+/// `pause`/`resume` 暂停、恢复流水线
+fn spawn_stdin_control(control: crossbeam_channel::Sender<PipelineCommand>) {
+    std::thread::spawn(move || {
+        for line in io::stdin().lines().map_while(Result::ok) {
+            let line = line.trim();
+            let command = if let Some(name) = line.strip_prefix("switch ") {
+                PipelineCommand::SwitchDevice(name.trim().to_string())
+            } else if let Some(name) = line.strip_prefix("remove-sink ") {
+                PipelineCommand::RemoveSink(name.trim().to_string())
+            } else if line == "sinks" {
+                PipelineCommand::ListSinks
+            } else if line == "pause" {
+                PipelineCommand::Pause
+            } else if line == "resume" {
+                PipelineCommand::Resume
+            } else {
+                if !line.is_empty() {
+                    error!("Unknown command: {}", line);
+                }
+                continue;
+            };
+            if control.send(command).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// 确保英译中模型存在并加载翻译器，失败时退出。`model_dir` 默认为 `models`
+#[cfg(feature = "translate")]
+fn load_translator(model_dir: Option<&str>, tokenizer_repo: Option<&str>) -> Translator {
+    let model_dir = Path::new(model_dir.unwrap_or("models"));
+    let translator_model_path = model_dir.join("model.safetensors");
+    let translator_download_url = "https://huggingface.co/Helsinki-NLP/opus-mt-en-zh/resolve/refs%2Fpr%2F26/model.safetensors";
+    ensure_model_exists(&translator_model_path.to_string_lossy(), translator_download_url);
+    load_marian(model_dir, "en", "zh", tokenizer_repo)
+}
+
+/// 加载 `model_dir` 中的 Marian 模型，失败时退出。分词器文件按 `tokenizer-marian-base-<语言>.json` 命名，
+/// `source`/`target` 为源语言与目标语言代码。给出 `tokenizer_repo`（Hugging Face 仓库 id）时从该仓库下载分词器
+/// 并缓存在 `models/hf` 下，否则使用 `model_dir` 中的文件
+#[cfg(feature = "translate")]
+fn load_marian(model_dir: &Path, source: &str, target: &str, tokenizer_repo: Option<&str>) -> Translator {
+    let model_path = model_dir.join("model.safetensors");
+    let tokenizer = |language: &str| {
+        let file = format!("tokenizer-marian-base-{}.json", language);
+        let Some(repo) = tokenizer_repo else {
+            return model_dir.join(file);
+        };
+        match hub_file(repo, &file, Path::new("models/hf")) {
+            Ok(path) => path,
+            Err(e) => {
+                error!("{:#}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+    let source_tokenizer = tokenizer(source);
+    let target_tokenizer = tokenizer(target);
+    match Translator::new(
+        &model_path.to_string_lossy(),
+        &source_tokenizer.to_string_lossy(),
+        &target_tokenizer.to_string_lossy(),
+    ) {
+        Ok(translator) => translator,
+        Err(e) => {
+            error!("Failed to load {}->{} translator: {:#}", source, target, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 加载翻译器并应用 `--top-p`、`--min-translate-chars` 等参数。给出中译英模型时，按转录检测到的语言在两个方向之间分派；给出术语表时在外面套一层术语处理
+#[cfg(feature = "translate")]
+fn build_translator(cli: &Cli) -> Box<dyn Translate> {
+    let defaults = TranslateConfig::default();
+    let config = TranslateConfig {
+        top_p: cli.top_p,
+        min_chars: cli.min_translate_chars.unwrap_or(defaults.min_chars),
+        ..defaults
+    };
+    // 两个方向的分词器不同，各自从自己的仓库下载
+    let en_zh =
+        load_translator(cli.translate_model.as_deref(), cli.tokenizer_repo.as_deref()).with_config(config.clone());
+    let zh_en_tokenizer_repo = cli.zh_en_tokenizer_repo.as_deref();
+    let translator: Box<dyn Translate> = match &cli.zh_en_model {
+        Some(dir) => Box::new(
+            TranslationRouter::new()
+                .with_route("en", Box::new(en_zh))
+                .with_route("zh", Box::new(load_marian(Path::new(dir), "zh", "en", zh_en_tokenizer_repo).with_config(config))),
+        ),
+        None => {
+            if let Some(repo) = zh_en_tokenizer_repo {
+                warn!("--zh-en-tokenizer-repo {} has no effect without --zh-en-model", repo);
+            }
+            Box::new(en_zh)
+        }
+    };
+    let translator: Box<dyn Translate> = match cli.glossary.as_deref().map(Glossary::load) {
+        Some(Ok(glossary)) => Box::new(GlossaryTranslator::new(translator, glossary)),
+        Some(Err(e)) => {
+            error!("{:#}", e);
+            translator
+        }
+        None => translator,
+    };
+    if !cli.protect_code {
+        return translator;
+    }
+    // 放在最外层，代码片段不会被术语表改动
+    match CodeProtectTranslator::new(translator, &cli.protect_patterns) {
+        Ok(translator) => Box::new(translator),
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// 加载 Whisper 模型，并应用配置文件和命令行中与转录相关的选项（命令行/环境变量优先）
+fn load_whisper(model_path: &str, cli: &Cli) -> Whisper {
+    let mut config = match &cli.whisper_config {
+        Some(path) => match WhisperConfig::load(path) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("{:#}", e);
+                std::process::exit(2);
+            }
+        },
+        None => WhisperConfig::default(),
+    };
+    if let Some(language) = &cli.language {
+        config.language = language.clone();
+    }
+    if let Some(secs) = cli.chunk_timeout {
+        config.timeout_secs = Some(secs);
+    }
+    if cli.word_confidence {
+        config.word_timestamps = true;
+    }
+    if let Some(max_len) = cli.max_segment_len {
+        config.max_len = Some(max_len);
+    }
+    if cli.split_on_word {
+        config.split_on_word = true;
+    }
+    if !is_supported_language(&config.language) {
+        error!(
+            "Unsupported transcription language '{}'; run with --list-languages to see valid codes",
+            config.language
+        );
+        std::process::exit(2);
+    }
+    if cli.zh_en_model.is_some() && config.language != "auto" {
+        warn!(
+            "--zh-en-model routes by detected language; use --language auto (current: {})",
+            config.language
+        );
+    }
+    let whisper = match Whisper::load(model_path, &config) {
+        Ok(whisper) => whisper,
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if !whisper.is_multilingual() && config.language != "en" {
+        warn!(
+            "Whisper model {} is English-only; language '{}' will not be transcribed correctly",
+            model_path, config.language
+        );
+    }
+    match cli.edge_fade_ms {
+        Some(fade_ms) => whisper.with_edge_fade_ms(fade_ms),
+        None => whisper,
+    }
+}
+
 fn main() {
     panic::set_hook(Box::new(|panic_info| {
         error!("Panic occurred: {:?}", panic_info);
     }));
-    setup_logging(true);
-    
-    // 确保 Whisper 模型存在
-    let whisper_model_path = "models/ggml-base-q5_1.bin";
-    let whisper_download_url = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base-q5_1.bin";
-    ensure_model_exists(whisper_model_path, whisper_download_url);
-
-    // 确保翻译模型存在
-    let translator_model_path = "models/model.safetensors";
-    let translator_download_url = "https://huggingface.co/Helsinki-NLP/opus-mt-en-zh/resolve/refs%2Fpr%2F26/model.safetensors";
-    ensure_model_exists(translator_model_path, translator_download_url);
+    // 先解析命令行以决定日志配置，解析错误在日志就绪后再报告
+    let cli = Cli::from_env();
+    let mut log_config = LogConfig::default();
+    if cli.as_ref().is_ok_and(use_tui) {
+        // 全屏界面占用终端，日志只写文件
+        log_config.stdout_level = LevelFilter::Off;
+    }
+    if let Ok(cli) = &cli {
+        // JSON 字幕写在标准输出上，日志混进去会破坏管道下游的解析
+        log_config.stderr = cli.json;
+        log_config.chunk_trace = cli.trace_chunks.clone();
+    }
+    if let Err(e) = setup_logging(&log_config) {
+        // 日志尚未就绪，直接写到标准错误
+        eprintln!("{:#}", e);
+        std::process::exit(2);
+    }
 
-    info!("Loading Whisper model...");
-    // 直接初始化 Whisper 实例（后续只在子线程中使用，不需要 Arc/Mutex）
-    let mut whisper = Whisper::new(whisper_model_path);
-    info!("Whisper model loaded.");
+    let cli = match cli {
+        Ok(cli) => cli,
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(2);
+        }
+    };
+    #[cfg(not(feature = "tui"))]
+    if cli.tui {
+        error!("--tui requires building with the `tui` feature");
+    }
+    #[cfg(not(feature = "translate"))]
+    if let Some(path) = &cli.glossary {
+        error!("--glossary {} requires building with the `translate` feature", path);
+    }
+    #[cfg(not(feature = "translate"))]
+    if cli.protect_code {
+        let flag = if cli.protect_patterns.is_empty() { "--protect-code" } else { "--protect-pattern" };
+        error!("{} requires building with the `translate` feature", flag);
+    }
+    #[cfg(not(feature = "translate"))]
+    if let Some(repo) = &cli.tokenizer_repo {
+        error!("--tokenizer-repo {} requires building with the `translate` feature", repo);
+    }
+    #[cfg(not(feature = "translate"))]
+    if let Some(repo) = &cli.zh_en_tokenizer_repo {
+        error!("--zh-en-tokenizer-repo {} requires building with the `translate` feature", repo);
+    }
+    #[cfg(not(feature = "translate"))]
+    if cli.top_p.is_some() {
+        error!("--top-p requires building with the `translate` feature");
+    }
+    #[cfg(not(feature = "translate"))]
+    if cli.min_translate_chars.is_some() {
+        error!("--min-translate-chars requires building with the `translate` feature");
+    }
 
-    // 初始化翻译器
-    let tokenizer_path_en = "models/tokenizer-marian-base-en.json";
-    let tokenizer_path_zh = "models/tokenizer-marian-base-zh.json";
-    let mut translator = Translator::new(translator_model_path, tokenizer_path_en, tokenizer_path_zh)
-        .expect("Failed to load translator model");
-    
-    // 创建音频数据传输的 channel
-    let (audio_sender, audio_receiver): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = unbounded();
-
-    // 将 Sender 传递给 capture 模块，采集到的数据会通过该 channel 发送
-    let _audio_capture = AudioCapture::new_stream_with_sender(audio_sender);
-
-    // 用于传递转录结果的 channel
-    let (result_sender, result_receiver): (Sender<String>, Receiver<String>) = unbounded();
-
-    // 启动一个线程：从 audio_receiver 中读取音频块，并同步进行转录处理
-    let transcribe_result_sender = result_sender.clone();
-    thread::spawn(move || {
-        while let Ok(chunk) = audio_receiver.recv() {
-            if let Some(text) = whisper.transcribe_samples(chunk) {
-                let _ = transcribe_result_sender.send(text.trim().to_string());
+    // 确保 Whisper 模型存在：指定了模型文件时直接使用，--auto-model 时按内存预算选择模型
+    // `manifest_model` 为从内置模型列表中选出的模型，指定模型文件时为 `None`
+    let (whisper_model_path, manifest_model) = match &cli.whisper_model {
+        Some(path) => {
+            if !Path::new(path).exists() {
+                error!("Whisper model not found: {}", path);
+                std::process::exit(2);
             }
+            (path.clone(), None)
+        }
+        None => {
+            let whisper_model = if cli.auto_model {
+                let with_translator = cfg!(feature = "translate")
+                    && !cli.transcribe_only
+                    && matches!(cli.command, Command::Run | Command::Serve { .. });
+                select_whisper_model(cli.memory_budget, with_translator)
+            } else {
+                DEFAULT_WHISPER_MODEL
+            };
+            ensure_model_exists(&whisper_model.path(), &whisper_model.download_url());
+            (whisper_model.path(), Some(whisper_model))
+        }
+    };
+    let whisper_model_path = &whisper_model_path;
+
+    if let Command::ListLanguages = cli.command {
+        // 不应用语言等配置，配置中的语言无效时也能列出
+        let whisper = Whisper::new(whisper_model_path);
+        for (code, name) in supported_languages() {
+            println!("{:<5} {}", code, name);
+        }
+        println!("auto  (detect per chunk)");
+        if whisper.is_multilingual() {
+            println!("Model {} is multilingual.", whisper_model_path);
+        } else {
+            println!("Model {} is English-only: use --language en.", whisper_model_path);
+        }
+        return;
+    }
+
+    if let Command::BenchmarkFile { wav, reference } = &cli.command {
+        let mut whisper = load_whisper(whisper_model_path, &cli);
+        match benchmark::run(&mut whisper, wav, reference) {
+            Ok(report) => report.print(),
+            Err(e) => {
+                error!("Benchmark failed: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Command::Serve { addr, workers } = &cli.command {
+        #[cfg(feature = "serve")]
+        {
+            let whisper = load_whisper(whisper_model_path, &cli);
+            #[cfg(feature = "translate")]
+            let translator = (!cli.transcribe_only).then(|| build_translator(&cli));
+            #[cfg(not(feature = "translate"))]
+            let translator = None;
+            if let Err(e) = serve::run(addr, whisper, translator, *workers) {
+                error!("{:#}", e);
+                std::process::exit(1);
+            }
+        }
+        #[cfg(not(feature = "serve"))]
+        error!("--serve {} ({} workers) requires building with the `serve` feature", addr, workers);
+        return;
+    }
+
+    info!("Loading Whisper model...");
+    // 加载 Whisper 实例；交给流水线后由转录线程与主循环经 `Arc<Mutex<_>>` 共用
+    let whisper = load_whisper(whisper_model_path, &cli);
+    info!("Whisper model loaded.");
+    let adaptive_model = cli.adaptive_model.and_then(|config| {
+        let adaptive = manifest_model.and_then(|model| AdaptiveModel::new(model, whisper.config().clone(), config));
+        if adaptive.is_none() {
+            error!("--adaptive-model requires a built-in model and cannot be used with --whisper-model");
         }
+        adaptive
     });
+    let session_details = format!(
+        "whisper={} language={} translate={}",
+        whisper_model_path,
+        whisper.language(),
+        match (&cli.translate_model, cli.transcribe_only) {
+            (_, true) => "off",
+            (Some(dir), false) => dir,
+            (None, false) => "models",
+        }
+    );
 
-    // 初始化显示模块
-    let mut display = ScrollingDisplay::new();
-    
-    // 主线程：处理转录结果，并进行翻译
-    info!("Starting real-time transcription loop...");
-    loop {
-        while let Ok(text) = result_receiver.try_recv() {
-            let text = text.trim();
-            if text.is_empty() || text == "[BLANK_AUDIO]" || text == "[Music]" {
-                continue;
+    // --transcribe-only 时跳过翻译模型的下载与加载，启动更快
+    #[cfg(feature = "translate")]
+    let translator = (!cli.transcribe_only).then(|| build_translator(&cli));
+
+    // 启动采集与转录，主线程负责翻译与显示
+    let mut capture_config = CaptureConfig::default();
+    if let Some(quality) = cli.resample_quality {
+        capture_config.resample_quality = quality;
+    }
+    capture_config.buffer_frames = cli.buffer_frames;
+    if let Some(mix) = cli.mix {
+        capture_config.mix = mix;
+    }
+    if let Some(warmup_ms) = cli.warmup_ms {
+        capture_config.warmup_ms = warmup_ms;
+    }
+    capture_config.peak_normalize = cli.peak_normalize;
+    capture_config.realtime_priority = cli.realtime_priority;
+    capture_config.echo_suppression = cli.echo_suppression;
+    if let Some(secs) = cli.max_flush_interval {
+        capture_config.max_flush_interval = (secs > 0.0).then(|| std::time::Duration::from_secs_f64(secs));
+    }
+    let sliding_window = cli.sliding_window.map(|window_secs| {
+        let defaults = SlidingWindowConfig::default();
+        SlidingWindowConfig {
+            window_secs,
+            hop_secs: cli.hop.unwrap_or(defaults.hop_secs),
+            stability: cli.stability.unwrap_or(defaults.stability),
+            stable_timeout_secs: cli.stable_timeout.or(defaults.stable_timeout_secs),
+        }
+    });
+    if let Some(config) = sliding_window {
+        // 采集块长与 hop 一致，每个块到达时正好触发一次重新转录
+        capture_config.chunk_secs = config.hop_secs;
+    }
+    // 指定了 --stdin-format 时从标准输入读取音频；
+    // 指定了 --mix-devices/LAT_MIX_DEVICES（如 "Microphone=1.0;Speakers=0.8"）时同时采集多个设备并混音；
+    // 指定了 --device/LAT_DEVICE 时采集该设备，否则采集默认输出设备
+    let pipeline = match (cli.stdin_format, &cli.mix_devices, &cli.device) {
+        (Some(format), _, _) => Pipeline::from_stdin(whisper, format, capture_config),
+        (None, Some(spec), _) => {
+            parse_mix_sources(spec).and_then(|sources| Pipeline::with_mixed_sources(whisper, &sources, capture_config))
+        }
+        (None, None, Some(device)) => Pipeline::for_device(whisper, device, capture_config),
+        (None, None, None) => Pipeline::new(whisper, capture_config),
+    };
+    let mut pipeline = match pipeline {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            error!("Failed to start audio capture: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "translate")]
+    if let Some(translator) = translator {
+        pipeline = pipeline
+            .with_translator(translator)
+            .with_translation_cache("translation_cache.json");
+    }
+    for (name, sink) in build_sinks(&cli, &session_details) {
+        pipeline = pipeline.with_named_sink(name, sink);
+    }
+    if !cli.no_normalize {
+        pipeline = pipeline.with_post_processor(Box::new(EnglishNormalizer));
+    }
+    if let Some(spec) = &cli.localize {
+        #[cfg(feature = "translate")]
+        match LocaleConfig::parse(spec) {
+            Ok(config) => pipeline = pipeline.with_translation_processor(Box::new(Localizer::new(config))),
+            Err(e) => {
+                error!("{:#}", e);
+                std::process::exit(2);
             }
-            match translator.translate(&text) {
-                Ok(translated) => {
-                    if text.trim() != translated.trim() {
-                        display.add_text(text, &translated);
-                    }
-                }
-                Err(e) => eprintln!("Translation error: {:?}", e),
+        }
+        #[cfg(not(feature = "translate"))]
+        error!("--localize {} requires building with the `translate` feature", spec);
+    }
+    if let Some(path) = &cli.mask_words {
+        match WordMasker::load(path) {
+            Ok(masker) => {
+                let replacement = cli.mask_replacement.as_deref().unwrap_or(mask::DEFAULT_REPLACEMENT);
+                pipeline = pipeline.with_output_filter(Box::new(masker.with_replacement(replacement)));
             }
+            Err(e) => error!("{:#}", e),
+        }
+    }
+    if let Some(config) = sliding_window {
+        pipeline = pipeline.with_sliding_window(config).with_revisions(cli.revise_captions);
+    } else if cli.revise_captions {
+        warn!("--revise-captions only takes effect with --sliding-window");
+    }
+    if cli.pinyin {
+        #[cfg(feature = "pinyin")]
+        {
+            pipeline = pipeline.with_pinyin(true);
         }
-        thread::sleep(Duration::from_millis(50));
+        #[cfg(not(feature = "pinyin"))]
+        error!("--pinyin requires building with the `pinyin` feature");
     }
+    if let Some(dir) = &cli.dump_chunks {
+        match ChunkDumper::new(dir, cli.dump_chunks_max.unwrap_or(DEFAULT_MAX_FILES)) {
+            Ok(dumper) => pipeline = pipeline.with_chunk_dump(dumper),
+            Err(e) => error!("{:#}", e),
+        }
+    }
+    if let Some(adaptive) = adaptive_model {
+        pipeline = pipeline.with_adaptive_model(adaptive);
+    }
+    if let Some(threshold) = cli.recovery_threshold {
+        pipeline = pipeline.with_recovery_threshold(threshold);
+    }
+    if cli.fused_worker {
+        pipeline = pipeline.with_fused_worker(true);
+    }
+    if let Some(threshold) = cli.min_translate_confidence {
+        pipeline = pipeline.with_min_translate_confidence(threshold);
+    }
+    if let Some(config) = cli.translation_throttle {
+        pipeline = pipeline.with_translation_throttle(TranslationThrottle::new(config));
+    }
+    if let Some(threshold) = cli.unclear_below {
+        let placeholder = cli.unclear_placeholder.as_deref().unwrap_or(DEFAULT_UNCLEAR_PLACEHOLDER);
+        pipeline = pipeline.with_unclear_placeholder(threshold, placeholder, cli.keep_unclear_guess);
+    }
+    if let Some(secs) = cli.sentence_hold {
+        pipeline = pipeline.with_sentence_buffer(std::time::Duration::from_secs_f64(secs));
+    }
+    if let Some(secs) = cli.paragraph_silence {
+        pipeline = pipeline.with_paragraph_silence(std::time::Duration::from_secs_f64(secs));
+    }
+
+    #[cfg(feature = "healthz")]
+    {
+        let addr = std::env::var("LAT_HEALTHZ_ADDR").unwrap_or_else(|_| "127.0.0.1:8787".to_string());
+        if let Err(e) = healthz::spawn(&addr, pipeline.state_handle()) {
+            error!("Failed to start health check server: {:?}", e);
+        }
+    }
+
+    // Ctrl-C 时不直接退出，而是让流水线把最后一段音频处理完
+    let state = pipeline.state_handle();
+    if let Err(e) = ctrlc::set_handler(move || state.request_shutdown()) {
+        error!("Failed to install Ctrl-C handler: {}", e);
+    }
+
+    #[cfg(feature = "tui")]
+    if use_tui(&cli) {
+        match tui::TuiSink::start(pipeline.state_handle(), pipeline.control_handle(), cli.display_content) {
+            Ok(sink) => pipeline.add_sink("tui", Box::new(sink)),
+            Err(e) => error!("Failed to start terminal UI: {}", e),
+        }
+    }
+
+    // 运行中可在终端输入 "switch <设备名>" 切换采集设备，无需重启；
+    // 标准输入用作音频来源或使用全屏界面时不读取控制命令
+    if cli.stdin_format.is_none() && !use_tui(&cli) {
+        spawn_stdin_control(pipeline.control_handle());
+    }
+
+    pipeline.run();
 }
@@ -0,0 +1,84 @@
+use std::fs;
+use anyhow::Context;
+
+use crate::normalize::TextPostProcessor;
+
+/// 默认的替换文本
+pub const DEFAULT_REPLACEMENT: &str = "***";
+
+/// 把词表中的词替换为固定文本（默认 "***"），用于屏蔽不雅用语等。
+///
+/// 英文词（纯 ASCII）不区分大小写，只匹配完整的词，"ass" 不会命中 "class"；
+/// 中文等其他词按子串匹配，中文没有空格分词。
+pub struct WordMasker {
+    /// ASCII 词已转为小写
+    words: Vec<String>,
+    replacement: String,
+}
+
+impl WordMasker {
+    pub fn new(words: impl IntoIterator<Item = String>) -> Self {
+        let mut words: Vec<String> = words
+            .into_iter()
+            .map(|word| word.trim().to_ascii_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        // 先替换较长的词，避免短词把长词拆开（如 "bull" 与 "bullshit"）
+        words.sort_by_key(|word| std::cmp::Reverse(word.len()));
+        Self {
+            words,
+            replacement: DEFAULT_REPLACEMENT.to_string(),
+        }
+    }
+
+    /// 从词表文件加载，每行一个词（或词组），空行和 `#` 开头的行忽略
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("failed to read word list {}", path))?;
+        let words = content
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .map(str::to_string);
+        Ok(Self::new(words))
+    }
+
+    /// 设置替换文本
+    pub fn with_replacement(mut self, replacement: &str) -> Self {
+        self.replacement = replacement.to_string();
+        self
+    }
+}
+
+impl TextPostProcessor for WordMasker {
+    fn process(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for word in &self.words {
+            text = if word.is_ascii() {
+                mask_whole_word(&text, word, &self.replacement)
+            } else {
+                text.replace(word.as_str(), &self.replacement)
+            };
+        }
+        text
+    }
+}
+
+/// 不区分大小写地替换 `text` 中作为完整词出现的 `word`（小写 ASCII）
+pub fn mask_whole_word(text: &str, word: &str, replacement: &str) -> String {
+    // ASCII 小写转换不改变字节长度，下标可直接用于原文
+    let lower = text.to_ascii_lowercase();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut masked = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, _) in lower.match_indices(word) {
+        let end = start + word.len();
+        let bounded = text[..start].chars().next_back().is_none_or(|c| !is_word_char(c))
+            && text[end..].chars().next().is_none_or(|c| !is_word_char(c));
+        if bounded {
+            masked.push_str(&text[last..start]);
+            masked.push_str(replacement);
+            last = end;
+        }
+    }
+    masked.push_str(&text[last..]);
+    masked
+}
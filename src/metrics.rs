@@ -0,0 +1,53 @@
+use std::fmt::Write;
+
+use crate::pipeline::{LatencyHistogram, PipelineState, LATENCY_BUCKETS};
+
+/// 导出指标名称的统一前缀
+const PREFIX: &str = "live_audio_trans";
+
+/// 以 Prometheus 文本格式（0.0.4）输出流水线的运行指标，供 `GET /metrics` 返回
+pub fn render(state: &PipelineState) -> String {
+    let status = state.snapshot();
+    let mut out = String::new();
+    let counters = [
+        ("processed_chunks_total", "Audio chunks transcribed", status.processed_chunks),
+        ("dropped_chunks_total", "Audio chunks dropped after a failed or undeliverable transcription", status.dropped_chunks),
+        ("whisper_resets_total", "Whisper states recreated after suspicious output", status.whisper_resets),
+        ("whisper_reloads_total", "Whisper models reloaded after repeated failures", status.whisper_reloads),
+        ("translation_errors_total", "Translations that failed and were dropped", status.translation_errors),
+        ("truncated_translations_total", "Translations that hit max_new_tokens", status.truncated_translations),
+    ];
+    for (name, help, value) in counters {
+        write_metric(&mut out, name, "counter", help, value as f64);
+    }
+    let gauges = [
+        ("real_time_factor", "Transcription time divided by audio duration for the latest chunk", status.real_time_factor),
+        ("queue_depth", "Audio chunks waiting to be transcribed", status.queue_depth as f64),
+        ("gpu_active", "Whether Whisper is running on the GPU", f64::from(u8::from(status.gpu_active))),
+        ("translator_loaded", "Whether a translation model is loaded", f64::from(u8::from(status.translator_loaded))),
+    ];
+    for (name, help, value) in gauges {
+        write_metric(&mut out, name, "gauge", help, value);
+    }
+    write_histogram(&mut out, "transcribe_seconds", "Time spent transcribing one audio chunk", state.transcribe_latency());
+    write_histogram(&mut out, "translate_seconds", "Time spent translating one caption", state.translate_latency());
+    out
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+    let _ = writeln!(out, "{}_{} {}", PREFIX, name, value);
+}
+
+fn write_histogram(out: &mut String, name: &str, help: &str, histogram: &LatencyHistogram) {
+    let (buckets, count, sum) = histogram.cumulative();
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} histogram", PREFIX, name);
+    for (bound, cumulative) in LATENCY_BUCKETS.iter().zip(buckets) {
+        let _ = writeln!(out, "{}_{}_bucket{{le=\"{}\"}} {}", PREFIX, name, bound, cumulative);
+    }
+    let _ = writeln!(out, "{}_{}_bucket{{le=\"+Inf\"}} {}", PREFIX, name, count);
+    let _ = writeln!(out, "{}_{}_sum {}", PREFIX, name, sum);
+    let _ = writeln!(out, "{}_{}_count {}", PREFIX, name, count);
+}
@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crossbeam_channel::{unbounded, Receiver, Select, Sender};
+use log::{error, info, warn};
+
+use crate::capture::{AudioCapture, CaptureConfig};
+
+/// 各路音频之间允许的最大错位（秒）。
+/// 某一路超过该量仍未跟上时（例如回环设备在无声时不回调），用静音补齐。
+const MAX_SKEW_SECS: usize = 3;
+/// 回声抑制滤波器覆盖的时长（秒），即能抵消的扬声器到麦克风的最大延迟
+const ECHO_FILTER_SECS: f64 = 0.1;
+/// NLMS 步长，越大收敛越快，但越容易在有人说话时失调
+const NLMS_STEP: f32 = 0.1;
+/// 避免参考信号静音时除以零
+const NLMS_EPSILON: f32 = 1e-6;
+/// Geigel 双讲检测门限：麦克风幅度超过近期参考峰值的该比例时视为有人在说话，暂停自适应
+const DOUBLE_TALK_RATIO: f32 = 0.5;
+
+/// 混音器的一路输入
+#[derive(Debug, Clone)]
+pub struct MixSource {
+    /// 设备名称，输入设备（麦克风）与输出设备（回环）均可
+    pub device: String,
+    /// 该路音频的增益
+    pub gain: f32,
+}
+
+/// 同时采集多个设备，并把它们重采样后的单声道音频按时间对齐后混合成一路。
+pub struct AudioMixer {
+    captures: Vec<AudioCapture>,
+    gains: Arc<Mutex<Vec<f32>>>,
+    /// 混音线程，`stop` 时等它把各路尾段混合发送完毕
+    mixer: Option<JoinHandle<()>>,
+}
+
+impl AudioMixer {
+    /// 为每个 `MixSource` 建立采集流，混合后的音频块通过 `audio_sender` 发送出去。
+    pub fn new(
+        sources: &[MixSource],
+        audio_sender: Sender<Vec<f32>>,
+        config: CaptureConfig,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(!sources.is_empty(), "mixer needs at least one source");
+
+        // 各路采集共用同一份配置，附加输出会收到几路交错的音频，因此混音时不支持
+        let mut config = config;
+        if config.tap.take().is_some() {
+            warn!("The audio tap is not supported when mixing several sources, disabled");
+        }
+        let mut captures = Vec::with_capacity(sources.len());
+        let mut receivers = Vec::with_capacity(sources.len());
+        for source in sources {
+            let (sender, receiver) = unbounded();
+            captures.push(AudioCapture::new_stream_for_device(&source.device, sender, config.clone())?);
+            receivers.push(receiver);
+        }
+        let gains = Arc::new(Mutex::new(sources.iter().map(|s| s.gain).collect::<Vec<_>>()));
+        info!("Mixing {} sources: {:?}", sources.len(), sources);
+
+        let echo = config.echo_suppression.and_then(|strength| {
+            let loopback: Vec<bool> = sources
+                .iter()
+                .map(|source| AudioCapture::is_loopback_device(&source.device))
+                .collect();
+            if !loopback.contains(&true) || !loopback.contains(&false) {
+                warn!("Echo suppression needs both a microphone and a loopback source, disabled");
+                return None;
+            }
+            info!("Echo suppression enabled (strength {:.2})", strength);
+            let taps = (config.target_sample_rate as f64 * ECHO_FILTER_SECS) as usize;
+            Some(EchoSuppressor::new(loopback, taps, strength))
+        });
+
+        let mixer_gains = Arc::clone(&gains);
+        let max_skew = config.target_sample_rate as usize * MAX_SKEW_SECS;
+        let chunk_samples = ((config.target_sample_rate as f64 * config.chunk_secs) as usize).max(1);
+        let mixer = thread::spawn(move || mix_loop(receivers, mixer_gains, audio_sender, max_skew, chunk_samples, echo));
+
+        Ok(Self {
+            captures,
+            gains,
+            mixer: Some(mixer),
+        })
+    }
+
+    /// 调整第 `index` 路输入的增益
+    #[allow(dead_code)]
+    pub fn set_gain(&self, index: usize, gain: f32) {
+        if let Some(g) = self.gains.lock().unwrap().get_mut(index) {
+            *g = gain;
+        }
+    }
+
+    /// 停止所有输入，各路的尾段音频混合后发送出去，返回时已全部送出
+    pub fn stop(&mut self) {
+        // 先让各路同时截止，再逐一等待其处理线程发送完尾段
+        for capture in self.captures.iter_mut() {
+            capture.request_stop();
+        }
+        for capture in self.captures.iter_mut() {
+            capture.stop();
+        }
+        // 丢弃采集对象，关闭各路 channel，混音线程据此输出剩余数据并退出
+        self.captures.clear();
+        if let Some(mixer) = self.mixer.take() {
+            if mixer.join().is_err() {
+                error!("Mixer thread panicked");
+            }
+        }
+    }
+
+    /// 暂停所有输入
+    pub fn pause(&self) {
+        for capture in &self.captures {
+            capture.pause();
+        }
+    }
+
+    /// 恢复所有输入
+    pub fn resume(&self) {
+        for capture in &self.captures {
+            capture.resume();
+        }
+    }
+
+    /// 各路输入中最大的采集延迟
+    pub fn latency(&self) -> Option<Duration> {
+        self.captures.iter().filter_map(AudioCapture::latency).max()
+    }
+
+    /// 参与混音的设备名称，以 " + " 连接
+    pub fn device_name(&self) -> String {
+        self.captures
+            .iter()
+            .map(|c| c.device_name())
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+}
+
+/// 混音线程：按来源分别缓冲，凑齐对齐的一段后加权求和并发送
+fn mix_loop(
+    receivers: Vec<Receiver<Vec<f32>>>,
+    gains: Arc<Mutex<Vec<f32>>>,
+    audio_sender: Sender<Vec<f32>>,
+    max_skew: usize,
+    chunk_samples: usize,
+    mut echo: Option<EchoSuppressor>,
+) {
+    let mut buffers: Vec<VecDeque<f32>> = vec![VecDeque::new(); receivers.len()];
+    // 已结束（channel 关闭）的来源，之后视为静音
+    let mut finished = vec![false; receivers.len()];
+    let mut select = Select::new();
+    for receiver in &receivers {
+        select.recv(receiver);
+    }
+
+    loop {
+        let op = select.select();
+        let index = op.index();
+        match op.recv(&receivers[index]) {
+            Ok(chunk) => buffers[index].extend(chunk),
+            Err(_) => {
+                // 其他来源可能还在发送尾段，继续混合，直到所有来源都结束
+                info!("Mixer source {} finished", index);
+                select.remove(index);
+                finished[index] = true;
+            }
+        }
+        let disconnected = finished.iter().all(|&f| f);
+
+        let longest = buffers.iter().map(|b| b.len()).max().unwrap_or(0);
+        for (buffer, &done) in buffers.iter_mut().zip(&finished) {
+            // 落后太多或已结束的来源视为静音，避免整条流水线被拖住
+            if longest > max_skew || done {
+                buffer.resize(longest, 0.0);
+            }
+        }
+
+        // 各来源的块边界互相错开，按对齐的长度直接输出会产生很短的碎块；
+        // 攒够一个采集块（chunk_secs）再输出，全部来源结束时把剩下的尾段一起输出
+        loop {
+            let ready = buffers.iter().map(|b| b.len()).min().unwrap_or(0);
+            let take = if ready >= chunk_samples {
+                chunk_samples
+            } else if disconnected && ready > 0 {
+                ready
+            } else {
+                break;
+            };
+
+            let mut chunks: Vec<Vec<f32>> = buffers.iter_mut().map(|b| b.drain(..take).collect()).collect();
+            if let Some(echo) = echo.as_mut() {
+                echo.process(&mut chunks);
+            }
+
+            let gains = gains.lock().unwrap().clone();
+            let mut mixed = vec![0.0f32; take];
+            for (chunk, gain) in chunks.iter().zip(gains) {
+                for (out, sample) in mixed.iter_mut().zip(chunk) {
+                    *out += sample * gain;
+                }
+            }
+            normalize_peak(&mut mixed);
+
+            if audio_sender.send(mixed).is_err() {
+                return;
+            }
+        }
+        if disconnected {
+            return;
+        }
+    }
+}
+
+/// 求和后若峰值超过 1.0，则整体缩放回 [-1.0, 1.0]，避免削波
+fn normalize_peak(samples: &mut [f32]) {
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if peak > 1.0 {
+        for s in samples.iter_mut() {
+            *s /= peak;
+        }
+    }
+}
+
+/// 混音前的回声抑制：把各路回环信号之和作为参考，从每一路麦克风中减去估计出的扬声器声音。
+/// 各路按到达的采样数对齐，设备间的固定延迟由自适应滤波器吸收
+struct EchoSuppressor {
+    /// 各路是否为回环来源
+    loopback: Vec<bool>,
+    /// 每一路麦克风各自的滤波器，回环来源为 `None`
+    cancellers: Vec<Option<EchoCanceller>>,
+}
+
+impl EchoSuppressor {
+    fn new(loopback: Vec<bool>, taps: usize, strength: f32) -> Self {
+        let cancellers = loopback
+            .iter()
+            .map(|&is_loopback| (!is_loopback).then(|| EchoCanceller::new(taps, strength)))
+            .collect();
+        Self { loopback, cancellers }
+    }
+
+    /// `chunks` 为各路对齐后等长的一段音频，原地处理麦克风各路
+    fn process(&mut self, chunks: &mut [Vec<f32>]) {
+        let len = chunks.first().map_or(0, Vec::len);
+        let mut reference = vec![0.0f32; len];
+        for (chunk, _) in chunks.iter().zip(&self.loopback).filter(|(_, &is_loopback)| is_loopback) {
+            for (r, sample) in reference.iter_mut().zip(chunk) {
+                *r += sample;
+            }
+        }
+        for (chunk, canceller) in chunks.iter_mut().zip(&mut self.cancellers) {
+            if let Some(canceller) = canceller {
+                canceller.process(chunk, &reference);
+            }
+        }
+    }
+}
+
+/// 归一化 LMS 自适应滤波器：用最近一段参考信号估计它在麦克风中的回声，
+/// 按 `strength` 比例减去。检测到有人说话（双讲）时暂停自适应，避免把人声当成回声学掉
+struct EchoCanceller {
+    weights: Vec<f32>,
+    /// 最近的参考样本，`history[0]` 为最新
+    history: VecDeque<f32>,
+    /// `history` 的能量，用于归一化步长
+    energy: f32,
+    strength: f32,
+}
+
+impl EchoCanceller {
+    fn new(taps: usize, strength: f32) -> Self {
+        let taps = taps.max(1);
+        Self {
+            weights: vec![0.0; taps],
+            history: VecDeque::from(vec![0.0; taps]),
+            energy: 0.0,
+            strength: strength.clamp(0.0, 1.0),
+        }
+    }
+
+    fn process(&mut self, mic: &mut [f32], reference: &[f32]) {
+        for (sample, &r) in mic.iter_mut().zip(reference) {
+            if let Some(oldest) = self.history.pop_back() {
+                self.energy -= oldest * oldest;
+            }
+            self.history.push_front(r);
+            // 浮点累计误差可能使能量略小于零
+            self.energy = (self.energy + r * r).max(0.0);
+
+            let mut estimate = 0.0;
+            let mut reference_peak = 0.0f32;
+            for (w, x) in self.weights.iter().zip(&self.history) {
+                estimate += w * x;
+                reference_peak = reference_peak.max(x.abs());
+            }
+            let error = *sample - estimate;
+            if sample.abs() < DOUBLE_TALK_RATIO * reference_peak {
+                let step = NLMS_STEP * error / (self.energy + NLMS_EPSILON);
+                for (w, x) in self.weights.iter_mut().zip(&self.history) {
+                    *w += step * x;
+                }
+            }
+            *sample -= self.strength * estimate;
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use log::info;
+use sysinfo::System;
+
+/// 可自动选择的 Whisper 模型，按从小到大排列
+pub const WHISPER_MODELS: &[WhisperModel] = &[
+    WhisperModel { name: "tiny", file: "ggml-tiny-q5_1.bin", memory_mb: 150 },
+    WhisperModel { name: "base", file: "ggml-base-q5_1.bin", memory_mb: 250 },
+    WhisperModel { name: "small", file: "ggml-small-q5_1.bin", memory_mb: 600 },
+    WhisperModel { name: "medium", file: "ggml-medium-q5_0.bin", memory_mb: 1500 },
+];
+
+/// 未开启自动选择时使用的模型
+pub const DEFAULT_WHISPER_MODEL: &WhisperModel = &WHISPER_MODELS[1];
+
+/// 加载翻译模型（Marian）预留的内存
+const TRANSLATOR_MEMORY_MB: u64 = 600;
+
+/// 自动选择时只使用可用内存的这一比例，给系统和其他程序留出余量
+const AVAILABLE_MEMORY_FRACTION: f64 = 0.5;
+
+/// 一个 Whisper 模型文件及其大致的运行内存占用
+#[derive(Debug)]
+pub struct WhisperModel {
+    pub name: &'static str,
+    pub file: &'static str,
+    /// 加载后转录时的大致内存占用（MB），含模型权重与推理缓冲区
+    pub memory_mb: u64,
+}
+
+impl WhisperModel {
+    pub fn path(&self) -> String {
+        format!("models/{}", self.file)
+    }
+
+    pub fn download_url(&self) -> String {
+        format!("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/{}", self.file)
+    }
+}
+
+/// 在内存预算内选出最大的 Whisper 模型，一个都放不下时退回最小的模型。
+///
+/// `budget_mb` 为整个程序可用的内存（MB），未给出时取当前可用内存的一半；
+/// `with_translator` 时先为翻译模型预留内存。sysinfo 只能查询系统内存，
+/// 使用 CUDA 时请用 `budget_mb` 按显存大小指定预算。
+pub fn select_whisper_model(budget_mb: Option<u64>, with_translator: bool) -> &'static WhisperModel {
+    let (budget_mb, reason) = match budget_mb {
+        Some(budget_mb) => (budget_mb, "--memory-budget"),
+        None => {
+            let mut system = System::new();
+            system.refresh_memory();
+            let available_mb = system.available_memory() / 1024 / 1024;
+            (
+                (available_mb as f64 * AVAILABLE_MEMORY_FRACTION) as u64,
+                "half of available memory",
+            )
+        }
+    };
+    let reserved_mb = if with_translator { TRANSLATOR_MEMORY_MB } else { 0 };
+    let whisper_budget_mb = budget_mb.saturating_sub(reserved_mb);
+    let model = WHISPER_MODELS
+        .iter()
+        .rev()
+        .find(|model| model.memory_mb <= whisper_budget_mb)
+        .unwrap_or(&WHISPER_MODELS[0]);
+    info!(
+        "Selected Whisper model '{}' (~{} MB): budget {} MB ({}), {} MB reserved for the translator, {} MB left for Whisper",
+        model.name, model.memory_mb, budget_mb, reason, reserved_mb, whisper_budget_mb
+    );
+    model
+}
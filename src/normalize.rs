@@ -0,0 +1,269 @@
+/// 翻译前对转录文本做的后处理，可在流水线上按需注册或移除
+pub trait TextPostProcessor: Send {
+    fn process(&self, text: &str) -> String;
+}
+
+/// 英文口语数字规范化：把 "twenty twenty four"、"five percent"、"ten dollars"
+/// 之类的说法改写为 "2024"、"5%"、"$10"，使中文译文更自然。
+///
+/// 规则刻意保守：单个数字词（如 "one of them"）保持原样，
+/// 除非后面紧跟百分号或货币单位；句中大写的数字词视为专有名词，不做改写。
+pub struct EnglishNormalizer;
+
+impl TextPostProcessor for EnglishNormalizer {
+    fn process(&self, text: &str) -> String {
+        normalize_numbers(text)
+    }
+}
+
+/// 一个待处理的词：`word` 为去掉尾部标点后的小写形式
+struct Token<'a> {
+    raw: &'a str,
+    word: String,
+    trailing: &'a str,
+    capitalized: bool,
+}
+
+impl<'a> Token<'a> {
+    fn new(raw: &'a str) -> Self {
+        let core = raw.trim_end_matches(['.', ',', '!', '?', ';', ':']);
+        Self {
+            raw,
+            word: core.to_lowercase(),
+            trailing: &raw[core.len()..],
+            capitalized: core.starts_with(|c: char| c.is_uppercase()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NumberWord {
+    Unit(u64),
+    Teen(u64),
+    Tens(u64),
+    Hundred,
+    Scale(u64),
+}
+
+fn number_word(word: &str) -> Option<NumberWord> {
+    use NumberWord::*;
+    Some(match word {
+        "zero" => Unit(0),
+        "one" => Unit(1),
+        "two" => Unit(2),
+        "three" => Unit(3),
+        "four" => Unit(4),
+        "five" => Unit(5),
+        "six" => Unit(6),
+        "seven" => Unit(7),
+        "eight" => Unit(8),
+        "nine" => Unit(9),
+        "ten" => Teen(10),
+        "eleven" => Teen(11),
+        "twelve" => Teen(12),
+        "thirteen" => Teen(13),
+        "fourteen" => Teen(14),
+        "fifteen" => Teen(15),
+        "sixteen" => Teen(16),
+        "seventeen" => Teen(17),
+        "eighteen" => Teen(18),
+        "nineteen" => Teen(19),
+        "twenty" => Tens(20),
+        "thirty" => Tens(30),
+        "forty" => Tens(40),
+        "fifty" => Tens(50),
+        "sixty" => Tens(60),
+        "seventy" => Tens(70),
+        "eighty" => Tens(80),
+        "ninety" => Tens(90),
+        "hundred" => Hundred,
+        "thousand" => Scale(1_000),
+        "million" => Scale(1_000_000),
+        "billion" => Scale(1_000_000_000),
+        _ => return None,
+    })
+}
+
+/// 数字后面可以合并的单位
+enum Unit {
+    Percent,
+    Currency(&'static str),
+}
+
+fn unit_word(word: &str) -> Option<Unit> {
+    Some(match word {
+        "percent" => Unit::Percent,
+        "dollar" | "dollars" => Unit::Currency("$"),
+        "euro" | "euros" => Unit::Currency("€"),
+        _ => return None,
+    })
+}
+
+/// 从开头解析出的一个基数词
+struct Cardinal {
+    value: u64,
+    words: usize,
+    /// 不含 hundred/thousand 等量级词，可作为年份的前后两半
+    simple: bool,
+}
+
+fn normalize_numbers(text: &str) -> String {
+    let tokens = tokenize(text);
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        if let Some((value, consumed)) = parse_number(&tokens[i..]) {
+            let last = &tokens[i + consumed - 1];
+            let unit = match tokens.get(i + consumed) {
+                Some(next) if last.trailing.is_empty() => unit_word(&next.word).map(|u| (u, next)),
+                _ => None,
+            };
+            if let Some((unit, next)) = unit {
+                out.push(format_with_unit(&value, unit, next.trailing));
+                i += consumed + 1;
+                continue;
+            }
+            if consumed >= 2 {
+                out.push(format!("{}{}", value, last.trailing));
+                i += consumed;
+                continue;
+            }
+        }
+
+        // 已是阿拉伯数字的情况，只合并单位，如 "5 percent" -> "5%"
+        let token = &tokens[i];
+        if token.trailing.is_empty() && token.word.parse::<f64>().is_ok() {
+            if let Some(next) = tokens.get(i + 1) {
+                if let Some(unit) = unit_word(&next.word) {
+                    out.push(format_with_unit(&token.word, unit, next.trailing));
+                    i += 2;
+                    continue;
+                }
+            }
+        }
+
+        out.push(token.raw.to_string());
+        i += 1;
+    }
+    out.join(" ")
+}
+
+/// 按空白切分；"twenty-four" 这类连字符数字拆成多个词
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for raw in text.split_whitespace() {
+        let parts: Vec<&str> = raw.split('-').collect();
+        let all_numbers = parts.len() > 1
+            && parts
+                .iter()
+                .all(|p| number_word(&Token::new(p).word).is_some());
+        if all_numbers {
+            tokens.extend(parts.into_iter().map(Token::new));
+        } else {
+            tokens.push(Token::new(raw));
+        }
+    }
+    tokens
+}
+
+/// 解析一个数字短语，返回其阿拉伯数字写法和消耗的词数。
+/// 除普通基数词外，还识别 "twenty twenty four" 式的年份与 "three point five" 式的小数。
+fn parse_number(tokens: &[Token]) -> Option<(String, usize)> {
+    let first = parse_cardinal(tokens)?;
+    let mut consumed = first.words;
+    let mut value = first.value.to_string();
+    let ended = !tokens[consumed - 1].trailing.is_empty();
+
+    if !ended && first.simple && matches!(first.value, 19 | 20) {
+        if let Some(second) = parse_cardinal(&tokens[consumed..]) {
+            if second.simple && (10..=99).contains(&second.value) {
+                value = format!("{}{}", first.value, second.value);
+                consumed += second.words;
+            }
+        }
+    } else if !ended && tokens.get(consumed).is_some_and(|t| t.word == "point") {
+        let digits: String = tokens[consumed + 1..]
+            .iter()
+            .map_while(|t| match number_word(&t.word) {
+                Some(NumberWord::Unit(d)) if !t.capitalized => Some((d, t.trailing.is_empty())),
+                _ => None,
+            })
+            .scan(true, |open, (d, no_trailing)| {
+                // 遇到标点后停止
+                if !*open {
+                    return None;
+                }
+                *open = no_trailing;
+                Some(char::from(b'0' + d as u8))
+            })
+            .collect();
+        if !digits.is_empty() {
+            consumed += 1 + digits.len();
+            value = format!("{}.{}", value, digits);
+        }
+    }
+    Some((value, consumed))
+}
+
+fn parse_cardinal(tokens: &[Token]) -> Option<Cardinal> {
+    use NumberWord::*;
+    let mut total = 0u64;
+    let mut current = 0u64;
+    let mut last: Option<NumberWord> = None;
+    let mut last_scale = u64::MAX;
+    let mut simple = true;
+    let mut words = 0;
+
+    for token in tokens {
+        // 句中大写的数字词多半是专有名词（如乐队名），不参与解析
+        if words > 0 && token.capitalized {
+            break;
+        }
+        let Some(number) = number_word(&token.word) else {
+            break;
+        };
+        let allowed = match number {
+            Unit(_) => matches!(last, None | Some(Tens(_)) | Some(Hundred) | Some(Scale(_))),
+            Teen(_) | Tens(_) => matches!(last, None | Some(Hundred) | Some(Scale(_))),
+            Hundred => matches!(last, Some(Unit(_)) | Some(Teen(_))) && current > 0,
+            Scale(scale) => {
+                matches!(last, Some(Unit(_)) | Some(Teen(_)) | Some(Tens(_)) | Some(Hundred))
+                    && scale < last_scale
+            }
+        };
+        if !allowed {
+            break;
+        }
+        match number {
+            Unit(v) | Teen(v) | Tens(v) => current += v,
+            Hundred => {
+                current *= 100;
+                simple = false;
+            }
+            Scale(scale) => {
+                total += current * scale;
+                current = 0;
+                last_scale = scale;
+                simple = false;
+            }
+        }
+        words += 1;
+        last = Some(number);
+        if !token.trailing.is_empty() {
+            break;
+        }
+    }
+
+    (words > 0).then_some(Cardinal {
+        value: total + current,
+        words,
+        simple,
+    })
+}
+
+fn format_with_unit(value: &str, unit: Unit, trailing: &str) -> String {
+    match unit {
+        Unit::Percent => format!("{}%{}", value, trailing),
+        Unit::Currency(symbol) => format!("{}{}{}", symbol, value, trailing),
+    }
+}
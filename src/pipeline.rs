@@ -0,0 +1,1691 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use crossbeam_channel::{bounded, select, unbounded, Receiver, Select, Sender};
+use log::{info, debug, warn, error};
+use serde::Serialize;
+
+use crate::adaptive::AdaptiveModel;
+use crate::chunk_dump::ChunkDumper;
+use crate::chunk_trace::ChunkTrace;
+use crate::capture::{audio_resample, downmix_with, rms, AudioCapture, CaptureConfig};
+use crate::mixer::{AudioMixer, MixSource};
+use crate::normalize::TextPostProcessor;
+use crate::sentence_buffer::{Sentence, SentenceBuffer};
+use crate::sink::{Caption, CaptionEvent, CaptionSink, SegmentConfidence, WordConfidence};
+use crate::speaker::{NoSpeakerLabeler, SpeakerLabeler};
+use crate::stdin_source::{StdinFormat, StdinSource};
+use crate::throttle::TranslationThrottle;
+use crate::streaming::{SlidingWindowConfig, SlidingWindowTranscriber};
+use crate::transcribe::{RecoveryMonitor, Transcription, Whisper, MIN_TRANSCRIBE_SAMPLES};
+use crate::translation::{DecodeStats, Translate};
+
+/// 流水线的运行状态快照，可用于健康检查
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineStatus {
+    /// Whisper 模型是否已加载
+    pub whisper_loaded: bool,
+    /// 翻译模型是否已加载
+    pub translator_loaded: bool,
+    /// 当前采集设备名称
+    pub device_name: String,
+    /// Whisper 是否运行在 GPU（CUDA）上，GPU 加载失败退回 CPU 时为 `false`
+    pub gpu_active: bool,
+    /// 最近一个音频块的实时率（转录耗时 / 音频时长），小于 1 表示跟得上
+    pub real_time_factor: f64,
+    /// 最近一次输出字幕的时间（RFC 3339），尚未输出过则为 `None`
+    pub last_caption_at: Option<String>,
+    /// 已处理的音频块数量
+    pub processed_chunks: u64,
+    /// 被丢弃（转录失败或无法投递）的音频块数量
+    pub dropped_chunks: u64,
+    /// 检测到异常输出后自动重建 Whisper 状态的次数
+    pub whisper_resets: u64,
+    /// 连续转录失败后重新加载 Whisper 模型的次数
+    pub whisper_reloads: u64,
+    /// 最近一次模型翻译的解码步数
+    pub last_decode_steps: u64,
+    /// 解码达到 `max_new_tokens` 上限、可能被截断的翻译次数
+    pub truncated_translations: u64,
+    /// 翻译出错、字幕被丢弃的次数
+    pub translation_errors: u64,
+    /// 采集设备报告的延迟（毫秒），设备不提供时为 `None`
+    pub device_latency_ms: Option<f64>,
+    /// 从声音被采集到字幕输出的估计延迟（毫秒）：设备延迟 + 音频块时长 + 转录耗时 + 翻译耗时，
+    /// 均取最近一次的值。尚未处理过音频时为 `None`。给视频配字幕时可据此平移时间轴
+    pub latency_ms: Option<f64>,
+    /// 当前启用的字幕输出端名称
+    pub sinks: Vec<String>,
+    /// 等待转录的音频块数量，持续增长说明转录跟不上
+    pub queue_depth: u64,
+}
+
+/// 延迟直方图各桶的上界（秒），按 Prometheus 的 `le` 语义累计
+pub const LATENCY_BUCKETS: [f64; 10] = [0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// 无锁的延迟直方图，用于导出转录、翻译耗时的分布
+#[derive(Default)]
+pub struct LatencyHistogram {
+    /// 落在各桶内的次数（不累计），最后一项为超出所有上界的次数
+    counts: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_us: AtomicU64,
+}
+
+impl LatencyHistogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let index = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| secs <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[index].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// 各桶上界对应的累计次数（与 `LATENCY_BUCKETS` 一一对应）、总次数和总耗时（秒）
+    #[allow(dead_code)]
+    pub fn cumulative(&self) -> (Vec<u64>, u64, f64) {
+        let mut total = 0;
+        let mut buckets = Vec::with_capacity(LATENCY_BUCKETS.len());
+        for (i, count) in self.counts.iter().enumerate() {
+            total += count.load(Ordering::Relaxed);
+            if i < LATENCY_BUCKETS.len() {
+                buckets.push(total);
+            }
+        }
+        let sum = self.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        (buckets, total, sum)
+    }
+}
+
+/// 各线程共享的运行统计，由转录线程和主循环更新
+pub struct PipelineState {
+    device_name: Mutex<String>,
+    whisper_loaded: bool,
+    translator_loaded: AtomicBool,
+    /// f64 的位模式，避免为一个数值加锁
+    real_time_factor: AtomicU64,
+    processed_chunks: AtomicU64,
+    dropped_chunks: AtomicU64,
+    last_caption_at: Mutex<Option<DateTime<Local>>>,
+    last_decode_steps: AtomicU64,
+    truncated_translations: AtomicU64,
+    /// 转录线程是否正在处理音频块
+    busy: AtomicBool,
+    /// 收到退出请求（如 Ctrl-C）
+    shutdown: AtomicBool,
+    /// 连续静音超过该时长（毫秒）时插入分段标记，0 表示不分段
+    paragraph_silence_ms: AtomicU64,
+    /// 设置后转录线程改用滑动窗口流式转录
+    sliding_window: Mutex<Option<SlidingWindowConfig>>,
+    /// 连续多少次异常转录后重建 Whisper 状态，0 表示不检测
+    recovery_threshold: AtomicU64,
+    /// 已自动重建 Whisper 状态的次数
+    whisper_resets: AtomicU64,
+    /// 已重新加载 Whisper 模型的次数
+    whisper_reloads: AtomicU64,
+    /// 当前的 Whisper 模型是否运行在 GPU 上
+    gpu_active: AtomicBool,
+    /// 以下延迟均以微秒计；设备延迟为 `u64::MAX` 表示未知
+    device_latency_us: AtomicU64,
+    last_chunk_us: AtomicU64,
+    last_transcribe_us: AtomicU64,
+    last_translate_us: AtomicU64,
+    /// 当前启用的字幕输出端名称，随 `add_sink`/`remove_sink` 更新
+    sink_names: Mutex<Vec<String>>,
+    /// 设置后把每个送入转录的音频块写成 WAV 文件
+    chunk_dump: Mutex<Option<ChunkDumper>>,
+    /// 最近一次检查时等待转录的音频块数量
+    queue_depth: AtomicU64,
+    translation_errors: AtomicU64,
+    transcribe_latency: LatencyHistogram,
+    translate_latency: LatencyHistogram,
+}
+
+impl PipelineState {
+    fn new(device_name: String, gpu_active: bool) -> Self {
+        Self {
+            device_name: Mutex::new(device_name),
+            whisper_loaded: true,
+            translator_loaded: AtomicBool::new(false),
+            real_time_factor: AtomicU64::new(0f64.to_bits()),
+            processed_chunks: AtomicU64::new(0),
+            dropped_chunks: AtomicU64::new(0),
+            last_caption_at: Mutex::new(None),
+            last_decode_steps: AtomicU64::new(0),
+            truncated_translations: AtomicU64::new(0),
+            busy: AtomicBool::new(false),
+            shutdown: AtomicBool::new(false),
+            paragraph_silence_ms: AtomicU64::new(0),
+            sliding_window: Mutex::new(None),
+            recovery_threshold: AtomicU64::new(DEFAULT_RECOVERY_THRESHOLD),
+            whisper_resets: AtomicU64::new(0),
+            whisper_reloads: AtomicU64::new(0),
+            gpu_active: AtomicBool::new(gpu_active),
+            device_latency_us: AtomicU64::new(u64::MAX),
+            last_chunk_us: AtomicU64::new(0),
+            last_transcribe_us: AtomicU64::new(0),
+            last_translate_us: AtomicU64::new(0),
+            sink_names: Mutex::new(Vec::new()),
+            chunk_dump: Mutex::new(None),
+            queue_depth: AtomicU64::new(0),
+            translation_errors: AtomicU64::new(0),
+            transcribe_latency: LatencyHistogram::default(),
+            translate_latency: LatencyHistogram::default(),
+        }
+    }
+
+    /// 启用调试转储时，把送入转录的音频块写成 WAV 文件
+    fn dump_chunk(&self, chunk: &[f32]) {
+        if let Some(dumper) = self.chunk_dump.lock().unwrap().as_mut() {
+            dumper.dump(chunk);
+        }
+    }
+
+    /// 记录一个音频块的转录耗时
+    fn record_chunk(&self, audio: Duration, elapsed: Duration) {
+        if !audio.is_zero() {
+            let rtf = elapsed.as_secs_f64() / audio.as_secs_f64();
+            self.real_time_factor.store(rtf.to_bits(), Ordering::Relaxed);
+        }
+        self.last_chunk_us.store(audio.as_micros() as u64, Ordering::Relaxed);
+        self.last_transcribe_us.store(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.processed_chunks.fetch_add(1, Ordering::Relaxed);
+        self.transcribe_latency.observe(elapsed);
+    }
+
+    fn record_dropped(&self) {
+        self.dropped_chunks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次模型翻译的解码统计
+    fn record_decode(&self, stats: DecodeStats) {
+        self.last_decode_steps.store(stats.steps as u64, Ordering::Relaxed);
+        if stats.hit_limit {
+            self.truncated_translations.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_translate(&self, elapsed: Duration) {
+        self.last_translate_us.store(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.translate_latency.observe(elapsed);
+    }
+
+    /// 转录耗时的分布
+    #[allow(dead_code)]
+    pub fn transcribe_latency(&self) -> &LatencyHistogram {
+        &self.transcribe_latency
+    }
+
+    /// 翻译耗时的分布
+    #[allow(dead_code)]
+    pub fn translate_latency(&self) -> &LatencyHistogram {
+        &self.translate_latency
+    }
+
+    fn set_device_latency(&self, latency: Option<Duration>) {
+        let us = latency.map_or(u64::MAX, |l| l.as_micros() as u64);
+        self.device_latency_us.store(us, Ordering::Relaxed);
+    }
+
+    /// 设备延迟（毫秒）与估计的端到端延迟（毫秒）
+    fn latency_ms(&self) -> (Option<f64>, Option<f64>) {
+        let device = match self.device_latency_us.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            us => Some(us as f64 / 1000.0),
+        };
+        if self.processed_chunks.load(Ordering::Relaxed) == 0 {
+            return (device, None);
+        }
+        let processing_us = self.last_chunk_us.load(Ordering::Relaxed)
+            + self.last_transcribe_us.load(Ordering::Relaxed)
+            + self.last_translate_us.load(Ordering::Relaxed);
+        (device, Some(device.unwrap_or(0.0) + processing_us as f64 / 1000.0))
+    }
+
+    fn record_caption(&self) {
+        *self.last_caption_at.lock().unwrap() = Some(Local::now());
+    }
+
+    fn paragraph_silence(&self) -> Option<Duration> {
+        match self.paragraph_silence_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// 请求流水线退出；`Pipeline::run` 会在处理完剩余音频后返回
+    pub fn request_shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// 生成当前状态的快照
+    pub fn snapshot(&self) -> PipelineStatus {
+        let (device_latency_ms, latency_ms) = self.latency_ms();
+        PipelineStatus {
+            whisper_loaded: self.whisper_loaded,
+            translator_loaded: self.translator_loaded.load(Ordering::Relaxed),
+            device_name: self.device_name.lock().unwrap().clone(),
+            gpu_active: self.gpu_active.load(Ordering::Relaxed),
+            real_time_factor: f64::from_bits(self.real_time_factor.load(Ordering::Relaxed)),
+            last_caption_at: self
+                .last_caption_at
+                .lock()
+                .unwrap()
+                .map(|t| t.to_rfc3339()),
+            processed_chunks: self.processed_chunks.load(Ordering::Relaxed),
+            dropped_chunks: self.dropped_chunks.load(Ordering::Relaxed),
+            whisper_resets: self.whisper_resets.load(Ordering::Relaxed),
+            whisper_reloads: self.whisper_reloads.load(Ordering::Relaxed),
+            last_decode_steps: self.last_decode_steps.load(Ordering::Relaxed),
+            truncated_translations: self.truncated_translations.load(Ordering::Relaxed),
+            translation_errors: self.translation_errors.load(Ordering::Relaxed),
+            device_latency_ms,
+            latency_ms,
+            sinks: self.sink_names.lock().unwrap().clone(),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 流水线的音频来源：单个设备、多个设备混音、标准输入中的原始 PCM，
+/// 或由调用方通过 `process_samples` 提供
+enum AudioInput {
+    Device(AudioCapture),
+    Mixer(AudioMixer),
+    Stdin(StdinSource),
+    External,
+}
+
+impl AudioInput {
+    fn pause(&self) {
+        match self {
+            AudioInput::Device(capture) => capture.pause(),
+            AudioInput::Mixer(mixer) => mixer.pause(),
+            AudioInput::Stdin(_) | AudioInput::External => {}
+        }
+    }
+
+    fn resume(&self) {
+        match self {
+            AudioInput::Device(capture) => capture.resume(),
+            AudioInput::Mixer(mixer) => mixer.resume(),
+            AudioInput::Stdin(_) | AudioInput::External => {}
+        }
+    }
+
+    fn stop(&mut self) {
+        match self {
+            AudioInput::Device(capture) => capture.stop(),
+            AudioInput::Mixer(mixer) => mixer.stop(),
+            // 读取线程阻塞在标准输入上，随进程退出即可
+            AudioInput::Stdin(_) | AudioInput::External => {}
+        }
+    }
+
+    fn device_name(&self) -> String {
+        match self {
+            AudioInput::Device(capture) => capture.device_name().to_string(),
+            AudioInput::Mixer(mixer) => mixer.device_name(),
+            AudioInput::Stdin(_) => "stdin".to_string(),
+            AudioInput::External => "external".to_string(),
+        }
+    }
+
+    /// 采集设备报告的延迟；非设备来源没有该值
+    fn latency(&self) -> Option<Duration> {
+        match self {
+            AudioInput::Device(capture) => capture.latency(),
+            AudioInput::Mixer(mixer) => mixer.latency(),
+            AudioInput::Stdin(_) | AudioInput::External => None,
+        }
+    }
+
+    /// 音频来源已经结束（目前只有标准输入会读到结尾）
+    fn is_finished(&self) -> bool {
+        match self {
+            AudioInput::Stdin(source) => source.is_finished(),
+            _ => false,
+        }
+    }
+}
+
+/// 转录线程交给主循环的结果
+enum WorkerResult {
+    /// 转录结果及其说话人编号，以及启用逐块记录时该块的记录
+    Transcribed(Transcription, Option<u32>, Option<Box<ChunkTrace>>),
+    /// 滑动窗口定稿的文本及其片段编号和说话人编号
+    WindowFinalized(u64, Transcription, Option<u32>),
+    /// 滑动窗口改正了之前定稿的片段（以片段编号指出）
+    Revised(u64, Transcription),
+    /// 滑动窗口中尚未稳定的尾部文本
+    Partial(String),
+    /// 连续静音超过设定时长
+    ParagraphBreak,
+}
+
+/// 说话人标注器及其音频时间轴，由转录线程与 `process_samples` 共用
+struct SpeakerTrack {
+    labeler: Box<dyn SpeakerLabeler>,
+    /// 已送入标注器的音频总时长
+    position: Duration,
+    /// 最近一次标注结果，滑动窗口定稿剩余文本时沿用
+    last: Option<u32>,
+}
+
+impl SpeakerTrack {
+    /// 标注下一个音频块，并把时间轴推进到块尾
+    fn label(&mut self, audio: &[f32]) -> Option<u32> {
+        let start = self.position;
+        let end = start + Duration::from_secs_f64(audio.len() as f64 / 16_000.0);
+        self.position = end;
+        self.last = self.labeler.label(audio, start, end);
+        self.last
+    }
+}
+
+/// 逐块转录：识别、异常检测与恢复、静音分段，结果交给主循环翻译和输出。
+/// 通常运行在独立的转录线程中；融合模式下由主循环直接调用
+struct ChunkWorker {
+    state: Arc<PipelineState>,
+    whisper: Arc<Mutex<Whisper>>,
+    speaker: Arc<Mutex<SpeakerTrack>>,
+    /// 自上次有语音以来累计的静音时长；`spoke` 保证两次分段之间至少有一句话
+    silence: Duration,
+    spoke: bool,
+    sliding: Option<SlidingWindowTranscriber>,
+    /// 滑动窗口模式下相邻结果本就会重复，只在分块模式下检测异常输出
+    monitor: Option<RecoveryMonitor>,
+    /// 连续转录失败的块数，达到 `FAILURES_BEFORE_RELOAD` 时重新加载模型
+    failures: usize,
+    /// 自动切换模型时由主循环送来的新模型，在处理下一个音频块之前换上
+    model_swap: Receiver<(Whisper, &'static str)>,
+}
+
+impl ChunkWorker {
+    fn new(
+        state: Arc<PipelineState>,
+        whisper: Arc<Mutex<Whisper>>,
+        speaker: Arc<Mutex<SpeakerTrack>>,
+        model_swap: Receiver<(Whisper, &'static str)>,
+    ) -> Self {
+        Self {
+            state,
+            whisper,
+            speaker,
+            silence: Duration::ZERO,
+            spoke: false,
+            sliding: None,
+            monitor: None,
+            failures: 0,
+            model_swap,
+        }
+    }
+
+    /// 在独立线程中从 `audio_receiver` 读取音频块并转录，结果经 `result_sender` 交给主循环。
+    /// `stop_receiver` 收到信号（或发送端被丢弃）后处理完队列中已有的音频再退出
+    fn spawn(
+        mut self,
+        audio_receiver: Receiver<Vec<f32>>,
+        result_sender: Sender<WorkerResult>,
+        stop_receiver: Receiver<()>,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            select! {
+                recv(audio_receiver) -> chunk => match chunk {
+                    Ok(chunk) => self.forward(chunk, &result_sender),
+                    Err(_) => break,
+                },
+                recv(stop_receiver) -> _ => {
+                    for chunk in audio_receiver.try_iter() {
+                        self.forward(chunk, &result_sender);
+                    }
+                    break;
+                }
+            }
+        })
+    }
+
+    /// 转录一个音频块并把结果交给主循环
+    fn forward(&mut self, chunk: Vec<f32>, result_sender: &Sender<WorkerResult>) {
+        for result in self.process(chunk) {
+            if let Err(e) = result_sender.send(result) {
+                if matches!(e.0, WorkerResult::Transcribed(..)) {
+                    self.state.record_dropped();
+                }
+            }
+        }
+    }
+
+    /// 转录一个音频块。空的音频块表示采集已停止：滑动窗口把剩余文本定稿
+    fn process(&mut self, chunk: Vec<f32>) -> Vec<WorkerResult> {
+        if let Some((whisper, name)) = self.model_swap.try_iter().last() {
+            self.state.gpu_active.store(whisper.uses_gpu(), Ordering::Relaxed);
+            *self.whisper.lock().unwrap() = whisper;
+            info!("Switched to Whisper model '{}'", name);
+        }
+        self.state.busy.store(true, Ordering::SeqCst);
+        let results = self.transcribe(chunk);
+        self.state.busy.store(false, Ordering::SeqCst);
+        results
+    }
+
+    fn transcribe(&mut self, chunk: Vec<f32>) -> Vec<WorkerResult> {
+        let mut results = Vec::new();
+        if self.sliding.is_none() {
+            self.sliding = self
+                .state
+                .sliding_window
+                .lock()
+                .unwrap()
+                .map(SlidingWindowTranscriber::new);
+        }
+
+        if chunk.is_empty() {
+            if let Some(window) = self.sliding.as_mut() {
+                if let Some(transcription) = window.flush(&mut self.whisper.lock().unwrap()) {
+                    let speaker = self.speaker.lock().unwrap().last;
+                    results.push(WorkerResult::Transcribed(transcription, speaker, None));
+                }
+            }
+            return results;
+        }
+
+        let audio = Duration::from_secs_f64(chunk.len() as f64 / 16_000.0);
+        // 块在会话时间轴上的起点，逐词时间据此换算
+        let (chunk_start, speaker) = {
+            let mut track = self.speaker.lock().unwrap();
+            (track.position, track.label(&chunk))
+        };
+        self.state.dump_chunk(&chunk);
+        let started = Instant::now();
+        if let Some(window) = self.sliding.as_mut() {
+            let update = window.push(&mut self.whisper.lock().unwrap(), &chunk);
+            if let Some(update) = update {
+                self.state.record_chunk(audio, started.elapsed());
+                for revision in update.revisions {
+                    results.push(WorkerResult::Revised(revision.segment, revision.transcription));
+                }
+                if let Some((segment, finalized)) = update.finalized {
+                    results.push(WorkerResult::WindowFinalized(segment, finalized, speaker));
+                }
+                results.push(WorkerResult::Partial(update.partial));
+            }
+            return results;
+        }
+
+        // 过短的零头不送去转录，也不计为转录失败
+        if chunk.len() < MIN_TRANSCRIBE_SAMPLES {
+            debug!("Skipping {} ms chunk, too short to transcribe", chunk.len() / 16);
+            return results;
+        }
+        let audio_rms = rms(&chunk);
+        let samples = chunk.len();
+        let result = self.whisper.lock().unwrap().transcribe(chunk);
+        let elapsed = started.elapsed();
+        self.state.record_chunk(audio, elapsed);
+        let Some(mut transcription) = result else {
+            self.state.record_dropped();
+            self.failures += 1;
+            if self.failures >= FAILURES_BEFORE_RELOAD {
+                warn!("{} consecutive chunks failed to transcribe, reloading the Whisper model", self.failures);
+                self.failures = 0;
+                reload_whisper(&self.whisper, &self.state);
+            }
+            return results;
+        };
+        self.failures = 0;
+        let speech = !is_non_speech(&transcription.text);
+        let trace = ChunkTrace::new(samples, audio_rms, speech, &transcription, elapsed).map(Box::new);
+        transcription.offset_words(chunk_start);
+        let threshold = self.state.recovery_threshold.load(Ordering::Relaxed) as usize;
+        if threshold > 0 {
+            if self.monitor.as_ref().is_none_or(|m| m.threshold() != threshold) {
+                self.monitor = Some(RecoveryMonitor::new(threshold));
+            }
+            let monitor = self.monitor.as_mut().unwrap();
+            if monitor.observe(audio_rms, &transcription.text) {
+                warn!(
+                    "Whisper produced {} consecutive empty or repeated results, recreating its state",
+                    threshold
+                );
+                match self.whisper.lock().unwrap().reset() {
+                    Ok(()) => {
+                        self.state.whisper_resets.fetch_add(1, Ordering::Relaxed);
+                        info!("Whisper state recreated");
+                    }
+                    Err(e) => {
+                        error!("{:#}", e);
+                        reload_whisper(&self.whisper, &self.state);
+                    }
+                }
+            }
+        }
+        if !speech {
+            self.silence += audio;
+            if self.spoke && self.state.paragraph_silence().is_some_and(|t| self.silence >= t) {
+                self.spoke = false;
+                results.push(WorkerResult::ParagraphBreak);
+            }
+        } else {
+            self.silence = Duration::ZERO;
+            self.spoke = true;
+        }
+        results.push(WorkerResult::Transcribed(transcription, speaker, trace));
+        results
+    }
+}
+
+/// 运行中可发送给流水线的控制命令
+pub enum PipelineCommand {
+    /// 切换到指定名称的采集设备（麦克风或回环输出设备），下游的转录与翻译不受影响
+    SwitchDevice(String),
+    /// 以给定名称注册一个字幕输出端，同名的输出端会被替换
+    #[allow(dead_code)]
+    AddSink(String, Box<dyn CaptionSink>),
+    /// 移除指定名称的字幕输出端
+    RemoveSink(String),
+    /// 在日志中列出当前启用的字幕输出端
+    ListSinks,
+    /// 暂停流水线，见 `Pipeline::pause`
+    Pause,
+    /// 恢复流水线，见 `Pipeline::resume`
+    Resume,
+}
+
+/// 串联 采集 -> 转录 -> 翻译 -> 显示 的完整流水线
+pub struct Pipeline {
+    audio_input: AudioInput,
+    /// 采集端使用的发送端，切换设备时交给新的采集流
+    audio_sender: Sender<Vec<f32>>,
+    capture_config: CaptureConfig,
+    control_sender: Sender<PipelineCommand>,
+    control_receiver: Receiver<PipelineCommand>,
+    /// 与转录线程共用的音频队列，用于暂停时等待排空、恢复时丢弃过期音频
+    audio_receiver: Receiver<Vec<f32>>,
+    /// 暂停期间不再更新显示
+    paused: bool,
+    /// 转录线程与 `process_samples` 共用
+    whisper: Arc<Mutex<Whisper>>,
+    speaker: Arc<Mutex<SpeakerTrack>>,
+    /// 未设置翻译器时只输出转录原文
+    translator: Option<Box<dyn Translate>>,
+    /// 翻译前依次作用于转录文本的后处理器
+    post_processors: Vec<Box<dyn TextPostProcessor>>,
+    /// 翻译后依次作用于原文和译文的过滤器（如屏蔽词）
+    output_filters: Vec<Box<dyn TextPostProcessor>>,
+    /// 翻译后、输出过滤之前依次作用于译文的处理器（如本地化）
+    translation_processors: Vec<Box<dyn TextPostProcessor>>,
+    /// 字幕输出端及其名称，只在主循环线程上访问；其他线程通过控制命令增删
+    sinks: Vec<(String, Box<dyn CaptionSink>)>,
+    result_receiver: Receiver<WorkerResult>,
+    /// 尚未启动的转录工作者及其结果发送端，见 `ensure_worker`
+    pending_worker: Option<(ChunkWorker, Sender<WorkerResult>)>,
+    /// 是否在主循环线程中直接转录，见 `with_fused_worker`
+    fused: bool,
+    /// 融合模式下由主循环驱动的转录工作者
+    fused_worker: Option<ChunkWorker>,
+    /// 转录线程及通知其退出的发送端，`stop` 时据此等待线程结束
+    worker_thread: Option<(JoinHandle<()>, Sender<()>)>,
+    /// 把自动切换的新模型交给转录工作者，由它在两个音频块之间换上，主循环不必等待当前块转录完毕
+    model_swap: Sender<(Whisper, &'static str)>,
+    state: Arc<PipelineState>,
+    /// 翻译缓存文件路径，设置后会定期保存
+    translation_cache: Option<String>,
+    /// 是否为译文标注拼音
+    #[cfg(feature = "pinyin")]
+    pinyin: bool,
+    /// 设置后翻译器只翻译完整的句子，半句话先缓冲；未设置时每段转录立即翻译
+    sentence_buffer: Option<SentenceBuffer>,
+    /// 设置后按负载自动切换 Whisper 模型
+    adaptive_model: Option<AdaptiveModel>,
+    /// 转录置信度低于该值的字幕不翻译，只输出原文
+    min_translate_confidence: Option<f32>,
+    /// 设置后只隔几条或隔一段时间翻译一条字幕，其余只输出原文
+    translation_throttle: Option<TranslationThrottle>,
+    /// 设置后低置信度的片段替换为占位符，而不是按原样输出
+    unclear: Option<UnclearConfig>,
+    /// 最近一次生成字幕时的翻译决策，供逐块记录使用："translated"、"translated_split"（超长输入按句切分后翻译）、
+    /// "unchanged"（译文与原文相同，含过短而未送入模型的输入）、
+    /// "skipped_low_confidence"、"throttled"、"no_translator"、"failed"、"buffered"（等待凑成整句）、"non_speech_marker"、
+    /// "unclear"（整段都替换成了占位符）
+    translate_decision: Option<&'static str>,
+    /// 滑动窗口改正已定稿的文本时是否重新翻译并输出修订，见 `with_revisions`
+    revisions: bool,
+    /// 下一条最终字幕的序号
+    next_caption: u64,
+    /// 最近由滑动窗口定稿片段直接生成的字幕：(片段编号, 字幕序号, 说话人)，供修订时查找
+    window_captions: VecDeque<(u64, u64, Option<u32>)>,
+}
+
+/// 低置信度片段的占位替换设置
+struct UnclearConfig {
+    /// 片段置信度（0~1）低于该值时替换
+    threshold: f32,
+    /// 替换后显示的文本，如 "[unclear]"
+    placeholder: String,
+    /// 是否把被替换的原文附在字幕上（`Caption::unclear`）
+    keep_guess: bool,
+}
+
+/// 最终字幕使用的日志 target，由独立的字幕日志文件接收
+pub const CAPTIONS_TARGET: &str = "captions";
+
+/// 翻译缓存的保存间隔
+const CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// 默认连续 8 次异常转录后重建 Whisper 状态
+const DEFAULT_RECOVERY_THRESHOLD: u64 = 8;
+/// 连续多少个音频块转录失败后重新加载 Whisper 模型
+const FAILURES_BEFORE_RELOAD: usize = 3;
+/// 最多记住多少条滑动窗口字幕以备修订。窗口清空后旧片段不会再被改正，几十条足够
+const MAX_REVISABLE_CAPTIONS: usize = 32;
+/// 暂停时等待在途音频处理完毕的最长时间
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// 停止时等待转录线程退出的最长时间，超时后放弃等待，避免卡住的转录拖住退出
+const WORKER_JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Pipeline {
+    /// 从默认输出设备采集，启动音频采集和转录线程，返回可运行的流水线
+    pub fn new(whisper: Whisper, capture_config: CaptureConfig) -> anyhow::Result<Self> {
+        ensure_whisper_rate(&capture_config)?;
+        // 创建音频数据传输的 channel
+        let (audio_sender, audio_receiver): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = unbounded();
+
+        // 将 Sender 传递给 capture 模块，采集到的数据会通过该 channel 发送
+        let audio_capture = AudioCapture::new_stream_with_config(audio_sender.clone(), capture_config.clone())?;
+        Ok(Self::start(
+            AudioInput::Device(audio_capture),
+            (audio_sender, audio_receiver),
+            whisper,
+            capture_config,
+        ))
+    }
+
+    /// 按名称选择采集设备（输入设备优先，其次是输出设备的回环）
+    pub fn for_device(whisper: Whisper, name: &str, capture_config: CaptureConfig) -> anyhow::Result<Self> {
+        ensure_whisper_rate(&capture_config)?;
+        let (audio_sender, audio_receiver): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = unbounded();
+        let audio_capture =
+            AudioCapture::new_stream_for_device(name, audio_sender.clone(), capture_config.clone())?;
+        Ok(Self::start(
+            AudioInput::Device(audio_capture),
+            (audio_sender, audio_receiver),
+            whisper,
+            capture_config,
+        ))
+    }
+
+    /// 同时采集多个设备并混音后送入转录
+    pub fn with_mixed_sources(
+        whisper: Whisper,
+        sources: &[MixSource],
+        capture_config: CaptureConfig,
+    ) -> anyhow::Result<Self> {
+        ensure_whisper_rate(&capture_config)?;
+        let (audio_sender, audio_receiver): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = unbounded();
+        let mixer = AudioMixer::new(sources, audio_sender.clone(), capture_config.clone())?;
+        Ok(Self::start(
+            AudioInput::Mixer(mixer),
+            (audio_sender, audio_receiver),
+            whisper,
+            capture_config,
+        ))
+    }
+
+    /// 从标准输入读取原始 PCM（如 `ffmpeg ... -f f32le -` 的输出），
+    /// 混为单声道并重采样到 16k 后送入转录。标准输入结束后 `run` 会处理完剩余音频再返回。
+    pub fn from_stdin(whisper: Whisper, format: StdinFormat, capture_config: CaptureConfig) -> anyhow::Result<Self> {
+        ensure_whisper_rate(&capture_config)?;
+        let (audio_sender, audio_receiver): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = unbounded();
+        let source = StdinSource::start(format, audio_sender.clone(), capture_config.clone());
+        Ok(Self::start(
+            AudioInput::Stdin(source),
+            (audio_sender, audio_receiver),
+            whisper,
+            capture_config,
+        ))
+    }
+
+    /// 不打开任何音频设备，音频完全由调用方通过 `process_samples` 提供
+    /// （例如来自网络流或 WebRTC）
+    #[allow(dead_code)]
+    pub fn external(whisper: Whisper) -> Self {
+        // 没有采集端，队列只在之后通过 `SwitchDevice` 切换到真实设备时使用
+        Self::start(AudioInput::External, unbounded(), whisper, CaptureConfig::default())
+    }
+
+    fn start(
+        audio_input: AudioInput,
+        (audio_sender, audio_receiver): (Sender<Vec<f32>>, Receiver<Vec<f32>>),
+        whisper: Whisper,
+        capture_config: CaptureConfig,
+    ) -> Self {
+        let state = Arc::new(PipelineState::new(audio_input.device_name(), whisper.uses_gpu()));
+        let whisper = Arc::new(Mutex::new(whisper));
+        let speaker = Arc::new(Mutex::new(SpeakerTrack {
+            labeler: Box::new(NoSpeakerLabeler),
+            position: Duration::ZERO,
+            last: None,
+        }));
+
+        // 用于传递转录结果的 channel
+        let (result_sender, result_receiver): (Sender<WorkerResult>, Receiver<WorkerResult>) = unbounded();
+
+        // 转录线程在第一次处理结果时才启动，此前可用 `with_fused_worker` 改为在主循环中转录
+        let (model_swap, model_swap_receiver) = unbounded();
+        let worker = ChunkWorker::new(
+            Arc::clone(&state),
+            Arc::clone(&whisper),
+            Arc::clone(&speaker),
+            model_swap_receiver,
+        );
+
+        let (control_sender, control_receiver) = unbounded();
+
+        Self {
+            audio_input,
+            audio_sender,
+            capture_config,
+            control_sender,
+            control_receiver,
+            audio_receiver,
+            pending_worker: Some((worker, result_sender)),
+            fused: false,
+            fused_worker: None,
+            worker_thread: None,
+            model_swap,
+            paused: false,
+            whisper,
+            speaker,
+            translator: None,
+            post_processors: Vec::new(),
+            output_filters: Vec::new(),
+            translation_processors: Vec::new(),
+            sinks: Vec::new(),
+            result_receiver,
+            state,
+            translation_cache: None,
+            #[cfg(feature = "pinyin")]
+            pinyin: false,
+            sentence_buffer: None,
+            adaptive_model: None,
+            min_translate_confidence: None,
+            translation_throttle: None,
+            unclear: None,
+            translate_decision: None,
+            revisions: false,
+            next_caption: 0,
+            window_captions: VecDeque::new(),
+        }
+    }
+
+    /// 注册一个字幕输出端，名称自动生成为 "sink-N"
+    #[allow(dead_code)]
+    pub fn with_sink(self, sink: Box<dyn CaptionSink>) -> Self {
+        let name = format!("sink-{}", self.sinks.len());
+        self.with_named_sink(&name, sink)
+    }
+
+    /// 以给定名称注册一个字幕输出端，之后可用 `remove_sink` 按名称移除
+    pub fn with_named_sink(mut self, name: &str, sink: Box<dyn CaptionSink>) -> Self {
+        self.add_sink(name, sink);
+        self
+    }
+
+    /// 运行中注册一个字幕输出端，同名的输出端会被替换。
+    /// 其他线程请通过 `control_handle` 发送 `PipelineCommand::AddSink`
+    pub fn add_sink(&mut self, name: &str, sink: Box<dyn CaptionSink>) {
+        match self.sinks.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) => {
+                info!("Replacing caption sink '{}'", name);
+                *existing = sink;
+            }
+            None => self.sinks.push((name.to_string(), sink)),
+        }
+        self.update_sink_names();
+    }
+
+    /// 运行中移除指定名称的字幕输出端并将其返回，不存在时返回 `None`。
+    /// 其他线程请通过 `control_handle` 发送 `PipelineCommand::RemoveSink`
+    pub fn remove_sink(&mut self, name: &str) -> Option<Box<dyn CaptionSink>> {
+        let index = self.sinks.iter().position(|(existing, _)| existing == name)?;
+        let (_, sink) = self.sinks.remove(index);
+        self.update_sink_names();
+        Some(sink)
+    }
+
+    /// 当前启用的字幕输出端名称，按注册顺序排列
+    pub fn sink_names(&self) -> Vec<String> {
+        self.sinks.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    fn update_sink_names(&self) {
+        *self.state.sink_names.lock().unwrap() = self.sink_names();
+    }
+
+    /// 注册一个文本后处理器，在翻译前按注册顺序作用于转录文本
+    pub fn with_post_processor(mut self, processor: Box<dyn TextPostProcessor>) -> Self {
+        self.post_processors.push(processor);
+        self
+    }
+
+    /// 注册一个输出过滤器，在翻译之后按注册顺序作用于原文、译文和临时字幕，
+    /// 所有输出端看到的都是过滤后的文本。翻译器仍拿到未过滤的原文，不影响译文质量
+    pub fn with_output_filter(mut self, filter: Box<dyn TextPostProcessor>) -> Self {
+        self.output_filters.push(filter);
+        self
+    }
+
+    /// 注册一个译文处理器，在翻译之后、输出过滤之前按注册顺序只作用于译文，如按目标区域统一日期和数字的写法
+    #[allow(dead_code)]
+    pub fn with_translation_processor(mut self, processor: Box<dyn TextPostProcessor>) -> Self {
+        self.translation_processors.push(processor);
+        self
+    }
+
+    /// 依次应用输出过滤器
+    fn filter_output(&self, text: &str) -> String {
+        self.output_filters
+            .iter()
+            .fold(text.to_string(), |text, filter| filter.process(&text))
+    }
+
+    /// 设置说话人标注器，替换默认的 `NoSpeakerLabeler`
+    #[allow(dead_code)]
+    pub fn with_speaker_labeler(self, labeler: Box<dyn SpeakerLabeler>) -> Self {
+        self.speaker.lock().unwrap().labeler = labeler;
+        self
+    }
+
+    /// 连续静音超过 `silence` 时插入一个分段标记（`CaptionEvent::ParagraphBreak`）
+    pub fn with_paragraph_silence(self, silence: Duration) -> Self {
+        self.state
+            .paragraph_silence_ms
+            .store(silence.as_millis() as u64, Ordering::Relaxed);
+        self
+    }
+
+    /// 改用滑动窗口流式转录：每个 hop 重新转录滚动缓冲区，
+    /// 稳定的前缀作为最终字幕输出，尾部作为临时字幕（`CaptionEvent::Partial`）
+    pub fn with_sliding_window(self, config: SlidingWindowConfig) -> Self {
+        *self.state.sliding_window.lock().unwrap() = Some(config);
+        self
+    }
+
+    /// 调试用：把每个送入转录的音频块原样写成 WAV 文件，见 `ChunkDumper`。
+    /// 滑动窗口模式下写出的是每个 hop 新到达的音频，而不是整个窗口
+    pub fn with_chunk_dump(self, dumper: ChunkDumper) -> Self {
+        *self.state.chunk_dump.lock().unwrap() = Some(dumper);
+        self
+    }
+
+    /// 设置连续多少次异常转录（有声音却输出空白，或重复同一句话）后自动重建 Whisper 状态，
+    /// 默认 8 次；设为 0 关闭检测
+    pub fn with_recovery_threshold(self, threshold: usize) -> Self {
+        self.state
+            .recovery_threshold
+            .store(threshold as u64, Ordering::Relaxed);
+        self
+    }
+
+    /// 转录跟不上时自动换用较小的 Whisper 模型（负载下降后可换回），见 `AdaptiveModel`
+    pub fn with_adaptive_model(mut self, adaptive: AdaptiveModel) -> Self {
+        self.adaptive_model = Some(adaptive);
+        self
+    }
+
+    /// 按队列中待转录的音频时长和实时率检查是否需要切换模型，新模型加载完成后换上
+    fn adapt_model(&mut self) {
+        let Some(adaptive) = self.adaptive_model.as_mut() else {
+            return;
+        };
+        let queue_secs = self.audio_receiver.len() as f64 * self.capture_config.chunk_secs;
+        let real_time_factor = f64::from_bits(self.state.real_time_factor.load(Ordering::Relaxed));
+        if let Some(model) = adaptive.observe(queue_secs, real_time_factor) {
+            // 转录工作者处理完当前的音频块后再换上，主循环照常刷新显示和处理命令
+            let _ = self.model_swap.send(model);
+        }
+    }
+
+    /// 把事件交给所有需要该类事件的输出端
+    fn emit(&mut self, event: &CaptionEvent) {
+        for (_, sink) in self.sinks.iter_mut().filter(|(_, sink)| sink.wants(event)) {
+            sink.emit(event);
+        }
+    }
+
+    /// 设置翻译后端。未设置时字幕只包含转录原文
+    #[allow(dead_code)]
+    pub fn with_translator(mut self, translator: Box<dyn Translate>) -> Self {
+        self.translator = Some(translator);
+        self.state.translator_loaded.store(true, Ordering::Relaxed);
+        self
+    }
+
+    /// 从 `path` 加载翻译缓存，并在运行过程中定期写回。需在 `with_translator` 之后调用
+    #[allow(dead_code)]
+    pub fn with_translation_cache(mut self, path: &str) -> Self {
+        if let Some(translator) = self.translator.as_mut() {
+            if let Err(e) = translator.load_cache(path) {
+                error!("Failed to load translation cache: {:#}", e);
+            }
+        }
+        self.translation_cache = Some(path.to_string());
+        self
+    }
+
+    /// 翻译前先缓冲转录文本，凑成以句末标点结尾的完整句子再翻译，译文更连贯；
+    /// 缓冲超过 `max_hold` 仍未成句时照常翻译。缓冲中的原文以临时字幕显示。
+    /// 未设置时每段转录立即翻译，延迟最低。没有翻译器时不缓冲
+    pub fn with_sentence_buffer(mut self, max_hold: Duration) -> Self {
+        self.sentence_buffer = Some(SentenceBuffer::new(max_hold));
+        self
+    }
+
+    /// 转录置信度（0~1）低于 `threshold` 的字幕不送入翻译器，只输出原文，
+    /// 避免把可能识别错的句子翻译成误导性的中文，也节省翻译开销
+    pub fn with_min_translate_confidence(mut self, threshold: f32) -> Self {
+        self.min_translate_confidence = Some(threshold);
+        self
+    }
+
+    /// 降低翻译频率：每条字幕照常转录输出原文，但按 `TranslationThrottle` 的条件只翻译其中一部分
+    pub fn with_translation_throttle(mut self, throttle: TranslationThrottle) -> Self {
+        self.translation_throttle = Some(throttle);
+        self
+    }
+
+    /// 置信度（0~1）低于 `threshold` 的片段替换为 `placeholder`，保留字幕结构以便人工复核；
+    /// 被替换的原文总会写入 debug 日志，`keep_guess` 为真时还会附在字幕的 `unclear` 字段中
+    pub fn with_unclear_placeholder(mut self, threshold: f32, placeholder: impl Into<String>, keep_guess: bool) -> Self {
+        self.unclear = Some(UnclearConfig {
+            threshold,
+            placeholder: placeholder.into(),
+            keep_guess,
+        });
+        self
+    }
+
+    /// 在主循环线程中直接完成 转录 -> 翻译 -> 输出，省去转录线程到主循环的结果队列和轮询间隔，
+    /// 端到端延迟更低；代价是翻译期间不能同时转录下一个块。需在 `run` 之前调用
+    pub fn with_fused_worker(mut self, fused: bool) -> Self {
+        self.fused = fused;
+        self
+    }
+
+    /// 滑动窗口模式下，之后的转录改正了已输出的字幕原文时，重新翻译并以 `CaptionEvent::Revision`
+    /// 输出整条字幕。只对未经整句缓冲、由单个定稿片段直接生成的字幕生效
+    pub fn with_revisions(mut self, enabled: bool) -> Self {
+        self.revisions = enabled;
+        self
+    }
+
+    /// 为译文标注拼音，结果放在 `Caption::pinyin` 中
+    #[cfg(feature = "pinyin")]
+    pub fn with_pinyin(mut self, enabled: bool) -> Self {
+        self.pinyin = enabled;
+        self
+    }
+
+    /// 若翻译缓存有新增，则写回磁盘
+    fn save_translation_cache(&mut self) {
+        if let (Some(path), Some(translator)) = (&self.translation_cache, self.translator.as_mut()) {
+            if translator.is_cache_dirty() {
+                if let Err(e) = translator.save_cache(path) {
+                    error!("Failed to save translation cache: {:#}", e);
+                }
+            }
+        }
+    }
+
+    /// 返回控制命令的发送端，可在其他线程中向运行中的流水线发送命令
+    pub fn control_handle(&self) -> Sender<PipelineCommand> {
+        self.control_sender.clone()
+    }
+
+    /// 执行所有已到达的控制命令
+    fn handle_commands(&mut self) {
+        while let Ok(command) = self.control_receiver.try_recv() {
+            match command {
+                PipelineCommand::SwitchDevice(name) => {
+                    if let Err(e) = self.switch_device(&name) {
+                        error!("Failed to switch to device '{}': {:#}", name, e);
+                    }
+                }
+                PipelineCommand::AddSink(name, sink) => {
+                    self.add_sink(&name, sink);
+                    info!("Caption sink '{}' added", name);
+                }
+                PipelineCommand::RemoveSink(name) => match self.remove_sink(&name) {
+                    Some(_) => info!("Caption sink '{}' removed", name),
+                    None => error!("No caption sink named '{}'", name),
+                },
+                PipelineCommand::ListSinks => info!("Caption sinks: {}", self.sink_names().join(", ")),
+                PipelineCommand::Pause => self.pause(),
+                PipelineCommand::Resume => self.resume(),
+            }
+        }
+    }
+
+    /// 切换采集设备：先建立新设备的采集流，成功后再停止旧的采集，
+    /// 旧设备尾段的音频会照常送入转录，转录和翻译线程保持运行。
+    pub fn switch_device(&mut self, name: &str) -> anyhow::Result<()> {
+        info!("Switching capture device to '{}'", name);
+        let capture = AudioCapture::new_stream_for_device(name, self.audio_sender.clone(), self.capture_config.clone())?;
+        if self.paused {
+            capture.pause();
+        }
+        let mut previous = std::mem::replace(&mut self.audio_input, AudioInput::Device(capture));
+        previous.stop();
+        *self.state.device_name.lock().unwrap() = self.audio_input.device_name();
+        info!("Capture device switched to '{}'", self.audio_input.device_name());
+        Ok(())
+    }
+
+    /// 返回当前运行状态
+    #[allow(dead_code)]
+    pub fn status(&self) -> PipelineStatus {
+        self.state.snapshot()
+    }
+
+    /// 返回共享状态的句柄，供其他线程（如健康检查服务）查询
+    pub fn state_handle(&self) -> Arc<PipelineState> {
+        Arc::clone(&self.state)
+    }
+
+    /// 暂停整条流水线：停止采集，等待已采集的音频完成转录、翻译和显示，
+    /// 之后不再更新显示，直到调用 `resume`。
+    pub fn pause(&mut self) {
+        if self.paused {
+            return;
+        }
+        self.audio_input.pause();
+        self.drain();
+        self.paused = true;
+        info!("Pipeline paused");
+    }
+
+    /// 恢复流水线。暂停期间残留的音频会被丢弃，不会在恢复后重放
+    pub fn resume(&mut self) {
+        if !self.paused {
+            return;
+        }
+        for _ in self.audio_receiver.try_iter() {}
+        for _ in self.result_receiver.try_iter() {}
+        self.paused = false;
+        self.audio_input.resume();
+        info!("Pipeline resumed");
+    }
+
+    /// 停止采集，并把尾段音频以及队列中剩余的音频全部转录、翻译、显示完毕，随后等待转录线程退出
+    pub fn stop(&mut self) {
+        info!("Stopping pipeline...");
+        self.audio_input.stop();
+        // 通知转录线程采集已结束，滑动窗口据此定稿剩余文本
+        let _ = self.audio_sender.send(Vec::new());
+        self.drain();
+        // 等转录线程退出后再收取它最后交出的结果，保证文稿、导出的音频块等输出都已写完
+        self.join_worker();
+        self.process_pending();
+        for event in self.flush_sentence_buffer() {
+            self.emit(&event);
+        }
+        self.save_translation_cache();
+        info!("Pipeline stopped");
+    }
+
+    /// 等待转录线程处理完队列中的音频，再处理全部转录结果。融合模式下由 `process_pending` 直接处理
+    fn drain(&mut self) {
+        self.ensure_worker();
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+        while self.fused_worker.is_none()
+            && (!self.audio_receiver.is_empty() || self.state.busy.load(Ordering::SeqCst))
+            && Instant::now() < deadline
+        {
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.process_pending();
+    }
+
+    /// 通知转录线程退出并等待其结束；超过 `WORKER_JOIN_TIMEOUT` 仍未退出时记录日志后放弃等待
+    fn join_worker(&mut self) {
+        let Some((handle, stop_sender)) = self.worker_thread.take() else {
+            return;
+        };
+        let _ = stop_sender.send(());
+        let deadline = Instant::now() + WORKER_JOIN_TIMEOUT;
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        if !handle.is_finished() {
+            warn!(
+                "Transcription thread did not exit within {}s, abandoning it",
+                WORKER_JOIN_TIMEOUT.as_secs()
+            );
+            return;
+        }
+        if handle.join().is_err() {
+            error!("Transcription thread panicked");
+        }
+    }
+
+    /// 流水线当前是否处于暂停状态
+    #[allow(dead_code)]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// 主循环：处理转录结果，并进行翻译。收到退出请求后，处理完剩余音频再返回
+    pub fn run(&mut self) {
+        info!("Starting real-time transcription loop...");
+        let mut last_cache_save = Instant::now();
+        while !self.state.shutdown.load(Ordering::SeqCst) {
+            self.handle_commands();
+            if self.audio_input.is_finished() {
+                info!("Audio input finished");
+                break;
+            }
+            if last_cache_save.elapsed() >= CACHE_SAVE_INTERVAL {
+                self.save_translation_cache();
+                last_cache_save = Instant::now();
+            }
+            if !self.paused {
+                self.process_pending();
+                self.adapt_model();
+            }
+            if self.fused_worker.is_some() && !self.paused {
+                // 融合模式：音频块一到就醒来处理，不按固定间隔轮询
+                let mut select = Select::new();
+                select.recv(&self.audio_receiver);
+                let _ = select.ready_timeout(Duration::from_millis(50));
+            } else {
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+        self.stop();
+    }
+
+    /// 启动转录：默认在独立线程中进行；设置了 `with_fused_worker` 时留给主循环驱动。只在第一次调用时生效
+    fn ensure_worker(&mut self) {
+        let Some((worker, result_sender)) = self.pending_worker.take() else {
+            return;
+        };
+        if self.fused {
+            info!("Transcribing on the main loop thread (fused worker)");
+            self.fused_worker = Some(worker);
+        } else {
+            let (stop_sender, stop_receiver) = bounded(1);
+            let handle = worker.spawn(self.audio_receiver.clone(), result_sender, stop_receiver);
+            self.worker_thread = Some((handle, stop_sender));
+        }
+    }
+
+    /// 处理目前已到达的全部转录结果：翻译并显示。
+    /// 自行驱动流水线（而非调用 `run`）时，应定期调用该方法。
+    pub fn process_pending(&mut self) {
+        self.ensure_worker();
+        self.state.set_device_latency(self.audio_input.latency());
+        self.state.queue_depth.store(self.audio_receiver.len() as u64, Ordering::Relaxed);
+        while let Ok(result) = self.result_receiver.try_recv() {
+            self.handle_result(result);
+        }
+        // 融合模式：在本线程中转录队列中的音频，结果直接翻译输出，不经过结果队列
+        if let Some(mut worker) = self.fused_worker.take() {
+            while let Ok(chunk) = self.audio_receiver.try_recv() {
+                for result in worker.process(chunk) {
+                    self.handle_result(result);
+                }
+            }
+            self.fused_worker = Some(worker);
+        }
+        let expired = self.sentence_buffer.as_mut().and_then(SentenceBuffer::take_expired);
+        if let Some(event) = expired.and_then(|sentence| self.translate_sentence(sentence)) {
+            self.emit(&event);
+        }
+    }
+
+    /// 翻译并输出一条转录结果
+    fn handle_result(&mut self, result: WorkerResult) {
+        match result {
+            WorkerResult::Transcribed(transcription, speaker, trace) => {
+                let started = Instant::now();
+                self.translate_decision = None;
+                let events = self.caption_events(&transcription, speaker);
+                if let Some(trace) = trace {
+                    trace.finish(self.translate_decision, &events, started.elapsed());
+                }
+                for event in &events {
+                    self.emit(event);
+                }
+            }
+            WorkerResult::WindowFinalized(segment, transcription, speaker) => {
+                self.translate_decision = None;
+                let events = self.caption_events(&transcription, speaker);
+                // 整句缓冲会把片段拼接、拆分，与字幕对不上，只记录一个片段直接生成一条字幕的情况
+                if let [CaptionEvent::Final(Caption { index: Some(index), .. })] = events.as_slice() {
+                    if self.revisions && self.sentence_buffer.is_none() {
+                        if self.window_captions.len() >= MAX_REVISABLE_CAPTIONS {
+                            self.window_captions.pop_front();
+                        }
+                        self.window_captions.push_back((segment, *index, speaker));
+                    }
+                }
+                for event in &events {
+                    self.emit(event);
+                }
+            }
+            WorkerResult::Revised(segment, transcription) => {
+                if let Some(event) = self.revise_caption(segment, &transcription) {
+                    self.emit(&event);
+                }
+            }
+            WorkerResult::Partial(text) => {
+                // 整句缓冲中尚未翻译的文本排在滑动窗口的临时文本之前
+                let pending = self.sentence_buffer.as_ref().and_then(SentenceBuffer::pending_text);
+                let text = match pending {
+                    Some(pending) if !text.is_empty() => format!("{} {}", pending, text),
+                    Some(pending) => pending.to_string(),
+                    None => text,
+                };
+                self.emit(&partial_event(self.filter_output(&text)));
+            }
+            WorkerResult::ParagraphBreak => {
+                for event in self.flush_sentence_buffer() {
+                    self.emit(&event);
+                }
+                self.emit(&CaptionEvent::ParagraphBreak);
+            }
+        }
+    }
+
+    /// 重新翻译被改正的滑动窗口片段，生成替换原字幕的修订；未启用修订或找不到对应字幕时返回 `None`
+    fn revise_caption(&mut self, segment: u64, transcription: &Transcription) -> Option<CaptionEvent> {
+        if !self.revisions {
+            return None;
+        }
+        let Some(&(_, index, speaker)) = self.window_captions.iter().find(|(s, _, _)| *s == segment) else {
+            debug!("No caption to revise for sliding window segment {}", segment);
+            return None;
+        };
+        let text = transcription.text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        let language = transcription.language.as_deref();
+        let caption =
+            self.build_caption(text.to_string(), transcription.confidence(), speaker, language, Vec::new(), Vec::new())?;
+        debug!("Revising caption {}: {:?}", index, caption.source);
+        Some(CaptionEvent::Revision(Caption {
+            index: Some(index),
+            ..caption
+        }))
+    }
+
+    /// 放出整句缓冲中剩余的文本并翻译
+    fn flush_sentence_buffer(&mut self) -> Vec<CaptionEvent> {
+        let flushed = self.sentence_buffer.as_mut().and_then(SentenceBuffer::flush);
+        flushed.and_then(|sentence| self.translate_sentence(sentence)).into_iter().collect()
+    }
+
+    /// 翻译整句缓冲放出的一句。合并后的句子与各次转录的片段对不上，不附带片段信息
+    fn translate_sentence(&mut self, sentence: Sentence) -> Option<CaptionEvent> {
+        let language = sentence.language.as_deref();
+        self.translate_caption(sentence.text, sentence.confidence, sentence.speaker, language, Vec::new(), Vec::new())
+    }
+
+    /// 同步处理一段外部音频：混为单声道、重采样到 16k、转录并翻译。
+    ///
+    /// 生成的事件会交给已注册的输出端，同时返回给调用方。
+    /// `samples` 为交错排列的 f32 数据，范围 [-1.0, 1.0]。
+    #[allow(dead_code)]
+    pub fn process_samples(
+        &mut self,
+        samples: Vec<f32>,
+        sample_rate: u32,
+        channels: u16,
+    ) -> anyhow::Result<Vec<CaptionEvent>> {
+        anyhow::ensure!(channels > 0, "channels must be greater than 0");
+        anyhow::ensure!(sample_rate > 0, "sample rate must be greater than 0");
+
+        let mono = downmix_with(samples, channels as usize, self.capture_config.mix);
+        let chunk = if sample_rate != 16_000 {
+            audio_resample(&mono, sample_rate, 16_000, self.capture_config.resample_quality)
+        } else {
+            mono
+        };
+        if chunk.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let audio = Duration::from_secs_f64(chunk.len() as f64 / 16_000.0);
+        let (chunk_start, speaker) = {
+            let mut track = self.speaker.lock().unwrap();
+            (track.position, track.label(&chunk))
+        };
+        self.state.dump_chunk(&chunk);
+        if chunk.len() < MIN_TRANSCRIBE_SAMPLES {
+            debug!("Skipping {} ms chunk, too short to transcribe", chunk.len() / 16);
+            return Ok(Vec::new());
+        }
+        let (samples, audio_rms) = (chunk.len(), rms(&chunk));
+        let started = Instant::now();
+        let result = self.whisper.lock().unwrap().transcribe(chunk);
+        let elapsed = started.elapsed();
+        self.state.record_chunk(audio, elapsed);
+        let Some(mut transcription) = result else {
+            self.state.record_dropped();
+            anyhow::bail!("transcription failed");
+        };
+        transcription.offset_words(chunk_start);
+
+        let speech = !is_non_speech(&transcription.text);
+        let trace = ChunkTrace::new(samples, audio_rms, speech, &transcription, elapsed);
+        let started = Instant::now();
+        self.translate_decision = None;
+        let events = self.caption_events(&transcription, speaker);
+        if let Some(trace) = trace {
+            trace.finish(self.translate_decision, &events, started.elapsed());
+        }
+        for event in &events {
+            self.emit(event);
+        }
+        Ok(events)
+    }
+
+    /// 处理一条转录结果，生成字幕事件；空白时不生成。
+    /// 启用整句缓冲时，半句话先留在缓冲中，以临时字幕显示。
+    fn caption_events(&mut self, transcription: &Transcription, speaker: Option<u32>) -> Vec<CaptionEvent> {
+        let marked = self.mark_unclear(transcription);
+        let (transcription, guesses) = match &marked {
+            Some((marked, guesses)) => (marked, guesses.clone()),
+            None => (transcription, Vec::new()),
+        };
+        let text = transcription.text.trim();
+        if text.is_empty() || text == "[BLANK_AUDIO]" {
+            return Vec::new();
+        }
+        // 整段都不可信、只剩占位符时同样不翻译，但它是一句话而不是非语音标记，输出端应照常显示
+        let unclear = self.unclear.as_ref().is_some_and(|config| text == config.placeholder.trim());
+        // "[Music]" 等非语音标记不翻译，原样交给输出端，由其决定如何显示
+        if unclear || (text.starts_with('[') && text.ends_with(']')) {
+            let mut events = self.flush_sentence_buffer();
+            self.translate_decision = Some(if unclear { "unclear" } else { "non_speech_marker" });
+            let index = self.take_caption_index();
+            events.push(CaptionEvent::Final(Caption {
+                index: Some(index),
+                timestamp: Local::now(),
+                source: text.to_string(),
+                translation: None,
+                pinyin: None,
+                confidence: Some(transcription.confidence()),
+                speaker,
+                segments: Vec::new(),
+                words: Vec::new(),
+                unclear: guesses,
+            }));
+            return events;
+        }
+
+        let language = transcription.language.as_deref();
+        let buffer = self.sentence_buffer.as_mut().filter(|_| self.translator.is_some());
+        let Some(buffer) = buffer else {
+            // 片段文本未经后处理，输出端需自行确认它们与 `source` 一致后再使用
+            let segments = transcription
+                .segments
+                .iter()
+                .filter_map(|segment| {
+                    Some(SegmentConfidence {
+                        text: segment.text.trim().to_string(),
+                        confidence: segment.confidence()?,
+                    })
+                })
+                .filter(|segment| !segment.text.is_empty())
+                .collect();
+            let words = transcription
+                .words
+                .iter()
+                .map(|word| WordConfidence {
+                    text: word.text.clone(),
+                    start_ms: word.start_ms,
+                    end_ms: word.end_ms,
+                    prob: word.prob,
+                })
+                .collect();
+            let mut event =
+                self.translate_caption(text.to_string(), transcription.confidence(), speaker, language, segments, words);
+            if let Some(CaptionEvent::Final(caption)) = &mut event {
+                caption.unclear = guesses;
+            }
+            return event.into_iter().collect();
+        };
+        let sentences = buffer.push(text, transcription.confidence(), speaker, language);
+        // 放出整句时由 `translate_caption` 改写
+        self.translate_decision = Some("buffered");
+        let pending = buffer.pending_text().map(str::to_string);
+        let mut events: Vec<CaptionEvent> = sentences
+            .into_iter()
+            .filter_map(|sentence| self.translate_sentence(sentence))
+            .collect();
+        if let Some(pending) = pending {
+            events.push(partial_event(self.filter_output(&pending)));
+        }
+        events
+    }
+
+    /// 把置信度低于阈值的片段替换为占位符，返回替换后的转录结果和被替换的原文；
+    /// 未启用或没有需要替换的片段时返回 `None`。整句缓冲只接收文本，被替换的原文仅记录在日志中
+    fn mark_unclear(&self, transcription: &Transcription) -> Option<(Transcription, Vec<String>)> {
+        let config = self.unclear.as_ref()?;
+        let is_unclear = |text: &str, confidence: f32| {
+            let text = text.trim();
+            confidence < config.threshold && !is_non_speech(text) && !(text.starts_with('[') && text.ends_with(']'))
+        };
+        let mut marked = transcription.clone();
+        let mut guesses = Vec::new();
+        if marked.segments.is_empty() {
+            // 拼接而来的结果没有片段信息，只能按整段的置信度判断
+            if !is_unclear(&marked.text, transcription.confidence()) {
+                return None;
+            }
+            guesses.push(marked.text.trim().to_string());
+            marked.text = config.placeholder.clone();
+        } else {
+            for segment in &mut marked.segments {
+                if segment.confidence().is_some_and(|confidence| is_unclear(&segment.text, confidence)) {
+                    let guess = std::mem::replace(&mut segment.text, config.placeholder.clone());
+                    guesses.push(guess.trim().to_string());
+                }
+            }
+            if guesses.is_empty() {
+                return None;
+            }
+            marked.text = marked
+                .segments
+                .iter()
+                .map(|segment| segment.text.trim())
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        debug!("Replaced {} low-confidence segment(s) with {:?}: {:?}", guesses.len(), config.placeholder, guesses);
+        if !config.keep_guess {
+            // 逐词结果会泄露被替换的原文
+            marked.words.clear();
+            guesses.clear();
+        }
+        Some((marked, guesses))
+    }
+
+    /// 对原文做后处理并翻译，生成最终字幕；翻译失败时返回 `None`。
+    /// 没有翻译器时直接输出只含原文的字幕。
+    fn translate_caption(
+        &mut self,
+        text: String,
+        confidence: f32,
+        speaker: Option<u32>,
+        language: Option<&str>,
+        segments: Vec<SegmentConfidence>,
+        words: Vec<WordConfidence>,
+    ) -> Option<CaptionEvent> {
+        let caption = self.build_caption(text, confidence, speaker, language, segments, words)?;
+        self.state.record_caption();
+        Some(CaptionEvent::Final(Caption {
+            index: Some(self.take_caption_index()),
+            ..caption
+        }))
+    }
+
+    /// 分配下一条最终字幕的序号
+    fn take_caption_index(&mut self) -> u64 {
+        let index = self.next_caption;
+        self.next_caption += 1;
+        index
+    }
+
+    /// `translate_caption` 的后处理与翻译部分，生成的字幕尚未分配序号
+    fn build_caption(
+        &mut self,
+        mut text: String,
+        confidence: f32,
+        speaker: Option<u32>,
+        language: Option<&str>,
+        segments: Vec<SegmentConfidence>,
+        words: Vec<WordConfidence>,
+    ) -> Option<Caption> {
+        for processor in &self.post_processors {
+            text = processor.process(&text);
+        }
+        let uncertain = self.min_translate_confidence.is_some_and(|threshold| confidence < threshold);
+        let real_time_factor = f64::from_bits(self.state.real_time_factor.load(Ordering::Relaxed));
+        let throttled = !uncertain
+            && self.translator.is_some()
+            && self
+                .translation_throttle
+                .as_mut()
+                .is_some_and(|throttle| !throttle.should_translate(real_time_factor));
+        let translation = if uncertain {
+            debug!("Skipping translation of low-confidence ({:.2}) transcription: {:?}", confidence, text);
+            Ok(None)
+        } else if throttled {
+            debug!("Translation throttled, showing source only: {:?}", text);
+            Ok(None)
+        } else {
+            self.translate(&text, language)
+        };
+        let translation = match translation {
+            Ok(translation) => translation,
+            Err(e) => {
+                error!("Translation error: {:#}", e);
+                self.state.translation_errors.fetch_add(1, Ordering::Relaxed);
+                self.translate_decision = Some("failed");
+                return None;
+            }
+        };
+        // 译文为空，或与原文相同（如输入本就是中文，或分词器无法处理而原样返回）时只显示原文
+        let translated = translation.is_some();
+        let translation = translation
+            .filter(|t| !t.trim().is_empty() && t.trim() != text.trim())
+            .map(|t| {
+                let t = self
+                    .translation_processors
+                    .iter()
+                    .fold(t, |t, processor| processor.process(&t));
+                self.filter_output(&t)
+            });
+        // 超长输入按句切分后翻译的，在记录中单独标出，便于排查拼接处的译文问题
+        let split = self
+            .translator
+            .as_ref()
+            .and_then(|translator| translator.last_stats())
+            .is_some_and(|stats| stats.pieces > 1);
+        self.translate_decision = Some(match (uncertain, translated, translation.is_some()) {
+            (true, _, _) => "skipped_low_confidence",
+            (false, false, _) if throttled => "throttled",
+            (false, false, _) => "no_translator",
+            (false, true, false) => "unchanged",
+            (false, true, true) if split => "translated_split",
+            (false, true, true) => "translated",
+        });
+        let text = self.filter_output(&text);
+        #[cfg(feature = "pinyin")]
+        let pinyin = translation
+            .as_deref()
+            .filter(|_| self.pinyin)
+            .map(crate::romanize::to_pinyin);
+        #[cfg(not(feature = "pinyin"))]
+        let pinyin = None;
+        Some(Caption {
+            index: None,
+            timestamp: Local::now(),
+            source: text,
+            translation,
+            pinyin,
+            confidence: Some(confidence),
+            speaker,
+            segments,
+            words,
+            unclear: Vec::new(),
+        })
+    }
+
+    /// 按源语言翻译文本；没有翻译器时返回 `Ok(None)`
+    fn translate(&mut self, text: &str, language: Option<&str>) -> anyhow::Result<Option<String>> {
+        let Some(translator) = self.translator.as_mut() else {
+            return Ok(None);
+        };
+        let started = Instant::now();
+        let result = translator.translate_from(language, text);
+        self.state.record_translate(started.elapsed());
+        if let Some(stats) = translator.last_stats() {
+            self.state.record_decode(stats);
+        }
+        result.map(Some)
+    }
+}
+
+/// 只含原文的临时字幕
+fn partial_event(source: String) -> CaptionEvent {
+    CaptionEvent::Partial(Caption {
+        index: None,
+        timestamp: Local::now(),
+        source,
+        translation: None,
+        pinyin: None,
+        confidence: None,
+        speaker: None,
+        segments: Vec::new(),
+        words: Vec::new(),
+        unclear: Vec::new(),
+    })
+}
+
+/// 转录线程中重新加载 Whisper 模型（如 GPU 出错后上下文已不可用），失败时保留原模型待下次重试
+fn reload_whisper(whisper: &Mutex<Whisper>, state: &PipelineState) {
+    let mut whisper = whisper.lock().unwrap();
+    match whisper.reload() {
+        Ok(()) => {
+            state.whisper_reloads.fetch_add(1, Ordering::Relaxed);
+            state.gpu_active.store(whisper.uses_gpu(), Ordering::Relaxed);
+        }
+        Err(e) => error!("{:#}", e),
+    }
+}
+
+/// Whisper 只接受 16k 音频，流水线的采集配置不能改用其他采样率；需要其他采样率的音频时用 `CaptureConfig::tap`
+fn ensure_whisper_rate(config: &CaptureConfig) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        config.target_sample_rate == 16_000,
+        "the transcription pipeline requires a 16000 Hz capture rate, got {}",
+        config.target_sample_rate
+    );
+    Ok(())
+}
+
+/// 转录结果是否不含语音：空白，或 Whisper 的静音/音乐标记
+fn is_non_speech(text: &str) -> bool {
+    let text = text.trim();
+    text.is_empty() || text == "[BLANK_AUDIO]" || text == "[Music]"
+}
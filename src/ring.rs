@@ -0,0 +1,95 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// 单生产者单消费者的无锁环形缓冲区，供实时音频回调使用：
+/// 写入端不加锁、不分配内存，读取端在普通线程中取出数据。
+pub fn ring_buffer(capacity: usize) -> (Producer, Consumer) {
+    let shared = Arc::new(Shared {
+        buffer: (0..capacity.max(1)).map(|_| UnsafeCell::new(0.0)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            shared: Arc::clone(&shared),
+        },
+        Consumer { shared },
+    )
+}
+
+struct Shared {
+    buffer: Box<[UnsafeCell<f32>]>,
+    /// 下一个待读位置，只由消费者推进
+    head: AtomicUsize,
+    /// 下一个待写位置，只由生产者推进
+    tail: AtomicUsize,
+}
+
+// SAFETY: 生产者只写 [tail, head + capacity) 区间，消费者只读 [head, tail) 区间，
+// 两者通过 head/tail 的 Acquire/Release 同步，不会同时访问同一个元素。
+// `Producer`、`Consumer` 均不可克隆，保证各只有一个。
+unsafe impl Sync for Shared {}
+
+impl Shared {
+    fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+/// 写入端，放在音频回调中使用
+pub struct Producer {
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    /// 写入全部 `data` 并返回 `true`；空间不足时不写入任何数据并返回 `false`，
+    /// 保证交错排列的多声道数据不会被截断在帧中间
+    pub fn push_slice(&mut self, data: &[f32]) -> bool {
+        let shared = &*self.shared;
+        let head = shared.head.load(Ordering::Acquire);
+        let tail = shared.tail.load(Ordering::Relaxed);
+        let free = shared.capacity() - tail.wrapping_sub(head);
+        if data.len() > free {
+            return false;
+        }
+        for (i, &sample) in data.iter().enumerate() {
+            let index = tail.wrapping_add(i) % shared.capacity();
+            // SAFETY: 该位置不在消费者可读的区间内，见 `Shared` 的说明
+            unsafe { *shared.buffer[index].get() = sample };
+        }
+        shared.tail.store(tail.wrapping_add(data.len()), Ordering::Release);
+        true
+    }
+}
+
+/// 读取端
+pub struct Consumer {
+    shared: Arc<Shared>,
+}
+
+impl Consumer {
+    /// 取出当前可读的全部数据，长度向下取整为 `multiple` 的整数倍（如声道数）
+    pub fn pop_all(&mut self, multiple: usize) -> Vec<f32> {
+        let shared = &*self.shared;
+        let tail = shared.tail.load(Ordering::Acquire);
+        let head = shared.head.load(Ordering::Relaxed);
+        let available = tail.wrapping_sub(head);
+        let len = available / multiple.max(1) * multiple.max(1);
+        let samples = (0..len)
+            .map(|i| {
+                let index = head.wrapping_add(i) % shared.capacity();
+                // SAFETY: 该位置已由生产者写入且尚未被读取，见 `Shared` 的说明
+                unsafe { *shared.buffer[index].get() }
+            })
+            .collect();
+        shared.head.store(head.wrapping_add(len), Ordering::Release);
+        samples
+    }
+
+    /// 丢弃当前可读的全部数据
+    pub fn clear(&mut self) {
+        let tail = self.shared.tail.load(Ordering::Acquire);
+        self.shared.head.store(tail, Ordering::Release);
+    }
+}
@@ -0,0 +1,28 @@
+use pinyin::ToPinyin;
+
+/// 为中文文本标注带声调的拼音，如 "你好，世界" -> "nǐ hǎo， shì jiè"。
+/// 汉字之间以空格分隔，非汉字字符（标点、英文、数字）原样保留
+pub fn to_pinyin(text: &str) -> String {
+    let mut result = String::new();
+    // 上一个输出的是否是拼音，用于在拼音与其后的内容之间补空格
+    let mut after_pinyin = false;
+    for c in text.chars() {
+        match c.to_pinyin() {
+            Some(pinyin) => {
+                if !result.is_empty() && !result.ends_with(' ') {
+                    result.push(' ');
+                }
+                result.push_str(pinyin.with_tone());
+                after_pinyin = true;
+            }
+            None => {
+                if after_pinyin && c.is_alphanumeric() {
+                    result.push(' ');
+                }
+                result.push(c);
+                after_pinyin = false;
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+/// 英文句末标点，其后为空白或文本结尾时视为一句话结束
+const SENTENCE_END: [char; 3] = ['.', '!', '?'];
+/// 全角句末标点，后面通常不跟空格，出现即视为一句话结束
+const FULLWIDTH_SENTENCE_END: [char; 3] = ['。', '！', '？'];
+/// 句末标点后可能紧跟的右引号、右括号，算作同一句
+const CLOSING: [char; 9] = ['"', '\'', ')', ']', '”', '’', '」', '』', '）'];
+
+/// 后面的句点不表示句子结束的英文缩写（不含句点，小写）。"etc." 常出现在句末，不在其中
+const DEFAULT_ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "mt", "vs", "e.g", "i.e", "cf", "inc", "ltd", "co", "corp",
+    "no", "fig", "approx", "dept", "est", "jan", "feb", "mar", "apr", "jun", "jul", "aug", "sep", "sept", "oct",
+    "nov", "dec", "a.m", "p.m", "u.s", "u.k",
+];
+
+/// 文本中的一个句子，`start..end` 为它在原文中的字节范围，不含首尾空白
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SentenceSpan {
+    pub start: usize,
+    pub end: usize,
+    /// 是否以句末标点结束；文本末尾尚未说完的半句为 `false`
+    pub complete: bool,
+}
+
+impl SentenceSpan {
+    /// 该句在 `text` 中的文本
+    #[allow(dead_code)]
+    pub fn as_str<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.start..self.end]
+    }
+}
+
+/// 分句器：整句缓冲、长文本切分等按句子处理文本的功能都通过它找句子边界，
+/// 调用方可以换成自己的实现（如针对其他语言的规则）
+pub trait SentenceSplitter: Send {
+    /// 把 `text` 切分为句子，按出现顺序返回，覆盖全部非空白文本
+    fn split(&self, text: &str) -> Vec<SentenceSpan>;
+}
+
+/// 默认分句器：英文按 `. ! ?` 后跟空白或文本结尾断句，跳过 "Dr."、"e.g." 等缩写和 "J. Smith" 一类的人名缩写；
+/// 中文按 `。！？` 断句，不要求后面有空格。句末标点后的右引号、右括号归入同一句
+pub struct DefaultSplitter {
+    abbreviations: HashSet<String>,
+}
+
+impl Default for DefaultSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DefaultSplitter {
+    pub fn new() -> Self {
+        Self {
+            abbreviations: DEFAULT_ABBREVIATIONS.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    /// 追加不断句的缩写，写法如 "Dr." 或 "dr"，不区分大小写
+    #[allow(dead_code)]
+    pub fn with_abbreviations<I, S>(mut self, abbreviations: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.abbreviations.extend(
+            abbreviations
+                .into_iter()
+                .map(|a| a.as_ref().trim().trim_end_matches('.').to_lowercase())
+                .filter(|a| !a.is_empty()),
+        );
+        self
+    }
+
+    /// `text[..dot]` 末尾的词加上位于 `dot` 的句点是否为缩写
+    fn is_abbreviation(&self, text: &str, dot: usize) -> bool {
+        let word_start = text[..dot]
+            .rfind(|c: char| c.is_whitespace() || c == '(' || c == '"')
+            .map_or(0, |i| i + 1);
+        let word = &text[word_start..dot];
+        // 单个大写字母视为人名缩写，如 "J. Smith"
+        let mut chars = word.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_uppercase() {
+                return true;
+            }
+        }
+        self.abbreviations.contains(&word.to_lowercase())
+    }
+}
+
+impl SentenceSplitter for DefaultSplitter {
+    fn split(&self, text: &str) -> Vec<SentenceSpan> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        let mut chars = text.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            let fullwidth = FULLWIDTH_SENTENCE_END.contains(&c);
+            if !fullwidth && !SENTENCE_END.contains(&c) {
+                continue;
+            }
+            // 连续的句末标点（如 "?!"、"..."）和其后的右引号、右括号并入同一句
+            let mut end = index + c.len_utf8();
+            while let Some(&(next_index, next)) = chars.peek() {
+                if SENTENCE_END.contains(&next) || FULLWIDTH_SENTENCE_END.contains(&next) || CLOSING.contains(&next) {
+                    end = next_index + next.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let at_boundary = text[end..].chars().next().is_none_or(char::is_whitespace);
+            let ends_sentence = fullwidth || (at_boundary && !(c == '.' && self.is_abbreviation(text, index)));
+            if ends_sentence {
+                push_span(&mut spans, text, start, end, true);
+                start = end;
+            }
+        }
+        push_span(&mut spans, text, start, text.len(), false);
+        spans
+    }
+}
+
+/// 去掉 `text[start..end]` 首尾的空白后加入 `spans`，全为空白时跳过
+fn push_span(spans: &mut Vec<SentenceSpan>, text: &str, start: usize, end: usize, complete: bool) {
+    let slice = &text[start..end];
+    let trimmed = slice.trim_start();
+    let start = start + (slice.len() - trimmed.len());
+    let end = start + trimmed.trim_end().len();
+    if end > start {
+        spans.push(SentenceSpan { start, end, complete });
+    }
+}
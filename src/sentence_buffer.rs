@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+use crate::sentence::{DefaultSplitter, SentenceSplitter};
+
+/// 翻译前的整句缓冲：把转录出的半句话暂存起来，凑成以句末标点结尾的完整句子后再交给翻译器，
+/// 避免逐块翻译出支离破碎的中文。缓冲超过 `max_hold` 仍未成句时整体放出，保证延迟有上限。
+pub struct SentenceBuffer {
+    max_hold: Duration,
+    pending: Option<Pending>,
+    splitter: Box<dyn SentenceSplitter>,
+}
+
+/// 缓冲中尚未成句的文本
+struct Pending {
+    text: String,
+    /// 组成这段文本的各次转录的置信度之和与个数，放出时取平均
+    confidence_sum: f32,
+    pieces: usize,
+    speaker: Option<u32>,
+    language: Option<String>,
+    /// 第一段文本进入缓冲的时间
+    since: Instant,
+}
+
+/// 缓冲放出的一段待翻译文本
+#[derive(Debug, Clone)]
+pub struct Sentence {
+    pub text: String,
+    /// 组成该句的各次转录置信度的平均值
+    pub confidence: f32,
+    pub speaker: Option<u32>,
+    /// 转录检测到的源语言，决定交给哪个翻译器
+    pub language: Option<String>,
+}
+
+impl Pending {
+    fn new(text: &str, confidence: f32, speaker: Option<u32>, language: Option<&str>) -> Self {
+        Self {
+            text: text.to_string(),
+            confidence_sum: confidence,
+            pieces: 1,
+            speaker,
+            language: language.map(str::to_string),
+            since: Instant::now(),
+        }
+    }
+
+    fn into_sentence(self) -> Sentence {
+        Sentence {
+            text: self.text,
+            confidence: self.confidence_sum / self.pieces as f32,
+            speaker: self.speaker,
+            language: self.language,
+        }
+    }
+}
+
+impl SentenceBuffer {
+    pub fn new(max_hold: Duration) -> Self {
+        Self {
+            max_hold,
+            pending: None,
+            splitter: Box::new(DefaultSplitter::new()),
+        }
+    }
+
+    /// 替换判断句子边界的分句器，默认为 `DefaultSplitter`
+    #[allow(dead_code)]
+    pub fn with_splitter(mut self, splitter: Box<dyn SentenceSplitter>) -> Self {
+        self.splitter = splitter;
+        self
+    }
+
+    /// 加入一次转录的文本，返回已凑成完整句子、可以翻译的部分（可能为空）。
+    /// 说话人或语言变化时先放出之前未说完的部分。
+    pub fn push(&mut self, text: &str, confidence: f32, speaker: Option<u32>, language: Option<&str>) -> Vec<Sentence> {
+        let text = text.trim();
+        let mut ready = Vec::new();
+        if text.is_empty() {
+            return ready;
+        }
+        let changed = |pending: &Pending| pending.speaker != speaker || pending.language.as_deref() != language;
+        if self.pending.as_ref().is_some_and(changed) {
+            ready.extend(self.flush());
+        }
+        let pending = match self.pending.as_mut() {
+            Some(pending) => {
+                pending.text.push(' ');
+                pending.text.push_str(text);
+                pending.confidence_sum += confidence;
+                pending.pieces += 1;
+                pending
+            }
+            None => self.pending.insert(Pending::new(text, confidence, speaker, language)),
+        };
+        let last_end = self
+            .splitter
+            .split(&pending.text)
+            .iter()
+            .rev()
+            .find(|span| span.complete)
+            .map(|span| span.end);
+        if let Some(end) = last_end {
+            let remainder = pending.text[end..].trim().to_string();
+            pending.text.truncate(end);
+            let complete = self.pending.take().unwrap();
+            if !remainder.is_empty() {
+                // 剩余的半句来自最新这次转录，沿用它的置信度，并从现在开始计时
+                self.pending = Some(Pending::new(&remainder, confidence, speaker, language));
+            }
+            ready.push(complete.into_sentence());
+        }
+        ready
+    }
+
+    /// 缓冲的文本超过 `max_hold` 仍未成句时，整体放出
+    pub fn take_expired(&mut self) -> Option<Sentence> {
+        if self.pending.as_ref()?.since.elapsed() < self.max_hold {
+            return None;
+        }
+        self.flush()
+    }
+
+    /// 放出缓冲中剩余的全部文本，用于分段或停止时
+    pub fn flush(&mut self) -> Option<Sentence> {
+        self.pending.take().map(Pending::into_sentence)
+    }
+
+    /// 缓冲中尚未翻译的文本
+    pub fn pending_text(&self) -> Option<&str> {
+        self.pending.as_ref().map(|pending| pending.text.as_str())
+    }
+}
@@ -0,0 +1,233 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use log::{debug, error, info};
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::capture::load_wav_bytes;
+use crate::transcribe::Whisper;
+use crate::translation::Translate;
+
+/// 上传文件的大小上限（512 MB，16 位立体声 44.1k 约 50 分钟）
+const MAX_UPLOAD_BYTES: usize = 512 * 1024 * 1024;
+/// 每次送入 Whisper 的音频长度（秒），与模型的输入窗口一致
+const WINDOW_SECS: usize = 30;
+
+/// 上传超过 `MAX_UPLOAD_BYTES`，单独区分出来以返回 413 而不是 400
+#[derive(Debug)]
+struct UploadTooLarge;
+
+impl std::fmt::Display for UploadTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "upload exceeds {} MB", MAX_UPLOAD_BYTES / 1024 / 1024)
+    }
+}
+
+impl std::error::Error for UploadTooLarge {}
+
+/// `POST /transcribe` 的返回结果
+#[derive(Debug, Serialize)]
+struct TranscribeResponse {
+    /// 音频时长（秒）
+    duration: f64,
+    /// 检测到的语言（按第一个有语音的窗口）；指定了 `--language` 时为该语言
+    language: Option<String>,
+    segments: Vec<SegmentResult>,
+    /// 处理耗时（秒）
+    elapsed: f64,
+}
+
+/// 一个转录片段，时间为相对音频开头的秒数
+#[derive(Debug, Serialize)]
+struct SegmentResult {
+    start: f64,
+    end: f64,
+    text: String,
+    /// 译文；未加载翻译模型、翻译失败或译文与原文相同时为 `None`
+    translation: Option<String>,
+    confidence: Option<f32>,
+}
+
+/// 启动 HTTP 转录服务并阻塞运行。`POST /transcribe` 接收一个 WAV 文件（`multipart/form-data`
+/// 上传，或直接作为请求体），整段转录并翻译后以 JSON 返回各片段的时间、原文和译文。
+///
+/// 开 `workers` 个线程并行处理请求，各自持有一个在同一模型上下文上 `fork` 出的转录状态，
+/// 模型只加载一份；翻译器只有一个，各请求依次使用。
+pub fn run(addr: &str, whisper: Whisper, translator: Option<Box<dyn Translate>>, workers: usize) -> anyhow::Result<()> {
+    let server = Server::http(addr).map_err(|e| anyhow::anyhow!("failed to bind {}: {}", addr, e))?;
+    let server = Arc::new(server);
+    let translator = translator.map(|translator| Arc::new(Mutex::new(translator)));
+    let workers = workers.max(1);
+    let mut states = Vec::with_capacity(workers);
+    for _ in 1..workers {
+        states.push(whisper.fork()?);
+    }
+    states.push(whisper);
+    info!("Transcription service listening on http://{}/transcribe ({} workers)", addr, workers);
+
+    let handles: Vec<_> = states
+        .into_iter()
+        .map(|mut whisper| {
+            let server = Arc::clone(&server);
+            let translator = translator.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(request, &mut whisper, translator.as_deref());
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    Ok(())
+}
+
+fn handle(mut request: Request, whisper: &mut Whisper, translator: Option<&Mutex<Box<dyn Translate>>>) {
+    let response = match (request.method(), request.url()) {
+        (Method::Post, "/transcribe") => match transcribe_request(&mut request, whisper, translator) {
+            Ok(result) => match serde_json::to_string(&result) {
+                Ok(body) => json_response(body, 200),
+                Err(e) => error_response(&e.to_string(), 500),
+            },
+            Err(e) if e.is::<UploadTooLarge>() => error_response(&e.to_string(), 413),
+            Err(e) => error_response(&format!("{:#}", e), 400),
+        },
+        (_, "/transcribe") => error_response("use POST", 405),
+        _ => error_response("not found", 404),
+    };
+    if let Err(e) = request.respond(response) {
+        error!("Failed to respond to transcription request: {}", e);
+    }
+}
+
+/// 读取上传的音频，按 30 秒的窗口依次转录，并逐段翻译
+fn transcribe_request(
+    request: &mut Request,
+    whisper: &mut Whisper,
+    translator: Option<&Mutex<Box<dyn Translate>>>,
+) -> anyhow::Result<TranscribeResponse> {
+    let started = Instant::now();
+    // 声明的长度超限时不读请求体；分块上传没有长度，读到超出上限为止
+    if request.body_length().is_some_and(|length| length > MAX_UPLOAD_BYTES) {
+        return Err(UploadTooLarge.into());
+    }
+    let mut body = Vec::new();
+    request.as_reader().take(MAX_UPLOAD_BYTES as u64 + 1).read_to_end(&mut body)?;
+    if body.len() > MAX_UPLOAD_BYTES {
+        return Err(UploadTooLarge.into());
+    }
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Content-Type"))
+        .map(|header| header.value.as_str().to_string())
+        .unwrap_or_default();
+    let audio = match multipart_boundary(&content_type) {
+        Some(boundary) => multipart_file(&body, &boundary).ok_or_else(|| anyhow::anyhow!("no file in multipart upload"))?,
+        None => &body[..],
+    };
+    let samples = load_wav_bytes(audio)?;
+    debug!("Transcribing uploaded audio ({} samples)", samples.len());
+
+    let mut language = None;
+    let mut segments = Vec::new();
+    for (window, chunk) in samples.chunks(16_000 * WINDOW_SECS).enumerate() {
+        let Some(transcription) = whisper.transcribe(chunk.to_vec()) else {
+            continue;
+        };
+        let offset = (window * WINDOW_SECS) as f64;
+        for segment in transcription.segments {
+            let text = segment.text.trim();
+            // 空白和 "[BLANK_AUDIO]"、"[Music]" 等非语音标记不返回
+            if text.is_empty() || (text.starts_with('[') && text.ends_with(']')) {
+                continue;
+            }
+            if language.is_none() {
+                language = transcription.language.clone();
+            }
+            let translation = translator.and_then(|translator| {
+                match translator.lock().unwrap().translate_from(transcription.language.as_deref(), text) {
+                    Ok(translation) => Some(translation).filter(|t| !t.trim().is_empty() && t.trim() != text),
+                    Err(e) => {
+                        error!("Translation error: {:#}", e);
+                        None
+                    }
+                }
+            });
+            segments.push(SegmentResult {
+                start: offset + segment.start_ms as f64 / 1000.0,
+                end: offset + segment.end_ms as f64 / 1000.0,
+                text: text.to_string(),
+                translation,
+                confidence: segment.confidence(),
+            });
+        }
+    }
+    if language.is_none() && whisper.language() != "auto" {
+        language = Some(whisper.language().to_string());
+    }
+    Ok(TranscribeResponse {
+        duration: samples.len() as f64 / 16_000.0,
+        language,
+        segments,
+        elapsed: started.elapsed().as_secs_f64(),
+    })
+}
+
+/// `multipart/form-data` 的分隔符，不是 multipart 请求时返回 `None`
+fn multipart_boundary(content_type: &str) -> Option<String> {
+    let (kind, params) = content_type.split_once(';')?;
+    if !kind.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim_matches('"').to_string())
+}
+
+/// 取出 multipart 请求体中的文件：优先取带 `filename` 的部分，其次是名为 `file` 的字段
+fn multipart_file<'a>(body: &'a [u8], boundary: &str) -> Option<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut position = find(body, delimiter.as_bytes(), 0)? + delimiter.len();
+    while let Some(next) = find(body, delimiter.as_bytes(), position) {
+        let part = &body[position..next];
+        // 每个部分以换行开头，头部与内容之间空一行，内容后紧跟换行再接分隔符
+        let part = part.strip_prefix(b"\r\n").unwrap_or(part);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        if let Some(split) = find(part, b"\r\n\r\n", 0) {
+            let headers = String::from_utf8_lossy(&part[..split]).to_lowercase();
+            parts.push((headers, &part[split + 4..]));
+        }
+        position = next + delimiter.len();
+    }
+    parts
+        .iter()
+        .find(|(headers, _)| headers.contains("filename="))
+        .or_else(|| parts.iter().find(|(headers, _)| headers.contains("name=\"file\"")))
+        .map(|(_, content)| *content)
+}
+
+/// `haystack[from..]` 中第一次出现 `needle` 的位置
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|index| from + index)
+}
+
+fn json_response(body: String, status: u16) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
+fn error_response(message: &str, status: u16) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(serde_json::json!({ "error": message }).to_string(), status)
+}
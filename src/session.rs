@@ -0,0 +1,183 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use log::info;
+
+use crate::sink::{write_transcript_event, Caption, CaptionEvent, SESSION_HEADER_PREFIX, TRANSCRIPT_TIME_FORMAT};
+use crate::translation::Translate;
+
+/// 文稿中的一行会话头及其位置（之前的事件数）
+type SessionHeader = (usize, String);
+
+/// 已保存的会话文稿（`--transcript` 的输出），用于事后逐条校对：
+/// 修改原文、重新翻译单条字幕，每次修改后立即写回文件，无需重新运行 Whisper。
+#[allow(dead_code)]
+pub struct Session {
+    path: PathBuf,
+    /// 文稿中的事件，只含 `Final` 和 `ParagraphBreak`
+    events: Vec<CaptionEvent>,
+    /// 会话头，保存时原样写回
+    session_headers: Vec<SessionHeader>,
+    translator: Box<dyn Translate>,
+}
+
+#[allow(dead_code)]
+impl Session {
+    /// 读取文稿文件。文稿中不含置信度和片段信息，读出的字幕这两项为空
+    pub fn load(path: impl AsRef<Path>, translator: Box<dyn Translate>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read transcript {}", path.display()))?;
+        let (events, session_headers) = parse_transcript(&content)
+            .with_context(|| format!("Failed to parse transcript {}", path.display()))?;
+        let session = Self {
+            path: path.to_path_buf(),
+            events,
+            session_headers,
+            translator,
+        };
+        info!("Loaded {} captions from {}", session.len(), path.display());
+        Ok(session)
+    }
+
+    /// 字幕条数
+    pub fn len(&self) -> usize {
+        self.captions().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 按顺序遍历全部字幕
+    pub fn captions(&self) -> impl Iterator<Item = &Caption> {
+        self.events.iter().filter_map(|event| match event {
+            CaptionEvent::Final(caption) => Some(caption),
+            _ => None,
+        })
+    }
+
+    /// 第 `index` 条字幕
+    pub fn get(&self, index: usize) -> Option<&Caption> {
+        self.captions().nth(index)
+    }
+
+    /// 用当前原文重新翻译第 `index` 条字幕并写回文件。
+    /// 与实时字幕一致，译文为空或与原文相同（如原文本就是中文）时不保存译文，文稿中只留原文
+    pub fn retranslate(&mut self, index: usize) -> Result<()> {
+        let source = self.caption_mut(index)?.source.clone();
+        let translation = self.translator.translate(&source)?;
+        let translation = Some(translation.trim().to_string()).filter(|t| !t.is_empty() && t != source.trim());
+        self.caption_mut(index)?.translation = translation;
+        self.save()
+    }
+
+    /// 把第 `index` 条字幕的原文改为 `text`，重新翻译并写回文件
+    pub fn edit_text(&mut self, index: usize, text: &str) -> Result<()> {
+        self.caption_mut(index)?.source = text.trim().to_string();
+        self.retranslate(index)
+    }
+
+    fn caption_mut(&mut self, index: usize) -> Result<&mut Caption> {
+        let count = self.len();
+        self.events
+            .iter_mut()
+            .filter_map(|event| match event {
+                CaptionEvent::Final(caption) => Some(caption),
+                _ => None,
+            })
+            .nth(index)
+            .ok_or_else(|| anyhow!("Caption index {} out of range (0..{})", index, count))
+    }
+
+    /// 先写临时文件再替换，避免写到一半时中断损坏原文稿
+    fn save(&self) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut writer = BufWriter::new(
+            File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?,
+        );
+        let mut headers = self.session_headers.iter().peekable();
+        for (index, event) in self.events.iter().enumerate() {
+            while let Some((_, header)) = headers.next_if(|(position, _)| *position <= index) {
+                writeln!(writer, "{}", header)?;
+            }
+            write_transcript_event(&mut writer, event)?;
+        }
+        for (_, header) in headers {
+            writeln!(writer, "{}", header)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// 解析 `TranscriptSink` 写出的文稿：每条字幕为 "[时间] 原文"、可选的一行译文和一个空行，
+/// 多出的空行为分段标记。会话头与它之前的事件数一并返回
+fn parse_transcript(content: &str) -> Result<(Vec<CaptionEvent>, Vec<SessionHeader>)> {
+    let mut events = Vec::new();
+    let mut headers = Vec::new();
+    let mut pending: Option<Caption> = None;
+    for (number, line) in content.lines().enumerate() {
+        if line.starts_with(SESSION_HEADER_PREFIX) {
+            if let Some(caption) = pending.take() {
+                events.push(CaptionEvent::Final(caption));
+            }
+            headers.push((events.len(), line.to_string()));
+        } else if line.trim().is_empty() {
+            match pending.take() {
+                Some(caption) => events.push(CaptionEvent::Final(caption)),
+                None => events.push(CaptionEvent::ParagraphBreak),
+            }
+        } else if let Some(caption) = parse_header(line) {
+            if let Some(previous) = pending.replace(caption?) {
+                events.push(CaptionEvent::Final(previous));
+            }
+        } else {
+            match pending.as_mut() {
+                Some(caption) if caption.translation.is_none() => {
+                    caption.translation = Some(line.to_string());
+                }
+                _ => bail!("Unexpected line {}: {}", number + 1, line),
+            }
+        }
+    }
+    if let Some(caption) = pending {
+        events.push(CaptionEvent::Final(caption));
+    }
+    Ok((events, headers))
+}
+
+/// 解析字幕首行 "[2024-01-01 12:00:00] Speaker 1: 原文"；不是首行格式时返回 `None`
+fn parse_header(line: &str) -> Option<Result<Caption>> {
+    let rest = line.strip_prefix('[')?;
+    let (time, text) = rest.split_once("] ")?;
+    let naive = NaiveDateTime::parse_from_str(time, TRANSCRIPT_TIME_FORMAT).ok()?;
+    let timestamp = match Local.from_local_datetime(&naive).earliest() {
+        Some(timestamp) => timestamp,
+        None => return Some(Err(anyhow!("Invalid local time: {}", time))),
+    };
+    let (speaker, source) = text
+        .strip_prefix("Speaker ")
+        .and_then(|rest| rest.split_once(": "))
+        .and_then(|(id, source)| Some((Some(id.parse().ok()?), source)))
+        .unwrap_or((None, text));
+    Some(Ok(Caption {
+        index: None,
+        timestamp,
+        source: source.to_string(),
+        translation: None,
+        pinyin: None,
+        confidence: None,
+        speaker,
+        segments: Vec::new(),
+        words: Vec::new(),
+        unclear: Vec::new(),
+    }))
+}
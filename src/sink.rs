@@ -0,0 +1,542 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use log::{info, error};
+use serde::Serialize;
+
+use crate::pipeline::CAPTIONS_TARGET;
+
+/// 一条完成转录（及翻译）的字幕
+#[derive(Debug, Clone, Serialize)]
+pub struct Caption {
+    /// 本次运行中最终字幕的序号（从 0 开始），`Revision` 据此指出修订的是哪一条；
+    /// 临时字幕和从文稿读回的字幕为 `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+    /// 字幕产生的时间
+    pub timestamp: DateTime<Local>,
+    /// 转录得到的原文
+    pub source: String,
+    /// 译文；未翻译时为 `None`
+    pub translation: Option<String>,
+    /// 译文的拼音标注，启用 `--pinyin` 时才有
+    pub pinyin: Option<String>,
+    /// 转录置信度（0~1），由 Whisper 的平均 token 对数概率换算而来
+    pub confidence: Option<f32>,
+    /// 说话人编号，由 `SpeakerLabeler` 给出；未标注时为 `None`
+    pub speaker: Option<u32>,
+    /// 原文各片段的置信度，可用于逐段着色；没有片段信息时为空
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<SegmentConfidence>,
+    /// 原文的逐词时间与概率，启用 `--word-confidence` 时才有；与 `segments` 一样未经后处理
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<WordConfidence>,
+    /// 被替换为占位符的低置信度原文，启用 `--keep-unclear-guess` 时才有，供人工复核
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unclear: Vec<String>,
+}
+
+/// 原文中一个片段及其置信度
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentConfidence {
+    pub text: String,
+    /// 0~1，越大越可信
+    pub confidence: f32,
+}
+
+/// 原文中的一个词。时间为本次会话音频时间轴上的毫秒数
+#[derive(Debug, Clone, Serialize)]
+pub struct WordConfidence {
+    pub text: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    /// 0~1，越大越可信
+    pub prob: f32,
+}
+
+impl Caption {
+    /// 带说话人前缀的原文，如 "Speaker 1: Hello"；未标注时即原文
+    pub fn labeled_source(&self) -> String {
+        match self.speaker {
+            Some(speaker) => format!("Speaker {}: {}", speaker, self.source),
+            None => self.source.clone(),
+        }
+    }
+}
+
+/// 终端显示（`ScrollingDisplay`、全屏界面）中每条字幕显示哪些内容。
+/// 日志、文稿和 JSON 等记录类输出端不受影响，始终保留原文和译文
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayContent {
+    /// 原文和译文都显示
+    #[default]
+    Both,
+    /// 只显示原文。此时不加载翻译模型，省去翻译开销
+    SourceOnly,
+    /// 只显示译文；没有译文的字幕（如原文本就是中文）仍显示原文
+    TargetOnly,
+}
+
+impl DisplayContent {
+    /// 是否显示原文，`has_translation` 为该字幕是否有译文
+    pub fn shows_source(self, has_translation: bool) -> bool {
+        self != DisplayContent::TargetOnly || !has_translation
+    }
+
+    /// 是否显示译文
+    pub fn shows_target(self) -> bool {
+        self != DisplayContent::SourceOnly
+    }
+}
+
+/// 流水线向输出端发出的事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptionEvent {
+    /// 一条最终字幕
+    Final(Caption),
+    /// 临时字幕：滑动窗口中尚未稳定的文本，会被后续的 `Partial` 或 `Final` 取代。
+    /// 没有译文；`source` 为空表示当前没有待定文本。
+    Partial(Caption),
+    /// 长时间静音后的分段标记（说话人停顿或换了话题）
+    ParagraphBreak,
+    /// 对之前一条最终字幕的修订：滑动窗口中之后的转录改正了原文，重新翻译后整条替换。
+    /// `index` 与被修订的字幕相同
+    Revision(Caption),
+}
+
+/// 字幕输出端。流水线会把每个事件依次交给所有已注册且需要该类事件的输出端。
+pub trait CaptionSink: Send {
+    fn emit(&mut self, event: &CaptionEvent);
+
+    /// 是否接收临时字幕（`Partial`）。文稿一类只记录定稿内容的输出端应返回 `false`
+    fn wants_partial(&self) -> bool {
+        true
+    }
+
+    /// 是否接收最终字幕（`Final`）、分段标记和修订
+    fn wants_final(&self) -> bool {
+        true
+    }
+
+    /// 该输出端是否需要 `event`
+    fn wants(&self, event: &CaptionEvent) -> bool {
+        match event {
+            CaptionEvent::Partial(_) => self.wants_partial(),
+            CaptionEvent::Final(_) | CaptionEvent::ParagraphBreak | CaptionEvent::Revision(_) => self.wants_final(),
+        }
+    }
+}
+
+/// 按配置过滤事件类型的输出端包装，用于让原本接收全部事件的输出端只收最终字幕或只收临时字幕
+pub struct RoutedSink {
+    inner: Box<dyn CaptionSink>,
+    partial: bool,
+    final_: bool,
+}
+
+impl RoutedSink {
+    /// `partial`/`final_` 为是否转发对应的事件；内层输出端本身不需要的事件仍不会转发
+    pub fn new(inner: Box<dyn CaptionSink>, partial: bool, final_: bool) -> Self {
+        Self { inner, partial, final_ }
+    }
+}
+
+impl CaptionSink for RoutedSink {
+    fn emit(&mut self, event: &CaptionEvent) {
+        self.inner.emit(event);
+    }
+
+    fn wants_partial(&self) -> bool {
+        self.partial && self.inner.wants_partial()
+    }
+
+    fn wants_final(&self) -> bool {
+        self.final_ && self.inner.wants_final()
+    }
+}
+
+/// 把最终字幕写到字幕专用的日志 target，由 `captions.log` 接收
+pub struct LogSink;
+
+impl CaptionSink for LogSink {
+    fn emit(&mut self, event: &CaptionEvent) {
+        let CaptionEvent::Final(caption) = event else {
+            return;
+        };
+        info!(
+            target: CAPTIONS_TARGET,
+            "{}\t{}",
+            caption.source,
+            caption.translation.as_deref().unwrap_or("")
+        );
+    }
+
+    fn wants_partial(&self) -> bool {
+        false
+    }
+}
+
+/// 把字幕追加到纯文本文稿文件：每条字幕一行原文、一行译文、一个空行。
+///
+/// 每条字幕写完即刷新到文件，并定期 `sync_all` 落盘，程序崩溃时最多丢失最近几秒的字幕。
+/// 每次启动时先写一行会话头（见 `SESSION_HEADER_PREFIX`），续写时沿用原会话的 id。
+pub struct TranscriptSink {
+    writer: BufWriter<File>,
+    session_id: String,
+    last_sync: Instant,
+}
+
+/// 文稿定期落盘的间隔
+const TRANSCRIPT_SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 会话头的前缀。会话头占一行："# session <id> <started|resumed> <时间> <模型与配置>"
+pub const SESSION_HEADER_PREFIX: &str = "# session ";
+
+impl TranscriptSink {
+    /// 打开（必要时创建）文稿并写入会话头。`resume` 为真且文稿中已有会话时沿用最后一个会话的 id，
+    /// 否则以当前时间为 id 开始新会话。`details` 记录在会话头中，如所用的模型和语言
+    pub fn create(path: &str, resume: bool, details: &str) -> io::Result<Self> {
+        let existing = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let previous = last_session_id(&existing);
+        if let (Some(id), false) = (&previous, resume) {
+            info!("Transcript {} already contains session {}; pass --resume to continue it", path, id);
+        }
+        let (session_id, action) = match previous {
+            Some(id) if resume => (id, "resumed"),
+            _ => (Local::now().format("%Y%m%d-%H%M%S").to_string(), "started"),
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+        // 上次中途退出时最后一条字幕可能没写完整，先补齐空行，使会话头单独成段
+        if !existing.is_empty() && !existing.ends_with("\n\n") {
+            let missing = if existing.ends_with('\n') { "\n" } else { "\n\n" };
+            writer.write_all(missing.as_bytes())?;
+        }
+        writeln!(
+            writer,
+            "{}{} {} {} {}",
+            SESSION_HEADER_PREFIX,
+            session_id,
+            action,
+            Local::now().format(TRANSCRIPT_TIME_FORMAT),
+            details
+        )?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        info!("Writing transcript to {} (session {} {})", path, session_id, action);
+        Ok(Self {
+            writer,
+            session_id,
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// 本次写入的会话 id
+    #[allow(dead_code)]
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+}
+
+/// 文稿中最后一个会话头的会话 id
+fn last_session_id(content: &str) -> Option<String> {
+    content
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix(SESSION_HEADER_PREFIX))
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(str::to_string)
+}
+
+/// 文稿中时间戳的格式
+pub const TRANSCRIPT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// 按文稿格式写出一个事件，由 `TranscriptSink` 和 `Session` 共用
+pub fn write_transcript_event(writer: &mut impl Write, event: &CaptionEvent) -> io::Result<()> {
+    match event {
+        CaptionEvent::Final(caption) => {
+            writeln!(
+                writer,
+                "[{}] {}",
+                caption.timestamp.format(TRANSCRIPT_TIME_FORMAT),
+                caption.labeled_source()
+            )?;
+            if let Some(translation) = &caption.translation {
+                writeln!(writer, "{}", translation)?;
+            }
+            writeln!(writer)
+        }
+        // 每条字幕后已有一个空行，分段处再多空一行
+        // 临时字幕会被之后的最终字幕取代，不写入文稿；文稿只追加写入，修订不回写
+        CaptionEvent::Partial(_) | CaptionEvent::Revision(_) => Ok(()),
+        CaptionEvent::ParagraphBreak => writeln!(writer),
+    }
+}
+
+impl CaptionSink for TranscriptSink {
+    fn emit(&mut self, event: &CaptionEvent) {
+        let mut result = write_transcript_event(&mut self.writer, event).and_then(|_| self.writer.flush());
+        if result.is_ok() && self.last_sync.elapsed() >= TRANSCRIPT_SYNC_INTERVAL {
+            result = self.writer.get_ref().sync_all();
+            self.last_sync = Instant::now();
+        }
+        if let Err(e) = result {
+            error!("Failed to write transcript: {}", e);
+        }
+    }
+
+    fn wants_partial(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for TranscriptSink {
+    fn drop(&mut self) {
+        let result = self.writer.flush().and_then(|_| self.writer.get_ref().sync_all());
+        if let Err(e) = result {
+            error!("Failed to sync transcript: {}", e);
+        }
+    }
+}
+
+/// 以 JSON Lines 格式把事件输出到标准输出，便于其他程序通过管道消费
+pub struct JsonLinesSink;
+
+impl CaptionSink for JsonLinesSink {
+    fn emit(&mut self, event: &CaptionEvent) {
+        match serde_json::to_string(event) {
+            Ok(line) => {
+                let mut stdout = io::stdout().lock();
+                let _ = writeln!(stdout, "{}", line);
+                let _ = stdout.flush();
+            }
+            Err(e) => error!("Failed to serialize caption event: {}", e),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use fifo::FifoSink;
+
+#[cfg(unix)]
+mod fifo {
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::fs::FileTypeExt;
+    use std::thread;
+    use std::time::Duration;
+    use crossbeam_channel::{Sender, TrySendError};
+    use log::{info, warn};
+
+    use super::{CaptionEvent, CaptionSink, TRANSCRIPT_TIME_FORMAT};
+
+    /// 读端未连接时最多缓存的行数，超出后丢弃新字幕，避免无人读取时内存无限增长
+    const MAX_PENDING_LINES: usize = 256;
+    /// 打开或写入失败后重试前的等待时间
+    const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+    /// 把最终字幕按 `format` 逐行写入命名管道（FIFO），供读取 FIFO 的本地字幕程序使用。
+    ///
+    /// 打开 FIFO 的写端会一直阻塞到有读端连接，因此由后台线程负责打开和写入：
+    /// 读端连接前的字幕先缓存起来，读端断开后重新等待连接，断开时未写完的那一行在重连后补发。
+    /// `format` 中的 `{time}`、`{source}`、`{translation}`、`{speaker}` 替换为字幕的对应内容，
+    /// 字幕中的换行替换为空格，保证一条字幕占一行
+    pub struct FifoSink {
+        format: String,
+        lines: Sender<String>,
+    }
+
+    impl FifoSink {
+        /// `path` 须是已存在的命名管道（用 `mkfifo` 创建），不会自动创建，以免误写成普通文件
+        pub fn open(path: &str, format: &str) -> io::Result<Self> {
+            if !fs::metadata(path)?.file_type().is_fifo() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a named pipe (create it with mkfifo)", path),
+                ));
+            }
+            info!("Writing captions to FIFO {}", path);
+            let (lines, pending) = crossbeam_channel::bounded::<String>(MAX_PENDING_LINES);
+            let path = path.to_string();
+            thread::spawn(move || {
+                let mut unsent: Option<String> = None;
+                loop {
+                    let mut pipe = match OpenOptions::new().write(true).open(&path) {
+                        Ok(pipe) => pipe,
+                        Err(e) => {
+                            warn!("Failed to open FIFO {}: {}", path, e);
+                            thread::sleep(RETRY_DELAY);
+                            continue;
+                        }
+                    };
+                    info!("FIFO reader connected on {}", path);
+                    loop {
+                        let line = match unsent.take() {
+                            Some(line) => line,
+                            None => match pending.recv() {
+                                Ok(line) => line,
+                                // 输出端已移除
+                                Err(_) => return,
+                            },
+                        };
+                        if let Err(e) = pipe.write_all(line.as_bytes()).and_then(|_| pipe.flush()) {
+                            if e.kind() != io::ErrorKind::BrokenPipe {
+                                warn!("Failed to write to FIFO {}: {}", path, e);
+                                thread::sleep(RETRY_DELAY);
+                            }
+                            info!("FIFO reader on {} disconnected, waiting for it to reconnect", path);
+                            unsent = Some(line);
+                            break;
+                        }
+                    }
+                }
+            });
+            Ok(Self {
+                format: format.to_string(),
+                lines,
+            })
+        }
+    }
+
+    impl CaptionSink for FifoSink {
+        fn emit(&mut self, event: &CaptionEvent) {
+            let CaptionEvent::Final(caption) = event else {
+                return;
+            };
+            let single_line = |text: &str| text.replace(['\r', '\n'], " ");
+            let mut line = self
+                .format
+                .replace("{time}", &caption.timestamp.format(TRANSCRIPT_TIME_FORMAT).to_string())
+                .replace("{speaker}", &caption.speaker.map(|speaker| speaker.to_string()).unwrap_or_default())
+                .replace("{source}", &single_line(&caption.source))
+                .replace("{translation}", &single_line(caption.translation.as_deref().unwrap_or("")));
+            line.push('\n');
+            if let Err(TrySendError::Full(_)) = self.lines.try_send(line) {
+                warn!("FIFO reader is not keeping up, dropping caption");
+            }
+        }
+
+        fn wants_partial(&self) -> bool {
+            false
+        }
+    }
+}
+
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketSink;
+
+#[cfg(feature = "websocket")]
+mod websocket {
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use log::{info, warn};
+    use tungstenite::{Message, WebSocket};
+
+    use super::{CaptionEvent, CaptionSink};
+
+    /// 把事件以 JSON 文本消息广播给所有已连接的 WebSocket 客户端
+    pub struct WebSocketSink {
+        clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+    }
+
+    impl WebSocketSink {
+        /// 在 `addr` 上监听，后台线程负责接受新连接
+        pub fn bind(addr: &str) -> std::io::Result<Self> {
+            let listener = TcpListener::bind(addr)?;
+            info!("WebSocket captions listening on ws://{}", addr);
+            let clients = Arc::new(Mutex::new(Vec::new()));
+            let accepted = Arc::clone(&clients);
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    match tungstenite::accept(stream) {
+                        Ok(socket) => accepted.lock().unwrap().push(socket),
+                        Err(e) => warn!("WebSocket handshake failed: {}", e),
+                    }
+                }
+            });
+            Ok(Self { clients })
+        }
+    }
+
+    impl CaptionSink for WebSocketSink {
+        fn emit(&mut self, event: &CaptionEvent) {
+            let Ok(json) = serde_json::to_string(event) else {
+                return;
+            };
+            // 发送失败的客户端视为已断开，直接移除
+            self.clients
+                .lock()
+                .unwrap()
+                .retain_mut(|socket| socket.send(Message::text(json.clone())).is_ok());
+        }
+    }
+}
+
+#[cfg(feature = "osc")]
+pub use osc::OscSink;
+
+#[cfg(feature = "osc")]
+mod osc {
+    use std::net::UdpSocket;
+    use log::{info, error};
+
+    use super::{CaptionEvent, CaptionSink};
+
+    /// 通过 UDP 发送 OSC 消息 `/caption <原文> <译文>`，供 VJ/直播软件使用
+    pub struct OscSink {
+        socket: UdpSocket,
+    }
+
+    impl OscSink {
+        /// 向 `target`（如 "127.0.0.1:9000"）发送
+        pub fn connect(target: &str) -> std::io::Result<Self> {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            socket.connect(target)?;
+            info!("Sending OSC captions to {}", target);
+            Ok(Self { socket })
+        }
+    }
+
+    impl CaptionSink for OscSink {
+        fn emit(&mut self, event: &CaptionEvent) {
+            let CaptionEvent::Final(caption) = event else {
+                return;
+            };
+            let translation = caption.translation.as_deref().unwrap_or("");
+            let packet = encode_message("/caption", &[&caption.source, translation]);
+            if let Err(e) = self.socket.send(&packet) {
+                error!("Failed to send OSC message: {}", e);
+            }
+        }
+
+        fn wants_partial(&self) -> bool {
+            false
+        }
+    }
+
+    /// 编码一条只含字符串参数的 OSC 消息
+    fn encode_message(address: &str, args: &[&str]) -> Vec<u8> {
+        let mut packet = Vec::new();
+        push_osc_string(&mut packet, address);
+        push_osc_string(&mut packet, &format!(",{}", "s".repeat(args.len())));
+        for arg in args {
+            push_osc_string(&mut packet, arg);
+        }
+        packet
+    }
+
+    /// OSC 字符串以 NUL 结尾，并补齐到 4 字节对齐
+    fn push_osc_string(packet: &mut Vec<u8>, s: &str) {
+        packet.extend_from_slice(s.as_bytes());
+        let padding = 4 - s.len() % 4;
+        packet.extend(std::iter::repeat(0u8).take(padding));
+    }
+}
@@ -0,0 +1,20 @@
+use std::time::Duration;
+
+/// 说话人标注钩子：对每个音频块给出说话人编号，结果附加在字幕上（`Caption::speaker`），
+/// 由终端显示和文稿输出为 "Speaker 1: ..."。
+///
+/// 本项目不做说话人分离，调用方可以接入自己的实现（如基于声纹嵌入的聚类）。
+pub trait SpeakerLabeler: Send {
+    /// `audio` 为 16k 单声道音频块，`start`/`end` 为它在本次会话音频时间轴上的起止位置。
+    /// 无法判断时返回 `None`。
+    fn label(&mut self, audio: &[f32], start: Duration, end: Duration) -> Option<u32>;
+}
+
+/// 默认实现：不标注说话人
+pub struct NoSpeakerLabeler;
+
+impl SpeakerLabeler for NoSpeakerLabeler {
+    fn label(&mut self, _audio: &[f32], _start: Duration, _end: Duration) -> Option<u32> {
+        None
+    }
+}
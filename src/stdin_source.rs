@@ -0,0 +1,159 @@
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use anyhow::{bail, Context};
+use crossbeam_channel::Sender;
+use log::{info, error};
+
+use crate::capture::{audio_resample, downmix_with, peak_normalize, CaptureConfig};
+
+/// 标准输入中 PCM 采样的编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmFormat {
+    /// 32 位小端浮点
+    F32,
+    /// 16 位小端有符号整数
+    I16,
+}
+
+impl PcmFormat {
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            PcmFormat::F32 => 4,
+            PcmFormat::I16 => 2,
+        }
+    }
+}
+
+/// 标准输入音频的格式，对应 `--stdin-format <rate>:<channels>:<fmt>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StdinFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: PcmFormat,
+}
+
+impl StdinFormat {
+    /// 解析 "48000:2:f32" 形式的格式描述，`fmt` 为 f32 或 i16
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let [rate, channels, format] = parts.as_slice() else {
+            bail!("invalid stdin format '{}', expected <rate>:<channels>:<f32|i16>", spec);
+        };
+        let sample_rate: u32 = rate
+            .parse()
+            .with_context(|| format!("invalid sample rate '{}'", rate))?;
+        let channels: u16 = channels
+            .parse()
+            .with_context(|| format!("invalid channel count '{}'", channels))?;
+        let sample_format = match *format {
+            "f32" | "f32le" => PcmFormat::F32,
+            "i16" | "s16le" => PcmFormat::I16,
+            other => bail!("unsupported stdin sample format '{}', expected f32 or i16", other),
+        };
+        if sample_rate == 0 || channels == 0 {
+            bail!("stdin sample rate and channel count must be greater than 0");
+        }
+        Ok(Self {
+            sample_rate,
+            channels,
+            sample_format,
+        })
+    }
+}
+
+/// 从标准输入读取交错排列的原始 PCM（例如 `ffmpeg -f f32le -`），
+/// 混为单声道并重采样后按块送入流水线。
+pub struct StdinSource {
+    /// 标准输入已读到结尾
+    finished: Arc<AtomicBool>,
+}
+
+impl StdinSource {
+    pub fn start(format: StdinFormat, audio_sender: Sender<Vec<f32>>, config: CaptureConfig) -> Self {
+        info!("Reading {:?} PCM from stdin", format);
+        let finished = Arc::new(AtomicBool::new(false));
+        let reader_finished = Arc::clone(&finished);
+        thread::spawn(move || {
+            if let Err(e) = read_loop(format, &audio_sender, &config) {
+                error!("Failed to read audio from stdin: {:#}", e);
+            }
+            info!("Stdin audio finished");
+            reader_finished.store(true, Ordering::SeqCst);
+        });
+        Self { finished }
+    }
+
+    /// 标准输入是否已结束（其中的音频已全部送出）
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+}
+
+/// 每次读取 `chunk_secs` 长度的音频，解码、混音、重采样后发送
+fn read_loop(format: StdinFormat, audio_sender: &Sender<Vec<f32>>, config: &CaptureConfig) -> anyhow::Result<()> {
+    let frame_bytes = format.sample_format.bytes_per_sample() * format.channels as usize;
+    let frames_per_chunk = ((format.sample_rate as f64 * config.chunk_secs) as usize).max(1);
+    let mut buffer = vec![0u8; frames_per_chunk * frame_bytes];
+    let mut stdin = io::stdin().lock();
+
+    loop {
+        let filled = read_full(&mut stdin, &mut buffer)?;
+        // 丢弃末尾不足一帧的字节
+        let usable = filled / frame_bytes * frame_bytes;
+        if usable > 0 {
+            let samples = decode(&buffer[..usable], format.sample_format);
+            let mut mono = downmix_with(samples, format.channels as usize, config.mix);
+            if let Some(tap) = &config.tap {
+                let tapped = if format.sample_rate != tap.sample_rate {
+                    audio_resample(&mono, format.sample_rate, tap.sample_rate, config.resample_quality)
+                } else {
+                    mono.clone()
+                };
+                let _ = tap.sender.send(tapped);
+            }
+            if let Some(normalize) = &config.peak_normalize {
+                peak_normalize(&mut mono, normalize);
+            }
+            let chunk = if format.sample_rate != config.target_sample_rate {
+                audio_resample(&mono, format.sample_rate, config.target_sample_rate, config.resample_quality)
+            } else {
+                mono
+            };
+            if audio_sender.send(chunk).is_err() {
+                return Ok(());
+            }
+        }
+        if filled < buffer.len() {
+            return Ok(());
+        }
+    }
+}
+
+/// 尽量读满 `buf`，只有遇到 EOF 时才返回不足的长度
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn decode(bytes: &[u8], format: PcmFormat) -> Vec<f32> {
+    match format {
+        PcmFormat::F32 => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        PcmFormat::I16 => bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+    }
+}
@@ -0,0 +1,279 @@
+use log::debug;
+
+use crate::transcribe::{Transcription, Whisper};
+
+/// 滑动窗口转录的参数
+#[derive(Debug, Clone, Copy)]
+pub struct SlidingWindowConfig {
+    /// 滚动缓冲区的最大长度（秒），超过后把剩余文本全部定稿并清空缓冲区
+    pub window_secs: f64,
+    /// 每累计多长的新音频（秒）就重新转录一次整个缓冲区
+    pub hop_secs: f64,
+    /// 一个词需要在连续多少次转录中保持不变才定稿。越大闪烁越少，但定稿越晚；
+    /// 默认 2，即相邻两次结果一致即定稿
+    pub stability: usize,
+    /// 一个词保持不变超过该时长（秒，按音频计）后，即使未达到 `stability` 次也定稿，
+    /// 避免 hop 较长时字幕迟迟不出。`None` 表示不设超时
+    pub stable_timeout_secs: Option<f64>,
+}
+
+impl Default for SlidingWindowConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 10.0,
+            hop_secs: 1.0,
+            stability: 2,
+            stable_timeout_secs: None,
+        }
+    }
+}
+
+/// 一次重新转录的结果
+#[derive(Debug, Clone, Default)]
+pub struct WindowUpdate {
+    /// 本次新定稿的文本及其片段编号，没有新定稿内容时为 `None`
+    pub finalized: Option<(u64, Transcription)>,
+    /// 尚未稳定的尾部文本，可能在下一次转录中改变
+    pub partial: String,
+    /// 本次转录中被改正的已定稿片段
+    pub revisions: Vec<WindowRevision>,
+}
+
+/// 已定稿片段的改正：之后覆盖同一段音频的转录把其中的词稳定地改成了别的词
+#[derive(Debug, Clone)]
+pub struct WindowRevision {
+    /// 被改正片段定稿时的编号，见 `WindowUpdate::finalized`
+    pub segment: u64,
+    pub transcription: Transcription,
+}
+
+/// 当前缓冲区中一个已定稿的片段
+struct CommittedSegment {
+    id: u64,
+    /// 在缓冲区转录结果中的单词范围
+    start: usize,
+    words: Vec<String>,
+}
+
+/// 上一次转录中的一个词及其稳定情况
+struct SeenWord {
+    word: String,
+    /// 连续多少次转录中该位置（连同之前的所有词）保持不变
+    count: usize,
+    /// 该词首次以当前形式出现时的音频位置（采样点）
+    since: usize,
+}
+
+/// 低延迟流式转录：维护一个滚动的音频缓冲区，每隔一个 hop 重新转录整个缓冲区。
+///
+/// 采用 "local agreement" 策略：在连续 `stability` 次转录中保持不变（或不变超过
+/// `stable_timeout_secs`）的前缀视为已稳定，作为最终字幕输出，其余部分作为临时字幕。
+/// 缓冲区达到窗口长度时，把剩余文本全部定稿并从头开始。
+pub struct SlidingWindowTranscriber {
+    window_samples: usize,
+    hop_samples: usize,
+    stability: usize,
+    timeout_samples: Option<usize>,
+    buffer: Vec<f32>,
+    /// 上次转录以来新到达的采样点数
+    pending: usize,
+    /// 累计收到的采样点数，作为稳定超时的时钟
+    position: usize,
+    /// 上一次转录得到的单词
+    previous: Vec<SeenWord>,
+    /// 当前缓冲区的转录结果中已定稿的单词数
+    committed: usize,
+    /// 当前缓冲区中已定稿的片段，用于发现之后的改正
+    segments: Vec<CommittedSegment>,
+    /// 下一个定稿片段的编号
+    next_segment: u64,
+}
+
+impl SlidingWindowTranscriber {
+    pub fn new(config: SlidingWindowConfig) -> Self {
+        let hop_samples = ((config.hop_secs * 16_000.0) as usize).max(1);
+        let window_samples = ((config.window_secs * 16_000.0) as usize).max(hop_samples);
+        Self {
+            window_samples,
+            hop_samples,
+            stability: config.stability.max(1),
+            timeout_samples: config.stable_timeout_secs.map(|secs| (secs * 16_000.0) as usize),
+            buffer: Vec::with_capacity(window_samples),
+            pending: 0,
+            position: 0,
+            previous: Vec::new(),
+            committed: 0,
+            segments: Vec::new(),
+            next_segment: 0,
+        }
+    }
+
+    /// 追加 16k 单声道音频。累计满一个 hop 后用 `whisper` 重新转录缓冲区并返回更新，
+    /// 否则返回 `None`。转录失败时同样返回 `None`。
+    pub fn push(&mut self, whisper: &mut Whisper, samples: &[f32]) -> Option<WindowUpdate> {
+        self.buffer.extend_from_slice(samples);
+        self.pending += samples.len();
+        self.position += samples.len();
+        if self.pending < self.hop_samples {
+            return None;
+        }
+        self.pending = 0;
+
+        let transcription = whisper.transcribe(self.buffer.clone())?;
+        let words: Vec<String> = transcription
+            .text
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+
+        let window_full = self.buffer.len() >= self.window_samples;
+        let seen = self.track(&words);
+        let stable = if window_full {
+            words.len()
+        } else {
+            seen.iter().take_while(|w| self.is_stable(w)).count()
+        };
+
+        let revisions = self.revise(&words, &seen, &transcription);
+        let finalized = (stable > self.committed).then(|| {
+            let id = self.next_segment;
+            self.next_segment += 1;
+            self.segments.push(CommittedSegment {
+                id,
+                start: self.committed,
+                words: words[self.committed..stable].to_vec(),
+            });
+            (
+                id,
+                Transcription {
+                    text: words[self.committed..stable].join(" "),
+                    avg_logprob: transcription.avg_logprob,
+                    segments: Vec::new(),
+                    words: Vec::new(),
+                    language: transcription.language.clone(),
+                },
+            )
+        });
+        // 同一窗口内已提交的位置只进不退：新一轮转录的词数变少时也不回退，
+        // 否则已经定稿的词会重新出现在未定稿部分，随后被再次提交
+        self.committed = self.committed.max(stable);
+        let partial = words.get(self.committed..).map(|rest| rest.join(" ")).unwrap_or_default();
+
+        if window_full {
+            debug!("Sliding window full ({} samples), starting a new window", self.buffer.len());
+            self.buffer.clear();
+            self.previous.clear();
+            self.committed = 0;
+            self.segments.clear();
+        } else {
+            self.previous = seen;
+        }
+
+        Some(WindowUpdate {
+            finalized,
+            partial,
+            revisions,
+        })
+    }
+
+    /// 找出本次转录中被改正的已定稿片段，并记下改正后的文本。
+    ///
+    /// 只接受已稳定（见 `is_stable`）的改动，且片段中至少一半的词保持不变：
+    /// 前面多出或少了一个词时，后面的片段整体错位，这种情况不当作改正
+    fn revise(&mut self, words: &[String], seen: &[SeenWord], transcription: &Transcription) -> Vec<WindowRevision> {
+        let mut revisions = Vec::new();
+        for index in 0..self.segments.len() {
+            let segment = &self.segments[index];
+            let range = segment.start..segment.start + segment.words.len();
+            let Some(current) = words.get(range.clone()) else {
+                continue;
+            };
+            let unchanged = segment.words.iter().zip(current).filter(|(a, b)| same_word(a, b)).count();
+            if unchanged == current.len() || unchanged * 2 < current.len() {
+                continue;
+            }
+            if !seen[range].iter().all(|w| self.is_stable(w)) {
+                continue;
+            }
+            debug!("Sliding window revised {:?} to {:?}", segment.words.join(" "), current.join(" "));
+            let segment = &mut self.segments[index];
+            segment.words = current.to_vec();
+            revisions.push(WindowRevision {
+                segment: segment.id,
+                transcription: Transcription {
+                    text: current.join(" "),
+                    avg_logprob: transcription.avg_logprob,
+                    segments: Vec::new(),
+                    words: Vec::new(),
+                    language: transcription.language.clone(),
+                },
+            });
+        }
+        revisions
+    }
+
+    /// 与上一次转录对比，更新每个词连续保持不变的次数与起始位置。
+    /// 只有公共前缀内的词算作不变，前面的词一旦改变，后面的词都重新计数
+    fn track(&self, words: &[String]) -> Vec<SeenWord> {
+        let prefix = self
+            .previous
+            .iter()
+            .zip(words)
+            .take_while(|(seen, word)| same_word(&seen.word, word))
+            .count();
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| match self.previous.get(i) {
+                Some(seen) if i < prefix => SeenWord {
+                    word: word.clone(),
+                    count: seen.count + 1,
+                    since: seen.since,
+                },
+                _ => SeenWord {
+                    word: word.clone(),
+                    count: 1,
+                    since: self.position,
+                },
+            })
+            .collect()
+    }
+
+    fn is_stable(&self, seen: &SeenWord) -> bool {
+        seen.count >= self.stability
+            || self
+                .timeout_samples
+                .is_some_and(|timeout| self.position - seen.since >= timeout)
+    }
+
+    /// 把尚未定稿的文本全部定稿，并清空缓冲区。停止流水线时调用，避免丢失最后几个词。
+    pub fn flush(&mut self, whisper: &mut Whisper) -> Option<Transcription> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let transcription = whisper.transcribe(std::mem::take(&mut self.buffer));
+        let committed = std::mem::take(&mut self.committed);
+        self.previous.clear();
+        self.segments.clear();
+        self.pending = 0;
+
+        let transcription = transcription?;
+        let words: Vec<&str> = transcription.text.split_whitespace().collect();
+        (words.len() > committed).then(|| Transcription {
+            text: words[committed..].join(" "),
+            avg_logprob: transcription.avg_logprob,
+            segments: Vec::new(),
+            words: Vec::new(),
+            language: transcription.language,
+        })
+    }
+}
+
+/// 两个词是否相同，比较时忽略大小写和句末标点
+fn same_word(a: &str, b: &str) -> bool {
+    let normalize = |w: &str| {
+        w.trim_end_matches(['.', ',', '!', '?', ';', ':'])
+            .to_lowercase()
+    };
+    normalize(a) == normalize(b)
+}
@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+use log::info;
+
+/// 降低翻译频率的条件
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleConfig {
+    /// 每隔多少条字幕翻译一条，1 表示每条都翻译
+    pub every: u32,
+    /// 两次翻译之间的最短间隔
+    pub min_interval: Duration,
+    /// 设置后只在转录实时率高于该值（转录跟不上）时降低翻译频率，低于其 80% 后恢复逐条翻译；
+    /// `None` 表示始终降低
+    pub auto_rtf: Option<f64>,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            every: 3,
+            min_interval: Duration::ZERO,
+            auto_rtf: None,
+        }
+    }
+}
+
+/// 翻译限流：性能较弱的机器上翻译跟不上时，每条字幕照常转录显示原文，
+/// 但只隔几条（或隔一段时间）翻译一条，英文字幕保持连续，中文定期更新，而不是两者一起卡顿
+pub struct TranslationThrottle {
+    config: ThrottleConfig,
+    /// 自上次翻译以来跳过的字幕数
+    skipped: u32,
+    last_translated: Option<Instant>,
+    /// 按实时率自动启用时，当前是否在限流
+    active: bool,
+}
+
+impl TranslationThrottle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            config,
+            skipped: 0,
+            last_translated: None,
+            active: config.auto_rtf.is_none(),
+        }
+    }
+
+    /// 根据最新一块的实时率决定这条字幕是否翻译；返回 `false` 时只显示原文
+    pub fn should_translate(&mut self, real_time_factor: f64) -> bool {
+        if let Some(threshold) = self.config.auto_rtf {
+            if !self.active && real_time_factor > threshold {
+                self.active = true;
+                info!("Real-time factor {:.2} above {:.2}, translating less often", real_time_factor, threshold);
+            } else if self.active && real_time_factor < threshold * 0.8 {
+                self.active = false;
+                info!("Real-time factor back to {:.2}, translating every caption", real_time_factor);
+            }
+        }
+        let due = !self.active
+            || (self.skipped + 1 >= self.config.every
+                && self
+                    .last_translated
+                    .is_none_or(|last| last.elapsed() >= self.config.min_interval));
+        if due {
+            self.skipped = 0;
+            self.last_translated = Some(Instant::now());
+        } else {
+            self.skipped += 1;
+        }
+        due
+    }
+}
@@ -1,13 +1,262 @@
-use log::{info, debug, error};
+use std::os::raw::{c_int, c_void};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
 use whisper_rs::{
     FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+    WhisperSysContext, WhisperSysState, WhisperTokenData,
 };
 
+/// 一次转录的结果
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    /// 识别出的文本，各片段以换行分隔
+    pub text: String,
+    /// 各片段平均 token 对数概率的均值，越接近 0 越可信
+    pub avg_logprob: f32,
+    /// Whisper 输出的各个片段；由多次转录拼接而来的结果（如滑动窗口）为空
+    pub segments: Vec<Segment>,
+    /// 逐词结果，仅在启用 `word_timestamps` 时有；时间相对于音频块开头，同样不含拼接的结果
+    pub words: Vec<Word>,
+    /// 本次转录使用的语言代码（如 "en"、"zh"），语言设为 "auto" 时为逐块检测的结果
+    pub language: Option<String>,
+}
+
+impl Transcription {
+    /// 把平均对数概率换算为 0~1 之间的置信度
+    pub fn confidence(&self) -> f32 {
+        self.avg_logprob.exp()
+    }
+
+    /// 把逐词时间由相对音频块开头改为加上 `offset`（音频块在会话时间轴上的起点）
+    pub fn offset_words(&mut self, offset: Duration) {
+        let offset_ms = offset.as_millis() as i64;
+        for word in &mut self.words {
+            word.start_ms += offset_ms;
+            word.end_ms += offset_ms;
+        }
+    }
+}
+
+/// Whisper 输出的一个片段
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub text: String,
+    /// 片段内普通 token 的平均对数概率，片段只含特殊 token 时为 `None`
+    pub avg_logprob: Option<f32>,
+    /// 片段的起止时间，为相对本次转录音频开头的毫秒数（不受 `offset_words` 影响）
+    #[allow(dead_code)]
+    pub start_ms: i64,
+    #[allow(dead_code)]
+    pub end_ms: i64,
+}
+
+impl Segment {
+    /// 片段的置信度（0~1）
+    pub fn confidence(&self) -> Option<f32> {
+        self.avg_logprob.map(f32::exp)
+    }
+}
+
+/// 一个词及其时间范围和概率，由 token 时间戳和 token 概率组合而来
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub text: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    /// 词内各 token 概率的均值（0~1）
+    pub prob: f32,
+}
+
+/// 组合中的词：累积 token 的原始字节（多字节字符可能被拆在两个 token 中）和概率
+struct PendingWord {
+    bytes: Vec<u8>,
+    prob_sum: f32,
+    tokens: usize,
+    t0: i64,
+    t1: i64,
+}
+
+impl PendingWord {
+    /// 最后一个完整字符是否为中日文字符
+    fn ends_with_cjk(&self) -> bool {
+        String::from_utf8_lossy(&self.bytes).chars().last().is_some_and(is_cjk)
+    }
+
+    /// whisper.cpp 的 token 时间以 10 毫秒为单位；只含空白的词丢弃
+    fn finish(self) -> Option<Word> {
+        let text = String::from_utf8_lossy(&self.bytes).trim().to_string();
+        (!text.is_empty()).then(|| Word {
+            text,
+            start_ms: self.t0 * 10,
+            end_ms: self.t1 * 10,
+            prob: self.prob_sum / self.tokens as f32,
+        })
+    }
+}
+
+/// 中日文字符不以空格分词，逐词结果中每个字单独成词
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+/// Whisper 的转录参数，可从 JSON 配置文件读取，未写出的字段取默认值
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WhisperConfig {
+    /// 识别语言，如 "en"；"auto" 表示自动检测
+    pub language: String,
+    /// 推理线程数，`None` 时使用 whisper.cpp 的默认值
+    pub threads: Option<i32>,
+    /// 束搜索宽度，`None` 时使用贪心解码
+    pub beam_size: Option<i32>,
+    /// 解码时的平均对数概率阈值，低于该值会触发温度回退
+    pub logprob_threshold: f32,
+    /// 解码结果的熵阈值，高于该值（重复度高）会触发温度回退
+    pub entropy_threshold: f32,
+    /// 压缩比阈值：整段文本的估计压缩比高于该值（大量重复，如音乐中反复出现的同一句）时丢弃结果。
+    /// `None` 表示不检查。温度回退后仍然重复的输出由它拦下。
+    /// 压缩比是简化 LZ77 的估计值，与 OpenAI Whisper 用 zlib 算出的数值不可直接比较，
+    /// 未经校准前默认关闭，需要时在配置文件中按实际输出调整
+    pub compression_ratio_threshold: Option<f32>,
+    /// 是否抑制非语音 token（音符、括号等），减少 "[Music]" 一类的幻觉输出
+    pub suppress_non_speech: bool,
+    /// 额外需要抑制的 token id，解码时其 logit 被置为负无穷
+    pub suppress_tokens: Vec<i32>,
+    /// 初始提示词，可提供专有名词、领域词汇以提高识别率
+    pub initial_prompt: Option<String>,
+    /// 若设置，转录前对音频块首尾各做该长度（毫秒）的 Hann 淡入淡出，减轻硬切边界的频谱伪影
+    pub edge_fade_ms: Option<u32>,
+    /// 单个音频块的转录时限（秒）。超时后中止该块并跳过，重建状态后继续处理后续音频，
+    /// 避免 GPU 负载过高时一次卡住的转录拖住整条流水线。`None` 表示不限时
+    pub timeout_secs: Option<f32>,
+    /// 是否计算 token 时间戳并输出逐词的时间和概率（`Transcription::words`）
+    pub word_timestamps: bool,
+    /// 单个片段的最大长度（字符数），超出时 whisper.cpp 把它切成多个片段，使字幕每行长度可控。
+    /// 依赖 token 时间戳，设置后会自动计算。`None` 表示不限制（whisper.cpp 的默认行为）
+    pub max_len: Option<i32>,
+    /// 按 `max_len` 切分片段时只在词边界处切开，不把一个词拆到两个片段中
+    pub split_on_word: bool,
+}
+
+impl Default for WhisperConfig {
+    fn default() -> Self {
+        Self {
+            language: "en".to_string(),
+            threads: None,
+            beam_size: None,
+            logprob_threshold: -2.0,
+            entropy_threshold: 2.4,
+            compression_ratio_threshold: None,
+            suppress_non_speech: true,
+            suppress_tokens: Vec::new(),
+            initial_prompt: None,
+            edge_fade_ms: None,
+            timeout_secs: None,
+            word_timestamps: false,
+            max_len: None,
+            split_on_word: false,
+        }
+    }
+}
+
+impl WhisperConfig {
+    /// 从 JSON 文件读取
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read Whisper config {}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("failed to parse Whisper config {}", path))
+    }
+}
+
+/// 根据连续的转录结果判断 Whisper 是否进入异常状态（长时间运行后偶尔出现），
+/// 例如有声音却一直输出空白，或者反复输出同一句话。
+pub struct RecoveryMonitor {
+    /// 连续多少次异常结果后触发恢复
+    threshold: usize,
+    streak: usize,
+    last_text: String,
+}
+
+/// 高于该 RMS 的音频视为有声音，此时输出空白才算异常
+const SPEECH_RMS: f32 = 0.01;
+
+impl RecoveryMonitor {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            streak: 0,
+            last_text: String::new(),
+        }
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// 记录一次转录结果，连续异常次数达到阈值时返回 `true` 并重新计数
+    pub fn observe(&mut self, audio_rms: f32, text: &str) -> bool {
+        let text = text.trim();
+        let blank = text.is_empty() || text == "[BLANK_AUDIO]";
+        // "[Music]"、"(applause)" 等非语音标记在音乐或掌声持续时本就会连续重复，不算异常
+        let marker = (text.starts_with('[') && text.ends_with(']')) || (text.starts_with('(') && text.ends_with(')'));
+        let suspicious = if marker && !blank {
+            false
+        } else if blank {
+            audio_rms >= SPEECH_RMS
+        } else {
+            text == self.last_text
+        };
+        self.last_text = text.to_string();
+        if !suspicious {
+            self.streak = 0;
+            return false;
+        }
+        self.streak += 1;
+        if self.streak >= self.threshold {
+            self.streak = 0;
+            self.last_text.clear();
+            return true;
+        }
+        false
+    }
+}
+
+/// 短于该长度（16k 采样点，100 毫秒）的音频不送去转录，其中不可能有完整的词
+pub const MIN_TRANSCRIBE_SAMPLES: usize = 1_600;
+/// whisper.cpp 拒绝处理短于 1 秒的音频，较短的音频在末尾补静音到该长度（略多于 1 秒，留出取整余量）
+const MIN_WHISPER_SAMPLES: usize = 16_800;
+/// `Whisper::detect_language` 可靠判断语言所需的最短音频（16k 采样点，2 秒）。更短的片段仍能检测，
+/// 但只有一两个词时概率分布很平，容易误判为相近的语言
+#[allow(dead_code)]
+pub const MIN_DETECT_SAMPLES: usize = 32_000;
+
+/// `Whisper::load` 在每种设备上尝试创建模型上下文的次数
+const LOAD_ATTEMPTS: u32 = 3;
+/// 两次尝试之间的等待时间，给驱动或显存释放留出时间
+const LOAD_RETRY_DELAY: Duration = Duration::from_secs(1);
+
 /// Whisper 结构体封装了 Whisper 状态，
 /// 并提供从 WAV 文件转录文本的接口。
 pub struct Whisper {
+    /// 模型上下文，`reset` 时用它重新创建状态；`fork` 出的转录器共用同一个上下文
+    ctx: Arc<WhisperContext>,
     /// Whisper 内部状态，用于执行转录操作
     whisper_state: WhisperState,
+    /// 结束符 token id，id 不小于它的都是特殊 token，不计入置信度
+    token_eot: i32,
+    /// 词表大小，用于校验 `suppress_tokens`
+    n_vocab: c_int,
+    /// 转录参数，每次 `transcribe` 时应用
+    config: WhisperConfig,
+    /// 模型文件路径，`reload` 时重新加载；从内存创建时为 `None`
+    model_path: Option<String>,
+    /// 模型是否运行在 GPU 上
+    use_gpu: bool,
 }
 
 impl Whisper {
@@ -30,12 +279,186 @@ impl Whisper {
             },
         )
         .expect("failed to create WhisperContext");
+        let mut whisper = Self::from_context(ctx);
+        whisper.model_path = Some(whisper_model_path.to_string());
+        whisper
+    }
+
+    /// 加载模型并应用 `config`，失败时返回错误而不是 panic。
+    ///
+    /// 创建模型上下文失败时会重试；启用 `cuda` 时 GPU 上多次失败（如显存不足、驱动异常）后退回 CPU。
+    pub fn load(whisper_model_path: &str, config: &WhisperConfig) -> anyhow::Result<Self> {
+        let (ctx, use_gpu) = load_context(whisper_model_path)?;
+        let state = ctx
+            .create_state()
+            .map_err(|e| anyhow::anyhow!("failed to create Whisper state: {:?}", e))?;
+        let whisper = Self {
+            whisper_state: state,
+            token_eot: ctx.token_eot(),
+            n_vocab: ctx.n_vocab(),
+            config: WhisperConfig::default(),
+            model_path: Some(whisper_model_path.to_string()),
+            use_gpu,
+            ctx: Arc::new(ctx),
+        };
+        Ok(whisper.with_config(config.clone()))
+    }
+
+    /// 加载模型并应用 `config` 中的全部转录参数
+    ///
+    /// # Panics
+    ///
+    /// 与 `new` 相同，创建 WhisperContext 或状态失败时 panic。需要重试和错误处理时请用 `load`
+    #[allow(dead_code)]
+    pub fn from_config(whisper_model_path: &str, config: &WhisperConfig) -> Self {
+        Self::new(whisper_model_path).with_config(config.clone())
+    }
+
+    /// 从内存中的模型数据创建 Whisper 转录器，适合通过 `include_bytes!` 内嵌模型的单文件发布。
+    ///
+    /// # Panics
+    ///
+    /// 如果模型数据无效或创建状态失败，则会直接 panic。
+    #[allow(dead_code)]
+    pub fn from_bytes(model: &[u8]) -> Self {
+        let ctx = WhisperContext::new_from_buffer_with_params(
+            model,
+            WhisperContextParameters {
+                use_gpu: cfg!(feature = "cuda"),
+                flash_attn: false,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create WhisperContext from buffer");
+        Self::from_context(ctx)
+    }
+
+    fn from_context(ctx: WhisperContext) -> Self {
         let state = ctx.create_state().expect("failed to create Whisper state");
         Self {
             whisper_state: state,
+            token_eot: ctx.token_eot(),
+            n_vocab: ctx.n_vocab(),
+            config: WhisperConfig::default(),
+            model_path: None,
+            use_gpu: cfg!(feature = "cuda"),
+            ctx: Arc::new(ctx),
         }
     }
 
+    /// 转录语言设置，"auto" 表示逐块检测
+    pub fn language(&self) -> &str {
+        &self.config.language
+    }
+
+    /// 当前的转录参数，换用其他模型时据此保持相同的设置
+    pub fn config(&self) -> &WhisperConfig {
+        &self.config
+    }
+
+    /// 模型是否运行在 GPU 上；GPU 加载失败退回 CPU 时为 `false`
+    pub fn uses_gpu(&self) -> bool {
+        self.use_gpu
+    }
+
+    /// 模型是否支持多语言；`.en` 结尾的纯英文模型只能转录英文
+    pub fn is_multilingual(&self) -> bool {
+        self.ctx.is_multilingual()
+    }
+
+    /// 丢弃当前状态并从模型上下文重新创建，用于从异常输出中恢复
+    pub fn reset(&mut self) -> anyhow::Result<()> {
+        self.whisper_state = self
+            .ctx
+            .create_state()
+            .map_err(|e| anyhow::anyhow!("failed to recreate Whisper state: {:?}", e))?;
+        Ok(())
+    }
+
+    /// 在同一个模型上下文上新建一个转录状态，得到参数相同、可在另一线程中并行转录的转录器。
+    /// 模型权重只加载一份，每个状态只额外占用解码所需的缓冲区
+    #[allow(dead_code)]
+    pub fn fork(&self) -> anyhow::Result<Self> {
+        let state = self
+            .ctx
+            .create_state()
+            .map_err(|e| anyhow::anyhow!("failed to create Whisper state: {:?}", e))?;
+        Ok(Self {
+            ctx: Arc::clone(&self.ctx),
+            whisper_state: state,
+            token_eot: self.token_eot,
+            n_vocab: self.n_vocab,
+            config: self.config.clone(),
+            model_path: self.model_path.clone(),
+            use_gpu: self.use_gpu,
+        })
+    }
+
+    /// 从模型文件重新加载上下文和状态，保留当前的转录参数。
+    /// 重建状态也无法恢复时使用，例如 GPU 出错后上下文已不可用；加载方式与 `load` 相同
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let path = self
+            .model_path
+            .clone()
+            .context("Whisper was created from memory and cannot be reloaded")?;
+        let reloaded = Self::load(&path, &self.config)?;
+        *self = reloaded;
+        info!("Whisper model {} reloaded", path);
+        Ok(())
+    }
+
+    /// 整体替换转录参数，`suppress_tokens` 会按词表校验
+    pub fn with_config(mut self, config: WhisperConfig) -> Self {
+        let suppress_tokens = config.suppress_tokens.clone();
+        self.config = config;
+        self.with_suppress_tokens(&suppress_tokens)
+    }
+
+    /// 设置解码的平均对数概率阈值（默认 -2.0）
+    #[allow(dead_code)]
+    pub fn with_logprob_threshold(mut self, threshold: f32) -> Self {
+        self.config.logprob_threshold = threshold;
+        self
+    }
+
+    /// 设置解码的熵阈值（默认 2.4，与 whisper.cpp 一致）
+    #[allow(dead_code)]
+    pub fn with_entropy_threshold(mut self, threshold: f32) -> Self {
+        self.config.entropy_threshold = threshold;
+        self
+    }
+
+    /// 设置压缩比阈值（默认 `None`，不检查）
+    #[allow(dead_code)]
+    pub fn with_compression_ratio_threshold(mut self, threshold: Option<f32>) -> Self {
+        self.config.compression_ratio_threshold = threshold;
+        self
+    }
+
+    /// 设置是否抑制非语音 token（默认开启，对应 OpenAI Whisper 的 `suppress_tokens="-1"`）
+    #[allow(dead_code)]
+    pub fn with_suppress_non_speech(mut self, suppress: bool) -> Self {
+        self.config.suppress_non_speech = suppress;
+        self
+    }
+
+    /// 设置额外需要抑制的 token id，超出词表范围的 id 会被忽略
+    #[allow(dead_code)]
+    pub fn with_suppress_tokens(mut self, tokens: &[i32]) -> Self {
+        self.config.suppress_tokens = tokens
+            .iter()
+            .copied()
+            .filter(|&id| {
+                let valid = (0..self.n_vocab).contains(&id);
+                if !valid {
+                    warn!("Ignoring suppress token {} outside vocabulary (n_vocab {})", id, self.n_vocab);
+                }
+                valid
+            })
+            .collect();
+        self
+    }
+
      /// 对音频数据进行转录，并返回识别的文本。
     ///
     /// 如果输入数据的采样率不是 16000Hz，则会自动进行重采样。
@@ -54,36 +477,390 @@ impl Whisper {
         &mut self,
         samples: Vec<f32>,
     ) -> Option<String> {
+        self.transcribe(samples).map(|t| t.text)
+    }
+
+    /// 设置音频块首尾的淡入淡出长度（毫秒），默认关闭
+    pub fn with_edge_fade_ms(mut self, fade_ms: u32) -> Self {
+        self.config.edge_fade_ms = Some(fade_ms);
+        self
+    }
+
+    /// 与 `transcribe_samples` 相同，但同时返回识别结果的置信度
+    ///
+    /// 过短的音频（如静音裁剪或结束时冲刷剩下的零头）直接返回 `None`；不足 1 秒的在末尾补静音后再转录。
+    pub fn transcribe(&mut self, mut samples: Vec<f32>) -> Option<Transcription> {
+        if samples.len() < MIN_TRANSCRIBE_SAMPLES {
+            debug!("Skipping {} ms of audio, too short to transcribe", samples.len() / 16);
+            return None;
+        }
+        let config = &self.config;
+        if let Some(fade_ms) = config.edge_fade_ms {
+            apply_edge_fade(&mut samples, fade_ms);
+        }
+        if samples.len() < MIN_WHISPER_SAMPLES {
+            debug!("Padding {} ms of audio with silence to 1 s", samples.len() / 16);
+            samples.resize(MIN_WHISPER_SAMPLES, 0.0);
+        }
 
         // 配置转录参数
-        let mut params = FullParams::new(SamplingStrategy::default());
+        let strategy = match config.beam_size {
+            Some(beam_size) => SamplingStrategy::BeamSearch {
+                beam_size,
+                patience: -1.0,
+            },
+            None => SamplingStrategy::default(),
+        };
+        let mut params = FullParams::new(strategy);
         params.set_debug_mode(false);
-        params.set_language(Some("en")); // 自动检测语言
+        params.set_language(Some(&config.language)); // "auto" 表示自动检测语言
+        if let Some(threads) = config.threads {
+            params.set_n_threads(threads);
+        }
+        if let Some(prompt) = &config.initial_prompt {
+            params.set_initial_prompt(prompt);
+        }
         params.set_print_progress(false);
         params.set_print_realtime(false);
         params.set_print_special(false);
         params.set_print_timestamps(false);
-        params.set_logprob_thold(-2.0);
+        params.set_logprob_thold(config.logprob_threshold);
+        params.set_entropy_thold(config.entropy_threshold);
         params.set_temperature(0.0);
+        params.set_suppress_blank(true);
+        params.set_suppress_nst(config.suppress_non_speech);
+        // whisper.cpp 只在计算 token 时间戳时才按 `max_len` 切分片段
+        params.set_token_timestamps(config.word_timestamps || config.max_len.is_some());
+        if let Some(max_len) = config.max_len {
+            params.set_max_len(max_len);
+        }
+        params.set_split_on_word(config.split_on_word);
+        if !config.suppress_tokens.is_empty() {
+            // SAFETY: 回调只读取 `suppress_tokens`，它由 `self` 持有，
+            // 在下面的 `full` 调用期间不会被移动或修改
+            unsafe {
+                params.set_filter_logits_callback(Some(suppress_tokens_callback));
+                params.set_filter_logits_callback_user_data(
+                    &config.suppress_tokens as *const Vec<c_int> as *mut c_void,
+                );
+            }
+        }
+        // 设置了时限时，whisper.cpp 在编码器计算和每步解码之间检查是否超时，超时则中止
+        let deadline = config
+            .timeout_secs
+            .map(|secs| Instant::now() + Duration::from_secs_f32(secs));
+        if let Some(deadline) = &deadline {
+            // SAFETY: 回调只读取 `deadline`，它在下面的 `full` 调用结束前一直有效
+            unsafe {
+                params.set_abort_callback(Some(abort_after_deadline));
+                params.set_abort_callback_user_data(deadline as *const Instant as *mut c_void);
+            }
+        }
         // 执行转录
-        self.whisper_state
-            .full(params, &samples)
-            .expect("transcription failed");
+        if let Err(e) = self.whisper_state.full(params, &samples) {
+            match deadline {
+                Some(deadline) if Instant::now() >= deadline => warn!(
+                    "Transcription exceeded the {:.1}s deadline, skipping chunk",
+                    self.config.timeout_secs.unwrap_or_default()
+                ),
+                _ => error!("Transcription failed: {:?}", e),
+            }
+            // 中止或失败后状态可能不完整，重建后再处理下一块；重建失败时重新加载模型
+            if let Err(e) = self.reset() {
+                error!("{:#}", e);
+                if let Err(e) = self.reload() {
+                    error!("{:#}", e);
+                }
+            }
+            return None;
+        }
     
         // 收集识别结果
         let mut result = String::new();
-        let num_segments = self
-            .whisper_state
-            .full_n_segments()
-            .expect("Failed to get number of segments");
+        let mut segment_logprobs = Vec::new();
+        let mut segments = Vec::new();
+        let mut words = Vec::new();
+        let num_segments = match self.whisper_state.full_n_segments() {
+            Ok(n) => n,
+            Err(e) => {
+                error!("Failed to get number of segments: {:?}", e);
+                return None;
+            }
+        };
         for i in 0..num_segments {
+            let logprob = self.segment_avg_logprob(i);
+            if let Some(logprob) = logprob {
+                segment_logprobs.push(logprob);
+            }
             if let Ok(segment_text) = self.whisper_state.full_get_segment_text_lossy(i) {
                 result.push_str(&segment_text);
                 result.push('\n');
+                segments.push(Segment {
+                    text: segment_text,
+                    avg_logprob: logprob,
+                    start_ms: self.whisper_state.full_get_segment_t0(i).unwrap_or(0) * 10,
+                    end_ms: self.whisper_state.full_get_segment_t1(i).unwrap_or(0) * 10,
+                });
+            }
+            if self.config.word_timestamps {
+                self.segment_words(i, &mut words);
+            }
+        }
+        let avg_logprob = if segment_logprobs.is_empty() {
+            0.0
+        } else {
+            segment_logprobs.iter().sum::<f32>() / segment_logprobs.len() as f32
+        };
+        debug!("Transcribed {} segments, avg logprob {:.3}", num_segments, avg_logprob);
+        if let Some(threshold) = self.config.compression_ratio_threshold {
+            let ratio = compression_ratio(result.trim());
+            if ratio > threshold {
+                debug!(
+                    "Rejected repetitive transcription (compression ratio {:.2} > {:.2}): {:?}",
+                    ratio,
+                    threshold,
+                    result.trim()
+                );
+                result.clear();
+                segments.clear();
+                words.clear();
+            }
+        }
+        let language = self
+            .whisper_state
+            .full_lang_id_from_state()
+            .ok()
+            .and_then(whisper_rs::get_lang_str)
+            .map(str::to_string);
+        Some(Transcription {
+            text: result,
+            avg_logprob,
+            segments,
+            words,
+            language,
+        })
+    }
+
+    /// 只运行编码器和语言检测，不做完整转录，返回概率最高的语言代码及其概率，用于按语言分流前的快速探测。
+    ///
+    /// 只看前 30 秒的音频；至少需要 `MIN_DETECT_SAMPLES`（2 秒）的语音结果才可靠，
+    /// 短于 `MIN_TRANSCRIBE_SAMPLES` 的音频直接返回错误。纯英文（`.en`）模型不支持语言检测
+    #[allow(dead_code)]
+    pub fn detect_language(&mut self, samples: &[f32]) -> anyhow::Result<(String, f32)> {
+        anyhow::ensure!(
+            samples.len() >= MIN_TRANSCRIBE_SAMPLES,
+            "{} ms of audio is too short to detect its language",
+            samples.len() / 16
+        );
+        anyhow::ensure!(self.is_multilingual(), "English-only models cannot detect the language");
+        let threads = self
+            .config
+            .threads
+            .map_or_else(|| thread::available_parallelism().map_or(1, |n| n.get().min(4)), |n| n.max(1) as usize);
+        let mut padded;
+        let samples = if samples.len() < MIN_WHISPER_SAMPLES {
+            padded = samples.to_vec();
+            padded.resize(MIN_WHISPER_SAMPLES, 0.0);
+            &padded[..]
+        } else {
+            samples
+        };
+        self.whisper_state
+            .pcm_to_mel(samples, threads)
+            .map_err(|e| anyhow::anyhow!("failed to compute the spectrogram: {:?}", e))?;
+        let (id, probabilities) = self
+            .whisper_state
+            .lang_detect(0, threads)
+            .map_err(|e| anyhow::anyhow!("language detection failed: {:?}", e))?;
+        let language = whisper_rs::get_lang_str(id).context("language detection returned an unknown language id")?;
+        let probability = probabilities.get(id as usize).copied().unwrap_or_default();
+        debug!("Detected language {} (p={:.2})", language, probability);
+        Ok((language.to_string(), probability))
+    }
+
+    /// 把一个片段的普通 token 组合成词，追加到 `words`：以空白开头的 token 开始一个新词，
+    /// 中日文字符各自成词
+    fn segment_words(&self, segment: i32, words: &mut Vec<Word>) {
+        let Ok(n_tokens) = self.whisper_state.full_n_tokens(segment) else {
+            return;
+        };
+        let mut pending: Option<PendingWord> = None;
+        for j in 0..n_tokens {
+            let (Ok(data), Ok(bytes)) = (
+                self.whisper_state.full_get_token_data(segment, j),
+                self.whisper_state.full_get_token_bytes(segment, j),
+            ) else {
+                continue;
+            };
+            if data.id >= self.token_eot {
+                continue;
             }
+            // 不完整的 UTF-8（多字节字符的一部分）总是接在当前词后面
+            let starts_word = std::str::from_utf8(&bytes).is_ok_and(|text| {
+                text.starts_with(char::is_whitespace)
+                    || text.chars().next().is_some_and(is_cjk)
+                    || pending.as_ref().is_some_and(PendingWord::ends_with_cjk)
+            });
+            if starts_word {
+                words.extend(pending.take().and_then(PendingWord::finish));
+            }
+            let word = pending.get_or_insert_with(|| PendingWord {
+                bytes: Vec::new(),
+                prob_sum: 0.0,
+                tokens: 0,
+                t0: data.t0,
+                t1: data.t1,
+            });
+            word.bytes.extend_from_slice(&bytes);
+            word.prob_sum += data.p;
+            word.tokens += 1;
+            word.t1 = data.t1;
         }
-        Some(result)
+        words.extend(pending.and_then(PendingWord::finish));
+    }
+
+    /// 计算一个片段内普通 token 的平均对数概率，没有普通 token 时返回 `None`
+    fn segment_avg_logprob(&self, segment: i32) -> Option<f32> {
+        let n_tokens = self.whisper_state.full_n_tokens(segment).ok()?;
+        let mut sum = 0.0;
+        let mut count = 0;
+        for j in 0..n_tokens {
+            if let Ok(data) = self.whisper_state.full_get_token_data(segment, j) {
+                if data.id < self.token_eot {
+                    sum += data.plog;
+                    count += 1;
+                }
+            }
+        }
+        (count > 0).then(|| sum / count as f32)
     }
     
 }
 
+/// 在指定设备上创建模型上下文
+fn create_context(path: &str, use_gpu: bool) -> anyhow::Result<WhisperContext> {
+    WhisperContext::new_with_params(
+        path,
+        WhisperContextParameters {
+            use_gpu,
+            flash_attn: false,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| anyhow::anyhow!("failed to load Whisper model {}: {:?}", path, e))
+}
+
+/// 创建模型上下文，每种设备最多尝试 `LOAD_ATTEMPTS` 次；启用 `cuda` 时先试 GPU，再退回 CPU。
+/// 返回上下文及其是否运行在 GPU 上
+fn load_context(path: &str) -> anyhow::Result<(WhisperContext, bool)> {
+    if !Path::new(path).exists() {
+        anyhow::bail!("Whisper model {} does not exist", path);
+    }
+    let devices: &[bool] = if cfg!(feature = "cuda") { &[true, false] } else { &[false] };
+    let mut last_error = None;
+    for &use_gpu in devices {
+        let device = if use_gpu { "GPU" } else { "CPU" };
+        for attempt in 1..=LOAD_ATTEMPTS {
+            match create_context(path, use_gpu) {
+                Ok(ctx) => return Ok((ctx, use_gpu)),
+                Err(e) => {
+                    warn!("{:#} on {} (attempt {}/{})", e, device, attempt, LOAD_ATTEMPTS);
+                    last_error = Some(e);
+                }
+            }
+            if attempt < LOAD_ATTEMPTS {
+                thread::sleep(LOAD_RETRY_DELAY);
+            }
+        }
+        if use_gpu {
+            warn!("Could not load Whisper on the GPU, falling back to CPU");
+        }
+    }
+    Err(last_error.expect("at least one load attempt"))
+}
+
+/// whisper.cpp 支持的全部语言，按语言 id 排列，每项为（语言代码, 英文名称）
+pub fn supported_languages() -> Vec<(&'static str, &'static str)> {
+    (0..=whisper_rs::get_lang_max_id())
+        .filter_map(|id| Some((whisper_rs::get_lang_str(id)?, whisper_rs::get_lang_str_full(id)?)))
+        .collect()
+}
+
+/// `language` 是否是可用的转录语言设置：whisper.cpp 支持的语言代码，或 "auto"
+pub fn is_supported_language(language: &str) -> bool {
+    language == "auto" || whisper_rs::get_lang_id(language).is_some()
+}
+
+/// 估计文本的压缩比（原长度 / 压缩后长度），用于识别重复的幻觉输出。
+///
+/// 采用简化的 LZ77：与前文重复至少 `MIN_MATCH` 字节的部分记为一个 3 字节的回指，其余按字面量计。
+/// 正常语句约为 1.0~1.5，同一短语反复出现时迅速升高。
+fn compression_ratio(text: &str) -> f32 {
+    const MIN_MATCH: usize = 4;
+    const MATCH_COST: usize = 3;
+    let bytes = text.as_bytes();
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let mut compressed = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        // 在已出现的内容中找最长的匹配，允许与当前位置重叠（可表示连续重复）
+        let longest = (0..i)
+            .map(|start| {
+                bytes[i..]
+                    .iter()
+                    .zip(&bytes[start..])
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
+        if longest >= MIN_MATCH {
+            compressed += MATCH_COST;
+            i += longest;
+        } else {
+            compressed += 1;
+            i += 1;
+        }
+    }
+    bytes.len() as f32 / compressed as f32
+}
+
+/// 对 16k 音频的首尾各 `fade_ms` 毫秒施加半个 Hann 窗的淡入/淡出。
+/// 淡化长度不超过块长的一半，以免首尾重叠。
+fn apply_edge_fade(samples: &mut [f32], fade_ms: u32) {
+    let fade_len = (16_000 * fade_ms as usize / 1000).min(samples.len() / 2);
+    if fade_len == 0 {
+        return;
+    }
+    let len = samples.len();
+    for i in 0..fade_len {
+        let gain = 0.5 - 0.5 * (std::f32::consts::PI * i as f32 / fade_len as f32).cos();
+        samples[i] *= gain;
+        samples[len - 1 - i] *= gain;
+    }
+}
+
+/// whisper.cpp 的中止回调：`user_data` 指向的截止时间已过时返回 `true`
+unsafe extern "C" fn abort_after_deadline(user_data: *mut c_void) -> bool {
+    Instant::now() >= *(user_data as *const Instant)
+}
+
+/// whisper.cpp 的 logits 过滤回调：把 `user_data` 指向的 token 的 logit 置为负无穷
+unsafe extern "C" fn suppress_tokens_callback(
+    _ctx: *mut WhisperSysContext,
+    _state: *mut WhisperSysState,
+    _tokens: *const WhisperTokenData,
+    _n_tokens: c_int,
+    logits: *mut f32,
+    user_data: *mut c_void,
+) {
+    if logits.is_null() || user_data.is_null() {
+        return;
+    }
+    let suppress_tokens = &*(user_data as *const Vec<c_int>);
+    for &id in suppress_tokens {
+        *logits.add(id as usize) = f32::NEG_INFINITY;
+    }
+}
@@ -1,16 +1,188 @@
-use anyhow::Error as E;
+use std::collections::HashMap;
+use std::path::Path;
+use anyhow::{Context, Error as E};
 use candle_core::{Device, Tensor, DType};
 use candle_nn::{Activation, VarBuilder};
 use candle_transformers::generation::LogitsProcessor;
 use candle_transformers::models::marian::{self, MTModel};
 use tokenizers::Tokenizer;
-use log::{info, debug, error};
+use log::{info, debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::sentence::{DefaultSplitter, SentenceSplitter};
+use crate::translation::{DecodeStats, Translate};
+
+/// 缓存文件格式版本，格式变化时递增
+const CACHE_VERSION: u32 = 1;
+/// 内存中最多缓存的翻译条数
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+/// 分词器编码/解码失败（如输入含有异常的 Unicode 字符）。
+/// 单独区分出来，`translate` 遇到时退回原文而不是报错
+#[derive(Debug)]
+struct TokenizerError(String);
+
+impl std::fmt::Display for TokenizerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tokenizer error: {}", self.0)
+    }
+}
+
+impl std::error::Error for TokenizerError {}
+
+/// 自定义的 logits 处理：每一步采样前调用，参数为已生成的 token（含 decoder 起始 token）
+/// 和当前步的 logits（形状为 `[vocab_size]`），返回处理后的 logits 用于采样
+pub type LogitsHook = Box<dyn FnMut(&[u32], Tensor) -> anyhow::Result<Tensor> + Send>;
+
+fn tokenizer_error(e: tokenizers::Error) -> E {
+    E::new(TokenizerError(e.to_string()))
+}
+
+/// 持久化到磁盘的翻译缓存
+#[derive(Deserialize)]
+struct CacheFile {
+    version: u32,
+    /// 生成该缓存的模型标识，换了模型的缓存不会被复用
+    model: String,
+    entries: HashMap<String, String>,
+}
+
+/// 与 `CacheFile` 格式相同，写入时借用数据，避免复制整个缓存
+#[derive(Serialize)]
+struct CacheFileRef<'a> {
+    version: u32,
+    model: &'a str,
+    entries: &'a HashMap<String, String>,
+}
+
+/// 模型目录中 Hugging Face `config.json` 里与词表、特殊 token 相关的字段
+#[derive(Deserialize)]
+struct HfModelConfig {
+    vocab_size: Option<usize>,
+    decoder_vocab_size: Option<usize>,
+    share_encoder_decoder_embeddings: Option<bool>,
+    decoder_start_token_id: Option<u32>,
+    pad_token_id: Option<u32>,
+    eos_token_id: Option<u32>,
+}
+
+impl HfModelConfig {
+    /// 读取 `config.json`，文件不存在时返回 `None`
+    fn load(path: Option<&Path>) -> anyhow::Result<Option<Self>> {
+        let Some(path) = path.filter(|path| path.is_file()) else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read model config {}", path.display()))?;
+        let config = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse model config {}", path.display()))?;
+        Ok(Some(config))
+    }
+}
+
+/// 解码用到的特殊 token id
+#[derive(Debug, Clone, Copy)]
+struct SpecialTokens {
+    decoder_start: u32,
+    pad: u32,
+    eos: u32,
+}
+
+impl SpecialTokens {
+    /// 依次从模型旁的 `config.json`、中文分词器词表（`<pad>`、`</s>`）中读取，
+    /// 都没有时使用 opus-mt-en-zh 的默认值；并确认它们都在 decoder 词表范围内。
+    fn resolve(from_config: Option<&HfModelConfig>, tokenizer_dec: &Tokenizer, vocab_size: usize) -> anyhow::Result<Self> {
+        let pad = from_config
+            .as_ref()
+            .and_then(|c| c.pad_token_id)
+            .or_else(|| tokenizer_dec.token_to_id("<pad>"))
+            .unwrap_or(65000);
+        let eos = from_config
+            .as_ref()
+            .and_then(|c| c.eos_token_id)
+            .or_else(|| tokenizer_dec.token_to_id("</s>"))
+            .unwrap_or(0);
+        // Marian 以 pad 作为 decoder 的起始 token
+        let decoder_start = from_config
+            .as_ref()
+            .and_then(|c| c.decoder_start_token_id)
+            .unwrap_or(pad);
+
+        for (name, id) in [("decoder_start_token_id", decoder_start), ("pad_token_id", pad), ("eos_token_id", eos)] {
+            if id as usize >= vocab_size {
+                anyhow::bail!(
+                    "{} {} is outside the decoder vocabulary (size {}); check the model's config.json and tokenizer",
+                    name,
+                    id,
+                    vocab_size
+                );
+            }
+        }
+        Ok(Self { decoder_start, pad, eos })
+    }
+}
+
+/// 翻译相关的可调参数
+#[derive(Debug, Clone)]
+pub struct TranslateConfig {
+    /// 字符数少于该值的输入不送入模型，直接原样返回。默认 2，只跳过单个字母或标点；
+    /// 调到 4 可跳过 "uh"、"the" 一类的碎片，但 "No."、"Yes" 这样的短句也会不翻译
+    pub min_chars: usize,
+    /// 每次解码最多生成的 token 数，防止无法收敛时死循环
+    pub max_new_tokens: usize,
+    /// 核采样（top-p）阈值。`None` 时贪心解码（默认），同一输入总得到同一译文；
+    /// 设置后从累计概率达到该值的候选 token 中随机采样，译文更自然但不那么直译，且每次可能不同。
+    /// 采样时不使用翻译缓存
+    pub top_p: Option<f64>,
+    /// 采样温度，只在设置了 `top_p` 时生效
+    pub temperature: f64,
+}
+
+impl Default for TranslateConfig {
+    fn default() -> Self {
+        Self {
+            min_chars: 2,
+            max_new_tokens: 128,
+            top_p: None,
+            temperature: 1.0,
+        }
+    }
+}
+
+/// 一次 encoder 前向的结果及其原文，由 `Translator::encode` 生成
+#[derive(Clone)]
+pub struct EncoderState {
+    text: String,
+    encoder_xs: Tensor,
+}
+
+impl EncoderState {
+    /// 编码的原文，可作为缓存的键
+    #[allow(dead_code)]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
 pub struct Translator {
     model: MTModel,
     config: marian::Config,
     tokenizer: Tokenizer,      // 用于对英文文本进行编码
     tokenizer_dec: Tokenizer,  // 用于对生成的 token 进行解码（中文）
     device: Device,
+    translate_config: TranslateConfig,
+    /// 最近一次 `translate` 的解码统计；直接返回（过短或命中缓存）时为 `None`
+    last_stats: Option<DecodeStats>,
+    /// 原文 -> 译文 的缓存
+    cache: HashMap<String, String>,
+    /// 模型标识（文件名 + 文件大小），用于校验缓存来源
+    model_tag: String,
+    /// 缓存自上次保存后是否有新增
+    cache_dirty: bool,
+    /// 采样前对 logits 的自定义处理，`None` 时直接采样
+    logits_hook: Option<LogitsHook>,
+    /// 已进行的采样解码次数，用作随机数种子，避免每次采样结果相同
+    samples_drawn: u64,
 }
 
 impl Translator {
@@ -22,25 +194,85 @@ impl Translator {
     pub fn new(model_path: &str, en_token: &str, zh_token: &str) -> anyhow::Result<Self> {
         info!("Initializing Translator with model_path: {}, en_token: {}, zh_token: {}", model_path, en_token, zh_token);
 
-        // 选择设备
-        let device = if cfg!(feature = "cuda") {
-            Device::new_cuda(0)?
-        } else {
-            Device::Cpu
-        };
+        // 先确认三个文件都存在，出错时能直接指出是哪一个
+        ensure_file(model_path, "translation model")?;
+        ensure_file(en_token, "English tokenizer")?;
+        ensure_file(zh_token, "Chinese tokenizer")?;
+
+        let device = select_device()?;
 
         // 从 safetensors 文件创建 VarBuilder，注意这里使用了 unsafe，
         // 但其含义只是“零拷贝”映射，不会真正不安全。
-        info!("Creating VarBuilder from model_path...");
+        debug!("Creating VarBuilder from model_path...");
         let vb = unsafe {
-            VarBuilder::from_mmaped_safetensors(&[model_path], DType::F32, &device)?
+            VarBuilder::from_mmaped_safetensors(&[model_path], DType::F32, &device)
+                .with_context(|| format!("failed to load translation model {}", model_path))?
         };
 
+        // 分别加载英文分词器和中文分词器
+        debug!("Loading English tokenizer from file: {}", en_token);
+        let tokenizer = Tokenizer::from_file(en_token)
+            .map_err(E::msg)
+            .with_context(|| format!("failed to load English tokenizer {}", en_token))?;
+        debug!("Loading Chinese tokenizer from file: {}", zh_token);
+        let tokenizer_dec = Tokenizer::from_file(zh_token)
+            .map_err(E::msg)
+            .with_context(|| format!("failed to load Chinese tokenizer {}", zh_token))?;
+
+        let model_tag = model_tag(model_path)?;
+        let config_json = Path::new(model_path).with_file_name("config.json");
+        Self::from_parts(vb, tokenizer, tokenizer_dec, device, model_tag, Some(&config_json))
+    }
+
+    /// 从内存中的 safetensors 模型与分词器 JSON 数据创建 `Translator`，
+    /// 适合通过 `include_bytes!` 内嵌模型的单文件发布。
+    #[allow(dead_code)]
+    pub fn from_bytes(model: Vec<u8>, en_token: &[u8], zh_token: &[u8]) -> anyhow::Result<Self> {
+        info!("Initializing Translator from {} bytes of embedded model data", model.len());
+        let device = select_device()?;
+        let model_tag = format!("embedded:{}", model.len());
+        let vb = VarBuilder::from_buffered_safetensors(model, DType::F32, &device)
+            .context("failed to load embedded translation model")?;
+        let tokenizer = Tokenizer::from_bytes(en_token)
+            .map_err(E::msg)
+            .context("failed to load embedded English tokenizer")?;
+        let tokenizer_dec = Tokenizer::from_bytes(zh_token)
+            .map_err(E::msg)
+            .context("failed to load embedded Chinese tokenizer")?;
+        Self::from_parts(vb, tokenizer, tokenizer_dec, device, model_tag, None)
+    }
+
+    fn from_parts(
+        vb: VarBuilder,
+        tokenizer: Tokenizer,
+        tokenizer_dec: Tokenizer,
+        device: Device,
+        model_tag: String,
+        config_json: Option<&Path>,
+    ) -> anyhow::Result<Self> {
+        let hf_config = HfModelConfig::load(config_json)?;
+        let d_model = 512;
+        let vocab_size = hf_config.as_ref().and_then(|c| c.vocab_size).unwrap_or(65001);
+        let decoder_vocab_size = hf_config
+            .as_ref()
+            .and_then(|c| c.decoder_vocab_size)
+            .unwrap_or(vocab_size);
+        let share_embeddings = hf_config
+            .as_ref()
+            .and_then(|c| c.share_encoder_decoder_embeddings)
+            .unwrap_or(true);
+        check_embeddings(&vb, vocab_size, decoder_vocab_size, share_embeddings, d_model)?;
+        let special = SpecialTokens::resolve(hf_config.as_ref(), &tokenizer_dec, decoder_vocab_size)?;
+        debug!(
+            "Resolved special tokens: decoder_start={}, pad={}, eos={}",
+            special.decoder_start, special.pad, special.eos
+        );
+
         // Marian 配置，可根据您的实际模型配置进行修改
-        // 这里是 Helsinki-NLP/opus-mt-en-zh 的典型参数
+        // 这里是 Helsinki-NLP/opus-mt-en-zh 的典型参数，词表大小与特殊 token 优先取自 config.json
         let config = marian::Config {
-            vocab_size: 65001,
-            decoder_vocab_size: Some(65001),
+            vocab_size,
+            decoder_vocab_size: Some(decoder_vocab_size),
             max_position_embeddings: 512,
             encoder_layers: 6,
             encoder_ffn_dim: 2048,
@@ -51,24 +283,19 @@ impl Translator {
             use_cache: true,
             is_encoder_decoder: true,
             activation_function: Activation::Swish,
-            d_model: 512,
-            decoder_start_token_id: 65000,
+            d_model,
+            decoder_start_token_id: special.decoder_start,
             scale_embedding: true,
-            pad_token_id: 65000,
-            eos_token_id: 0,
-            forced_eos_token_id: 0,
-            share_encoder_decoder_embeddings: true,
+            pad_token_id: special.pad,
+            eos_token_id: special.eos,
+            forced_eos_token_id: special.eos,
+            share_encoder_decoder_embeddings: share_embeddings,
         };
 
-        // 分别加载英文分词器和中文分词器
-        info!("Loading English tokenizer from file: {}", en_token);
-        let tokenizer = Tokenizer::from_file(en_token).map_err(E::msg)?;
-        info!("Loading Chinese tokenizer from file: {}", zh_token);
-        let tokenizer_dec = Tokenizer::from_file(zh_token).map_err(E::msg)?;
-
         // 创建 Marian 模型
-        info!("Creating Marian model...");
-        let model = MTModel::new(&config, vb)?;
+        debug!("Creating Marian model...");
+        let model = MTModel::new(&config, vb)
+            .context("translation model weights do not match the Marian en-zh configuration")?;
 
         info!("Translator initialized successfully.");
         Ok(Self {
@@ -77,31 +304,147 @@ impl Translator {
             tokenizer,
             tokenizer_dec,
             device,
+            translate_config: TranslateConfig::default(),
+            last_stats: None,
+            cache: HashMap::new(),
+            model_tag,
+            cache_dirty: false,
+            logits_hook: None,
+            samples_drawn: 0,
         })
     }
 
-    /// 翻译：若文本是英文，则进行翻译；否则原样返回
-    pub fn translate(&mut self, text: &str) -> anyhow::Result<String> {
+    /// 使用自定义的翻译参数
+    #[allow(dead_code)]
+    pub fn with_config(mut self, translate_config: TranslateConfig) -> Self {
+        self.translate_config = translate_config;
+        self
+    }
+
+    /// 在每一步采样前调用 `hook` 处理 logits，可用于屏蔽或加权特定 token。
+    /// 处理在内置的“第一步不允许 EOS”之后进行；命中缓存的翻译不会经过解码，也就不会调用它
+    #[allow(dead_code)]
+    pub fn with_logits_hook(
+        mut self,
+        hook: impl FnMut(&[u32], Tensor) -> anyhow::Result<Tensor> + Send + 'static,
+    ) -> Self {
+        self.logits_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// 不经过缓存，直接调用模型翻译
+    fn translate_uncached(&mut self, text: &str) -> anyhow::Result<String> {
+        // 预留一个位置给 encoder 端的 eos token
+        let max_tokens = self.config.max_position_embeddings - 1;
+        let mut stats = DecodeStats::default();
+        if self.count_tokens(text)? <= max_tokens {
+            let translation = self.translate_segment(text, &mut stats)?;
+            self.last_stats = Some(stats);
+            return Ok(translation);
+        }
+
+        let pieces = split_to_fit(text, max_tokens, |piece| self.count_tokens(piece))?;
+        debug!("Input exceeds {} tokens, translating in {} pieces", max_tokens, pieces.len());
+        let mut translation = String::new();
+        for piece in &pieces {
+            translation.push_str(&self.translate_segment(piece, &mut stats)?);
+        }
+        self.last_stats = Some(stats);
+        Ok(translation)
+    }
+
+    /// 统计文本经英文分词器编码后的 token 数
+    fn count_tokens(&self, text: &str) -> anyhow::Result<usize> {
+        Ok(self.tokenizer.encode(text, true).map_err(tokenizer_error)?.len())
+    }
+
+    /// 对一段长度合适的文本执行一次完整的 encoder + decoder 翻译，并把解码步数累加到 `stats`
+    fn translate_segment(&mut self, text: &str, stats: &mut DecodeStats) -> anyhow::Result<String> {
+        let state = self.encode(text)?;
+        self.decode_segment(&state, stats)
+    }
 
+    /// 单独执行 encoder，返回可缓存的编码结果，之后可多次交给 `decode_from` 解码。
+    ///
+    /// encoder 是双向的，每个位置的输出都依赖整句，因此编码结果只能在原文完全相同时复用，
+    /// 不能把某个前缀的编码结果用于更长的文本。超出位置编码长度的输入会被截断，
+    /// 需要按句切分的长文本请使用 `translate`。
+    #[allow(dead_code)]
+    pub fn encode(&mut self, text: &str) -> anyhow::Result<EncoderState> {
         let mut tokens = self
             .tokenizer
             .encode(text, /* add_special_tokens = */ true)
-            .map_err(E::msg)?
+            .map_err(tokenizer_error)?
             .get_ids()
             .to_vec();
 
+        // encoder 的位置编码只有 `max_position_embeddings` 个。按句/按词切分后仍超长
+        // （例如一个极长的"单词"）时截断输入，保证位置不越界
+        let max_positions = self.config.max_position_embeddings;
+        if tokens.len() >= max_positions {
+            warn!(
+                "Input has {} tokens, truncating to {} to fit max_position_embeddings: {:?}",
+                tokens.len(),
+                max_positions - 1,
+                text
+            );
+            tokens.truncate(max_positions - 1);
+        }
+
         // 将 encoder 端的 eos token 写入序列
         tokens.push(self.config.eos_token_id);
         let tokens = Tensor::new(tokens.as_slice(), &self.device)?.unsqueeze(0)?;
         let encoder_xs = self.model.encoder().forward(&tokens, /* start_pos = */ 0)?;
+        Ok(EncoderState {
+            text: text.to_string(),
+            encoder_xs,
+        })
+    }
+
+    /// 从 `encode` 得到的编码结果解码出译文，解码统计可通过 `last_stats` 获取。
+    ///
+    /// 解码前后都会清空 decoder 的 KV 缓存：encoder 不保存状态，cross-attention 每步都从
+    /// `state` 重新计算，只有 decoder 的 self-attention 缓存了已生成的 token。
+    /// 因此多个 `EncoderState` 可以交替解码，也可以与 `translate` 混用，不会互相污染；
+    /// 但不要在外部直接操作模型的 KV 缓存。`state` 只能用于产生它的同一个 `Translator`。
+    #[allow(dead_code)]
+    pub fn decode_from(&mut self, state: &EncoderState) -> anyhow::Result<String> {
+        let mut stats = DecodeStats::default();
+        let translation = self.decode_segment(state, &mut stats);
+        self.last_stats = Some(stats);
+        translation
+    }
+
+    /// decoder 端逐 token 解码，并把解码步数累加到 `stats`
+    fn decode_segment(&mut self, state: &EncoderState, stats: &mut DecodeStats) -> anyhow::Result<String> {
+        let text = state.text.as_str();
+        let encoder_xs = &state.encoder_xs;
+        let max_positions = self.config.max_position_embeddings;
+        // 上一次解码中途出错时缓存可能残留，先清空
+        self.model.reset_kv_cache();
 
         // decoder 端逐 token 解码
         let mut token_ids = vec![self.config.decoder_start_token_id];
-        // 随机数种子、其他采样参数在这里指定
-        let mut logits_processor = LogitsProcessor::new(/*seed=*/1337, /*top_k=*/None, /*top_p=*/None);
+        // 默认贪心解码；设置了 `top_p` 时按温度做核采样，每次解码换一个种子
+        let mut logits_processor = match self.translate_config.top_p {
+            Some(top_p) => {
+                self.samples_drawn += 1;
+                LogitsProcessor::new(
+                    /*seed=*/1337 + self.samples_drawn,
+                    /*temperature=*/Some(self.translate_config.temperature),
+                    /*top_p=*/Some(top_p),
+                )
+            }
+            None => LogitsProcessor::new(/*seed=*/1337, /*temperature=*/None, /*top_p=*/None),
+        };
 
-        // 这里设一个最大循环步数，以免无法收敛时死循环。可视需求调整
-        for index in 0..128 {
+        // 这里设一个最大循环步数，以免无法收敛时死循环。可通过 `max_new_tokens` 调整。
+        // 第 index 步送入 decoder 的最大位置为 index，因此步数同时不能超过 decoder 的位置编码数
+        let max_steps = self.translate_config.max_new_tokens.min(max_positions);
+        let mut steps = 0;
+        let mut reached_eos = false;
+        for index in 0..max_steps {
+            steps += 1;
             // decoder 部分：可以只拿最后一个 token 送入，也可以拿整个序列
             // 下面的逻辑是“第一步用全部，后续一步只输入一个新 token”。
             let context_size = if index >= 1 { 1 } else { token_ids.len() };
@@ -109,34 +452,433 @@ impl Translator {
             let input_ids = Tensor::new(&token_ids[start_pos..], &self.device)?.unsqueeze(0)?;
 
             // decoder forward
-            let logits = self.model.decode(&input_ids, &encoder_xs, start_pos)?;
+            let logits = self.model.decode(&input_ids, encoder_xs, start_pos)?;
             // logits shape: [batch=1, seq_len, vocab_size] => 先移除 batch=1
             let logits = logits.squeeze(0)?;
             // 取序列最后一个 step 的 logits
             let logits = logits.get(logits.dim(0)? - 1)?;
 
+            // 第一步不允许直接输出 EOS：至少生成一个真实 token 后才能结束，避免得到空译文
+            let logits = if index == 0 {
+                suppress_token(&logits, self.config.eos_token_id)?
+            } else {
+                logits
+            };
+            let logits = match self.logits_hook.as_mut() {
+                Some(hook) => hook(&token_ids, logits)?,
+                None => logits,
+            };
+
             // 用采样策略拿到下一个 token
             let next_token = logits_processor.sample(&logits)?;
 
-            // 如果是 eos 或 forced_eos，就提前结束
-            if next_token == self.config.eos_token_id
-                || next_token == self.config.forced_eos_token_id
-            {
+            // 模型自然输出 EOS 时结束。`forced_eos_token_id` 表示长度达到上限时强制补上的结束符，
+            // 本模型中它与 `eos_token_id` 同为 0，不能用来判断自然结束
+            if next_token == self.config.eos_token_id {
+                reached_eos = true;
                 break;
             }
 
             token_ids.push(next_token);
         }
 
-        // 用 tokenizer_dec 解码生成后的序列（去掉开头的 decoder_start_token_id）
-        let translation = self
-            .tokenizer_dec
-            .decode(&token_ids[1..], /* skip_special_tokens = */ true)
-            .map_err(E::msg)?;
+        stats.steps += steps;
+        stats.pieces += 1;
+        if reached_eos {
+            debug!("Decoder reached natural EOS after {} steps", steps);
+        } else {
+            // 长度驱动的强制结束：通常意味着译文被截断，或模型陷入了重复输出
+            stats.hit_limit = true;
+            let limit = if max_steps < self.translate_config.max_new_tokens {
+                "max_position_embeddings"
+            } else {
+                "max_new_tokens"
+            };
+            warn!(
+                "Decoder forced EOS at {} ({}), translation may be truncated: {:?}",
+                limit, max_steps, text
+            );
+        }
 
-        // 5. 注意要清空 KV 缓存，避免下次翻译时冲突
+        // 生成结束后清空 KV 缓存，避免下次翻译时冲突；解码中途出错跳过这里时，由下次解码开始时的清空兜底
         self.model.reset_kv_cache();
 
+        // 用 tokenizer_dec 解码生成后的序列，跳过其余特殊 token
+        let leading = [self.config.decoder_start_token_id, self.config.pad_token_id];
+        let tokenizer_dec = &self.tokenizer_dec;
+        decode_generated(&token_ids, &leading, |generated| {
+            tokenizer_dec
+                .decode(generated, /* skip_special_tokens = */ true)
+                .map_err(tokenizer_error)
+        })
+    }
+}
+
+impl Translate for Translator {
+    /// 翻译：若文本是英文，则进行翻译；否则原样返回
+    ///
+    /// - 字符数少于 `min_chars` 的输入直接原样返回；
+    /// - token 数超过 `max_position_embeddings` 的输入会按句切分，逐段翻译后拼接；
+    /// - 分词器无法编码或解码的输入原样返回，不中断字幕输出。
+    fn translate(&mut self, text: &str) -> anyhow::Result<String> {
+        let text = text.trim();
+        self.last_stats = None;
+        if text.chars().count() < self.translate_config.min_chars {
+            debug!("Input shorter than {} chars, passthrough: {:?}", self.translate_config.min_chars, text);
+            return Ok(text.to_string());
+        }
+
+        // 采样的结果每次不同，不读也不写缓存
+        let sampling = self.translate_config.top_p.is_some();
+        if let Some(cached) = self.cache.get(text).filter(|_| !sampling) {
+            debug!("Translation cache hit: {:?}", text);
+            return Ok(cached.clone());
+        }
+        let translation = match passthrough_on_tokenizer_error(self.translate_uncached(text), text)? {
+            Ok(translation) => translation,
+            Err(source) => {
+                self.last_stats = None;
+                return Ok(source);
+            }
+        };
+        if !sampling && self.cache.len() < MAX_CACHE_ENTRIES {
+            self.cache.insert(text.to_string(), translation.clone());
+            self.cache_dirty = true;
+        }
         Ok(translation)
     }
+
+    /// 返回最近一次 `translate` 的解码统计，未调用模型时为 `None`
+    fn last_stats(&self) -> Option<DecodeStats> {
+        self.last_stats
+    }
+
+    /// 从 JSON 文件加载翻译缓存。文件不存在时不做任何事；
+    /// 缓存来自其他模型或格式版本不符时会被忽略。
+    fn load_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        if !Path::new(path).exists() {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read translation cache {}", path))?;
+        let file: CacheFile = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse translation cache {}", path))?;
+        if file.version != CACHE_VERSION || file.model != self.model_tag {
+            warn!(
+                "Ignoring translation cache {} (version {}, model {}), current model is {}",
+                path, file.version, file.model, self.model_tag
+            );
+            return Ok(());
+        }
+        info!("Loaded {} cached translations from {}", file.entries.len(), path);
+        self.cache.extend(file.entries);
+        Ok(())
+    }
+
+    /// 将翻译缓存保存为 JSON 文件
+    fn save_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        let file = CacheFileRef {
+            version: CACHE_VERSION,
+            model: &self.model_tag,
+            entries: &self.cache,
+        };
+        let content = serde_json::to_string(&file)?;
+        std::fs::write(path, content)
+            .with_context(|| format!("failed to write translation cache {}", path))?;
+        self.cache_dirty = false;
+        debug!("Saved {} cached translations to {}", self.cache.len(), path);
+        Ok(())
+    }
+
+    /// 缓存自上次保存后是否有新增条目
+    fn is_cache_dirty(&self) -> bool {
+        self.cache_dirty
+    }
+}
+
+/// 将过长的文本按句子边界切分，保证每段的 token 数（由 `count_tokens` 统计）不超过 `max_tokens`。
+/// 单个句子本身就超长时，再退化为按单词切分；单个单词仍超长时单独成段，由 `encode` 截断。
+fn split_to_fit(
+    text: &str,
+    max_tokens: usize,
+    count_tokens: impl Fn(&str) -> anyhow::Result<usize>,
+) -> anyhow::Result<Vec<String>> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let spans = DefaultSplitter::new().split(text);
+    for sentence in spans.iter().map(|span| span.as_str(text)) {
+        let candidate = if current.is_empty() {
+            sentence.to_string()
+        } else {
+            format!("{} {}", current, sentence)
+        };
+        if count_tokens(&candidate)? <= max_tokens {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if count_tokens(sentence)? <= max_tokens {
+            current = sentence.to_string();
+            continue;
+        }
+        // 单句超长：按单词继续切分
+        for word in sentence.split_whitespace() {
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current, word)
+            };
+            if !current.is_empty() && count_tokens(&candidate)? > max_tokens {
+                pieces.push(std::mem::replace(&mut current, word.to_string()));
+            } else {
+                current = candidate;
+            }
+        }
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    Ok(pieces)
+}
+
+/// 分词器无法处理的输入（如含控制字符等异常 Unicode）退回原文，不中断字幕输出。
+/// 返回 `Ok(Err(原文))` 表示退回了原文，其他错误照常返回
+fn passthrough_on_tokenizer_error(
+    result: anyhow::Result<String>,
+    text: &str,
+) -> anyhow::Result<Result<String, String>> {
+    match result {
+        Ok(translation) => Ok(Ok(translation)),
+        Err(e) if e.is::<TokenizerError>() => {
+            debug!("{}, passthrough: {:?}", e, text);
+            Ok(Err(text.to_string()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// 把 decoder 输出的 token 序列解码成译文。
+///
+/// 去掉开头的 decoder_start_token_id。有的模型在起始符之后还会生成若干起始/填充符，
+/// 分词器未把它们标记为特殊 token 时会混进译文，一并去掉（`leading`）；中间出现的不受影响。
+/// 一个 token 都没有生成（如第一步就结束，或解码步数上限为 0）时得到空译文，不调用 `decode`
+fn decode_generated(
+    token_ids: &[u32],
+    leading: &[u32],
+    decode: impl FnOnce(&[u32]) -> anyhow::Result<String>,
+) -> anyhow::Result<String> {
+    let generated: Vec<u32> = token_ids
+        .iter()
+        .skip(1)
+        .skip_while(|id| leading.contains(id))
+        .copied()
+        .collect();
+    if generated.is_empty() {
+        return Ok(String::new());
+    }
+    decode(&generated)
+}
+
+/// 返回把 `token` 的 logit 置为负无穷后的副本，使其不会被采样
+fn suppress_token(logits: &Tensor, token: u32) -> candle_core::Result<Tensor> {
+    let mut values = logits.to_vec1::<f32>()?;
+    if let Some(value) = values.get_mut(token as usize) {
+        *value = f32::NEG_INFINITY;
+    }
+    Tensor::new(values, logits.device())
+}
+
+/// 确认模型的嵌入层与配置一致。
+///
+/// candle 的 Marian 实现总是让 encoder、decoder 与输出层共用 `model.shared` 这一份嵌入，
+/// 不共享嵌入（或 decoder 词表不同）的模型虽然能加载，译文却是乱码，因此在这里直接报错。
+fn check_embeddings(
+    vb: &VarBuilder,
+    vocab_size: usize,
+    decoder_vocab_size: usize,
+    share_embeddings: bool,
+    d_model: usize,
+) -> anyhow::Result<()> {
+    if !share_embeddings || decoder_vocab_size != vocab_size {
+        anyhow::bail!(
+            "the translation model does not share encoder/decoder embeddings \
+             (share_encoder_decoder_embeddings={}, vocab_size={}, decoder_vocab_size={}); \
+             only Marian models with tied embeddings are supported",
+            share_embeddings,
+            vocab_size,
+            decoder_vocab_size
+        );
+    }
+    let shared = vb
+        .get((vocab_size, d_model), "model.shared.weight")
+        .with_context(|| format!("model.shared.weight does not match vocab_size {} x d_model {}", vocab_size, d_model))?;
+    vb.get((1, decoder_vocab_size), "final_logits_bias")
+        .with_context(|| format!("final_logits_bias does not match decoder_vocab_size {}", decoder_vocab_size))?;
+    // 有的模型文件单独保存了 decoder 的嵌入，此时它必须与共享嵌入完全相同
+    if vb.contains_tensor("model.decoder.embed_tokens.weight") {
+        let decoder = vb
+            .get((vocab_size, d_model), "model.decoder.embed_tokens.weight")
+            .context("model.decoder.embed_tokens.weight does not match the shared embedding shape")?;
+        let max_diff = (decoder - &shared)?.abs()?.max_all()?.to_scalar::<f32>()?;
+        if max_diff > 0.0 {
+            anyhow::bail!(
+                "model.decoder.embed_tokens.weight differs from model.shared.weight (max diff {}); \
+                 the model does not actually share embeddings, which is not supported",
+                max_diff
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 选择设备：启用 cuda feature 时使用第一块 GPU，否则使用 CPU
+fn select_device() -> anyhow::Result<Device> {
+    Ok(if cfg!(feature = "cuda") {
+        Device::new_cuda(0)?
+    } else {
+        Device::Cpu
+    })
+}
+
+/// 确认 `path` 是一个存在的文件，否则返回指明文件用途的错误
+fn ensure_file(path: &str, what: &str) -> anyhow::Result<()> {
+    let path_ref = Path::new(path);
+    if !path_ref.exists() {
+        anyhow::bail!("{} not found: {}", what, path);
+    }
+    if !path_ref.is_file() {
+        anyhow::bail!("{} is not a file: {}", what, path);
+    }
+    Ok(())
+}
+
+/// 以模型文件名和大小作为模型标识
+fn model_tag(model_path: &str) -> anyhow::Result<String> {
+    let len = std::fs::metadata(model_path)
+        .with_context(|| format!("failed to stat model file {}", model_path))?
+        .len();
+    let name = Path::new(model_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(format!("{}:{}", name, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// decoder 起始符，opus-mt 模型中与填充符相同
+    const START: u32 = 65000;
+    /// opus-mt 模型的 EOS
+    const EOS: u32 = 0;
+
+    fn join_ids(ids: &[u32]) -> anyhow::Result<String> {
+        Ok(ids.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+    }
+
+    /// 只认识几个单词、没有 `[UNK]` 的分词器，遇到词表外的字符时编码失败
+    fn strict_tokenizer() -> Tokenizer {
+        use tokenizers::models::wordlevel::WordLevel;
+        use tokenizers::pre_tokenizers::whitespace::Whitespace;
+        let vocab = [("hello".to_string(), 0), ("world".to_string(), 1)].into_iter().collect();
+        let model = WordLevel::builder().vocab(vocab).build().unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Some(Whitespace {}));
+        tokenizer
+    }
+
+    #[test]
+    fn tokenizer_error_falls_back_to_source_text() {
+        let tokenizer = strict_tokenizer();
+        assert!(tokenizer.encode("hello world", true).is_ok());
+
+        let text = "hello \u{7}world";
+        let result = tokenizer
+            .encode(text, true)
+            .map_err(tokenizer_error)
+            .map(|encoding| format!("{} tokens", encoding.len()));
+        assert!(result.is_err());
+        assert_eq!(passthrough_on_tokenizer_error(result, text).unwrap(), Err(text.to_string()));
+    }
+
+    #[test]
+    fn other_errors_are_not_passed_through() {
+        assert_eq!(passthrough_on_tokenizer_error(Ok("你好".to_string()), "hello").unwrap(), Ok("你好".to_string()));
+        assert!(passthrough_on_tokenizer_error(Err(anyhow::anyhow!("decoder failed")), "hello").is_err());
+    }
+
+    /// 按空白切分计数，再加一个 EOS，近似真实分词器的行为
+    fn count_words(text: &str) -> anyhow::Result<usize> {
+        Ok(text.split_whitespace().count() + 1)
+    }
+
+    #[test]
+    fn split_to_fit_keeps_pieces_within_the_limit() {
+        let text = "one two three four. five six seven. eight nine ten eleven twelve thirteen fourteen. fifteen.";
+        for max_tokens in 2..=16 {
+            let pieces = split_to_fit(text, max_tokens, count_words).unwrap();
+            for piece in &pieces {
+                assert!(
+                    count_words(piece).unwrap() <= max_tokens,
+                    "piece {:?} exceeds {} tokens",
+                    piece,
+                    max_tokens
+                );
+            }
+            // 不丢词也不重复
+            let words: Vec<&str> = pieces.iter().flat_map(|piece| piece.split_whitespace()).collect();
+            assert_eq!(words, text.split_whitespace().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn split_to_fit_fills_pieces_up_to_the_limit() {
+        // 两句刚好能放进一段（4 + 3 + 1 = 8），第三句放不下时另起一段
+        let pieces = split_to_fit("a b c d. e f g. h i.", 8, count_words).unwrap();
+        assert_eq!(pieces, vec!["a b c d. e f g.", "h i."]);
+        // 单词本身超长时单独成段
+        let pieces = split_to_fit("a b c d e f", 1, count_words).unwrap();
+        assert_eq!(pieces.len(), 6);
+    }
+
+    #[test]
+    fn suppressed_eos_is_never_sampled_on_the_first_step() {
+        // 短输入时模型第一步最可能直接输出 EOS；压掉后贪心解码会选到次优的真实 token，译文不为空
+        let logits = Tensor::new(&[5.0f32, 1.0, 3.0, -2.0], &Device::Cpu).unwrap();
+        let suppressed = suppress_token(&logits, EOS).unwrap();
+        let values = suppressed.to_vec1::<f32>().unwrap();
+        assert_eq!(values[EOS as usize], f32::NEG_INFINITY);
+        assert_eq!(&values[1..], &[1.0, 3.0, -2.0]);
+
+        let mut greedy = LogitsProcessor::new(1337, None, None);
+        assert_eq!(greedy.sample(&logits).unwrap(), EOS);
+        let next = greedy.sample(&suppressed).unwrap();
+        assert_eq!(next, 2);
+        assert_eq!(decode_generated(&[START, next], &[START], join_ids).unwrap(), "2");
+    }
+
+    #[test]
+    fn suppress_token_ignores_ids_outside_the_vocabulary() {
+        let logits = Tensor::new(&[0.5f32, 1.5], &Device::Cpu).unwrap();
+        let values = suppress_token(&logits, 7).unwrap().to_vec1::<f32>().unwrap();
+        assert_eq!(values, vec![0.5, 1.5]);
+    }
+
+    #[test]
+    fn decode_generated_handles_immediate_eos() {
+        // 第一步就输出 EOS 时序列里只有起始符，得到空译文且不调用分词器
+        let translation = decode_generated(&[START], &[START, START], |_| panic!("decoder should not be called"));
+        assert_eq!(translation.unwrap(), "");
+        // 起始符之后只生成了填充符，同样视为空译文
+        assert_eq!(decode_generated(&[START, START, START], &[START], join_ids).unwrap(), "");
+    }
+
+    #[test]
+    fn decode_generated_strips_only_leading_start_tokens() {
+        let translation = decode_generated(&[START, START, 12, START, 34], &[START], join_ids).unwrap();
+        assert_eq!(translation, format!("12,{},34", START));
+    }
 }
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 最近一次模型翻译的解码统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// 各段解码步数之和
+    pub steps: usize,
+    /// 是否有段落在遇到 EOS 之前就达到了解码步数上限（`max_new_tokens` 或位置编码长度），
+    /// 译文可能被截断
+    pub hit_limit: bool,
+    /// 解码的段数：1 表示整段一次翻译，大于 1 表示输入超出位置编码长度，按句切分后逐段翻译再拼接
+    pub pieces: usize,
+}
+
+/// 翻译后端。流水线只通过该 trait 调用翻译，默认实现是基于 candle 的 Marian 模型
+/// （`translate::Translator`），也可以换成其他后端（如 ONNX Runtime）。
+///
+/// 除 `translate` 外的方法都有默认实现，不支持解码统计或持久化缓存的后端无需实现。
+pub trait Translate: Send {
+    /// 把英文文本翻译为中文
+    fn translate(&mut self, text: &str) -> anyhow::Result<String>;
+
+    /// 按转录检测到的源语言（如 "en"、"zh"）翻译。只支持单一语言方向的后端无需实现，
+    /// 默认忽略语言直接调用 `translate`
+    fn translate_from(&mut self, _language: Option<&str>, text: &str) -> anyhow::Result<String> {
+        self.translate(text)
+    }
+
+    /// 最近一次 `translate` 的解码统计，未调用模型或后端不提供时为 `None`
+    fn last_stats(&self) -> Option<DecodeStats> {
+        None
+    }
+
+    /// 从 `path` 加载持久化的翻译缓存
+    fn load_cache(&mut self, _path: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// 把翻译缓存保存到 `path`
+    fn save_cache(&mut self, _path: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// 缓存自上次保存后是否有新增条目
+    fn is_cache_dirty(&self) -> bool {
+        false
+    }
+}
+
+/// 按源语言分派的翻译器：每种源语言对应一个翻译器（如英文走英译中模型、中文走中译英模型），
+/// 用于中英混说的场景。没有对应翻译器的语言原样返回，流水线此时只显示原文。
+#[allow(dead_code)]
+pub struct TranslationRouter {
+    routes: HashMap<String, Box<dyn Translate>>,
+    /// 最近一次实际调用的翻译器对应的语言
+    last_route: Option<String>,
+}
+
+#[allow(dead_code)]
+impl TranslationRouter {
+    pub fn new() -> Self {
+        Self {
+            routes: HashMap::new(),
+            last_route: None,
+        }
+    }
+
+    /// 为源语言 `language`（Whisper 的语言代码）注册翻译器，同一语言重复注册时替换
+    pub fn with_route(mut self, language: &str, translator: Box<dyn Translate>) -> Self {
+        self.routes.insert(language.to_string(), translator);
+        self
+    }
+}
+
+impl Translate for TranslationRouter {
+    /// 不知道源语言时无从分派，原样返回
+    fn translate(&mut self, text: &str) -> anyhow::Result<String> {
+        self.translate_from(None, text)
+    }
+
+    fn translate_from(&mut self, language: Option<&str>, text: &str) -> anyhow::Result<String> {
+        match language.and_then(|language| self.routes.get_mut(language)) {
+            Some(translator) => {
+                self.last_route = language.map(str::to_string);
+                translator.translate_from(language, text)
+            }
+            None => {
+                self.last_route = None;
+                Ok(text.to_string())
+            }
+        }
+    }
+
+    fn last_stats(&self) -> Option<DecodeStats> {
+        self.routes.get(self.last_route.as_ref()?)?.last_stats()
+    }
+
+    /// 各翻译器分别使用 `<文件名>.<语言>.<扩展名>` 形式的缓存文件，互不覆盖
+    fn load_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        for (language, translator) in &mut self.routes {
+            translator.load_cache(&route_cache_path(path, language))?;
+        }
+        Ok(())
+    }
+
+    fn save_cache(&mut self, path: &str) -> anyhow::Result<()> {
+        for (language, translator) in &mut self.routes {
+            translator.save_cache(&route_cache_path(path, language))?;
+        }
+        Ok(())
+    }
+
+    fn is_cache_dirty(&self) -> bool {
+        self.routes.values().any(|translator| translator.is_cache_dirty())
+    }
+}
+
+/// 在缓存文件名的扩展名前插入语言代码，如 "translation_cache.json" -> "translation_cache.zh.json"
+#[allow(dead_code)]
+fn route_cache_path(path: &str, language: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, language, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, language),
+    };
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
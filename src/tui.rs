@@ -0,0 +1,345 @@
+use std::io::{self, Stdout};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use log::error;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{Frame, Terminal};
+use unicode_width::UnicodeWidthChar;
+
+use crate::pipeline::{PipelineCommand, PipelineState};
+use crate::sink::{Caption, CaptionEvent, CaptionSink, DisplayContent};
+
+/// 历史中最多保留的行数，超出后丢弃最早的行
+const MAX_HISTORY_LINES: usize = 20_000;
+/// 界面刷新（及按键轮询）间隔
+const TICK: Duration = Duration::from_millis(100);
+/// `PgUp`/`PgDn` 一次滚动的行数
+const PAGE_LINES: usize = 10;
+
+/// 全屏终端界面：可回滚的字幕历史 + 状态栏（设备、实时率、队列长度）。
+///
+/// 界面运行在独立线程上，`emit` 只把事件转交过去。按键：
+/// `↑`/`↓`、`PgUp`/`PgDn`、`Home` 回滚，`End`/`G` 回到实时位置，`p`/空格 暂停/恢复，`q` 退出。
+/// 回滚期间新字幕照常进入历史，回到底部即可继续跟随。
+pub struct TuiSink {
+    events: Option<Sender<CaptionEvent>>,
+    ui: Option<JoinHandle<()>>,
+}
+
+impl TuiSink {
+    /// 接管终端并启动界面线程。`state` 用于状态栏和退出，`control` 用于暂停/恢复，
+    /// `content` 决定每条字幕显示原文、译文或两者
+    pub fn start(
+        state: Arc<PipelineState>,
+        control: Sender<PipelineCommand>,
+        content: DisplayContent,
+    ) -> anyhow::Result<Self> {
+        let mut terminal = setup_terminal()?;
+        let (events, receiver) = unbounded();
+        let ui = thread::spawn(move || {
+            let mut app = App::new(state, control, content);
+            if let Err(e) = app.run(&mut terminal, &receiver) {
+                error!("Terminal UI failed: {}", e);
+            }
+            if let Err(e) = restore_terminal(&mut terminal) {
+                error!("Failed to restore terminal: {}", e);
+            }
+        });
+        Ok(Self {
+            events: Some(events),
+            ui: Some(ui),
+        })
+    }
+}
+
+impl CaptionSink for TuiSink {
+    fn emit(&mut self, event: &CaptionEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.send(event.clone());
+        }
+    }
+}
+
+impl Drop for TuiSink {
+    /// 关闭事件通道，等待界面线程恢复终端后退出
+    fn drop(&mut self) {
+        self.events.take();
+        if let Some(ui) = self.ui.take() {
+            let _ = ui.join();
+        }
+    }
+}
+
+type Backend = CrosstermBackend<Stdout>;
+
+fn setup_terminal() -> io::Result<Terminal<Backend>> {
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(io::stdout()))
+}
+
+fn restore_terminal(terminal: &mut Terminal<Backend>) -> io::Result<()> {
+    disable_raw_mode()?;
+    terminal.backend_mut().execute(LeaveAlternateScreen)?;
+    terminal.show_cursor()
+}
+
+/// 界面线程持有的状态
+struct App {
+    state: Arc<PipelineState>,
+    control: Sender<PipelineCommand>,
+    /// 已完成的字幕，按显示行存放
+    history: Vec<Line<'static>>,
+    /// 当前的临时字幕
+    partial: String,
+    /// 最后一条字幕的序号及其在 `history` 末尾占用的行数，收到它的修订时替换
+    last_caption: Option<(u64, usize)>,
+    /// 距底部回滚的（折行后的）行数，0 表示跟随最新字幕
+    scroll_back: usize,
+    /// 最近一次绘制时字幕区的宽度，用于折行
+    width: usize,
+    paused: bool,
+    content: DisplayContent,
+}
+
+impl App {
+    fn new(state: Arc<PipelineState>, control: Sender<PipelineCommand>, content: DisplayContent) -> Self {
+        Self {
+            state,
+            control,
+            history: Vec::new(),
+            partial: String::new(),
+            last_caption: None,
+            scroll_back: 0,
+            width: 80,
+            paused: false,
+            content,
+        }
+    }
+
+    /// 事件通道关闭（流水线结束）或按下 `q` 时返回
+    fn run(&mut self, terminal: &mut Terminal<Backend>, events: &Receiver<CaptionEvent>) -> io::Result<()> {
+        loop {
+            loop {
+                match events.try_recv() {
+                    Ok(event) => self.push(event),
+                    Err(crossbeam_channel::TryRecvError::Empty) => break,
+                    Err(crossbeam_channel::TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key.code) {
+                        self.state.request_shutdown();
+                    }
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, event: CaptionEvent) {
+        match event {
+            CaptionEvent::Final(caption) => {
+                self.partial.clear();
+                self.push_caption(caption);
+            }
+            // 只替换最后一条字幕，更早的字幕之后已有其他内容
+            CaptionEvent::Revision(caption) => {
+                let Some((_, lines)) = self.last_caption.filter(|(index, _)| Some(*index) == caption.index) else {
+                    return;
+                };
+                let removed = self.history.split_off(self.history.len().saturating_sub(lines));
+                if self.scroll_back > 0 {
+                    let rows: usize = removed.iter().map(|line| wrap_line(line, self.width).len()).sum();
+                    self.scroll_back = self.scroll_back.saturating_sub(rows);
+                }
+                self.push_caption(caption);
+            }
+            // 临时字幕只有原文，只显示译文时不显示
+            CaptionEvent::Partial(_) if self.content == DisplayContent::TargetOnly => {}
+            CaptionEvent::Partial(caption) => self.partial = caption.source,
+            CaptionEvent::ParagraphBreak => {
+                self.last_caption = None;
+                self.add_line(Line::from(Span::styled(
+                    "────────────────────────",
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+        }
+    }
+
+    /// 把一条字幕按显示内容加入历史，并记下它占用的行数
+    fn push_caption(&mut self, caption: Caption) {
+        let index = caption.index;
+        let mut lines = Vec::new();
+        let time = Span::styled(
+            format!("{} ", caption.timestamp.format("%H:%M:%S")),
+            Style::default().fg(Color::DarkGray),
+        );
+        let source_style = match caption.confidence {
+            Some(c) if c < 0.5 => Style::default().fg(Color::DarkGray),
+            _ => Style::default().fg(Color::Yellow),
+        };
+        let translation_style = Style::default().fg(Color::Green);
+        let source = caption.labeled_source();
+        let translation = caption.translation.filter(|_| self.content.shows_target());
+        let pinyin = caption.pinyin.filter(|_| translation.is_some());
+        match translation {
+            Some(translation) if !self.content.shows_source(true) => {
+                // 只显示译文：时间和说话人前缀移到译文行
+                let label = caption.speaker.map(|speaker| format!("Speaker {}: ", speaker)).unwrap_or_default();
+                lines.push(Line::from(vec![time, Span::styled(label + &translation, translation_style)]));
+            }
+            translation => {
+                lines.push(Line::from(vec![time, Span::styled(source, source_style)]));
+                if let Some(translation) = translation {
+                    lines.push(Line::from(Span::styled(translation, translation_style)));
+                }
+            }
+        }
+        if let Some(pinyin) = pinyin {
+            lines.push(Line::from(Span::styled(pinyin, Style::default().fg(Color::Gray))));
+        }
+        self.last_caption = index.map(|index| (index, lines.len()));
+        for line in lines {
+            self.add_line(line);
+        }
+    }
+
+    fn add_line(&mut self, line: Line<'static>) {
+        // 回滚时保持视图停在原处
+        if self.scroll_back > 0 {
+            self.scroll_back = self.scroll_back.saturating_add(wrap_line(&line, self.width).len());
+        }
+        self.history.push(line);
+        if self.history.len() > MAX_HISTORY_LINES {
+            let excess = self.history.len() - MAX_HISTORY_LINES;
+            self.history.drain(..excess);
+        }
+    }
+
+    /// 处理按键，返回 `false` 表示退出
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('p') | KeyCode::Char(' ') => {
+                self.paused = !self.paused;
+                let command = if self.paused { PipelineCommand::Pause } else { PipelineCommand::Resume };
+                let _ = self.control.send(command);
+            }
+            KeyCode::Up => self.scroll_back = self.scroll_back.saturating_add(1),
+            KeyCode::Down => self.scroll_back = self.scroll_back.saturating_sub(1),
+            KeyCode::PageUp => self.scroll_back = self.scroll_back.saturating_add(PAGE_LINES),
+            KeyCode::PageDown => self.scroll_back = self.scroll_back.saturating_sub(PAGE_LINES),
+            // 超出历史的部分在绘制时截断
+            KeyCode::Home => self.scroll_back = usize::MAX,
+            KeyCode::End | KeyCode::Char('G') => self.scroll_back = 0,
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let areas = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).split(frame.size());
+        let (captions_area, status_area) = (areas[0], areas[1]);
+
+        // 边框占去两行两列。从最新的一行往回折行，只处理可见范围及其上方的回滚部分
+        self.width = captions_area.width.saturating_sub(2).max(1) as usize;
+        let height = captions_area.height.saturating_sub(2) as usize;
+        let needed = height.saturating_add(self.scroll_back);
+        let partial = (!self.partial.is_empty())
+            .then(|| Line::from(Span::styled(self.partial.clone(), Style::default().fg(Color::DarkGray))));
+        let mut rows = Vec::new();
+        for line in partial.iter().chain(self.history.iter().rev()) {
+            if rows.len() >= needed {
+                break;
+            }
+            rows.extend(wrap_line(line, self.width).into_iter().rev());
+        }
+        self.scroll_back = self.scroll_back.min(rows.len().saturating_sub(height));
+        let mut visible: Vec<Line> = rows.into_iter().skip(self.scroll_back).take(height).collect();
+        visible.reverse();
+        let title = if self.scroll_back > 0 {
+            format!(" Captions (scrolled back {} lines, End to follow) ", self.scroll_back)
+        } else {
+            " Captions ".to_string()
+        };
+        let captions = Paragraph::new(visible)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(captions, captions_area);
+
+        let status = self.state.snapshot();
+        let mode = if self.paused { "PAUSED" } else { "LIVE" };
+        let text = format!(
+            " {} | {} | RTF {:.2} | queue {} | ↑↓ PgUp/PgDn scroll  End follow  p pause  q quit",
+            mode, status.device_name, status.real_time_factor, status.queue_depth
+        );
+        let bar = Paragraph::new(Line::from(text))
+            .style(Style::default().fg(Color::Black).bg(Color::Gray).add_modifier(Modifier::BOLD));
+        frame.render_widget(bar, status_area);
+    }
+}
+
+/// 按显示宽度把一行折成多行，保留各段的样式
+fn wrap_line(line: &Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let mut rows = Vec::new();
+    let mut row: Vec<Span<'static>> = Vec::new();
+    let mut row_width = 0;
+    for span in &line.spans {
+        let mut chunk = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or(0);
+            if row_width + w > width && row_width > 0 {
+                if !chunk.is_empty() {
+                    row.push(Span::styled(std::mem::take(&mut chunk), span.style));
+                }
+                rows.push(Line::from(std::mem::take(&mut row)));
+                row_width = 0;
+            }
+            chunk.push(c);
+            row_width += w;
+        }
+        if !chunk.is_empty() {
+            row.push(Span::styled(chunk, span.style));
+        }
+    }
+    rows.push(Line::from(row));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row_text(row: &Line<'static>) -> String {
+        row.spans.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn wrap_line_never_splits_chinese_characters() {
+        // 奇数列宽时行尾只剩一列，全角字符要整个折到下一行，不能从字节中间切开
+        let line = Line::from(vec![
+            Span::raw("[12:00:01] "),
+            Span::styled("今天我们来讨论一下语音识别的延迟问题", Style::default().fg(Color::Yellow)),
+        ]);
+        let rows = wrap_line(&line, 15);
+        let text: String = rows.iter().map(row_text).collect();
+        assert_eq!(text, "[12:00:01] 今天我们来讨论一下语音识别的延迟问题");
+        for row in &rows {
+            let width: usize = row_text(row).chars().filter_map(|c| c.width()).sum();
+            assert!(width <= 15, "row {:?} is {} columns wide", row_text(row), width);
+        }
+        assert_eq!(row_text(&rows[0]), "[12:00:01] 今天");
+        assert_eq!(row_text(&rows[1]), "我们来讨论一下");
+    }
+}